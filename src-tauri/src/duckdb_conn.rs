@@ -1,19 +1,32 @@
-use std::{collections::HashMap, io::Cursor, path::Path, sync::Mutex, vec::Vec };
+use std::{collections::{hash_map::Entry, HashMap}, io::Cursor, path::Path, sync::{Arc, Mutex, PoisonError}, time::Duration, vec::Vec };
 use once_cell::sync::Lazy;
+use serde::Serialize;
 use tauri::ipc::Response;
-use duckdb::{ arrow::{array::RecordBatch, datatypes::Schema}, types::Value, Arrow, Connection };
+use duckdb::{ arrow::{array::{ArrayRef, RecordBatch}, compute::{can_cast_types, cast}, datatypes::Schema}, types::Value, Arrow, Connection, InterruptHandle };
 use arrow_ipc::{ writer::StreamWriter, };
 
-// Connection pool for multi-database support
-static CONN_HANDLER: Lazy<Mutex<ConnectionHandler>> = Lazy::new(|| Mutex::new(ConnectionHandler::new()));
+// Pool key of the in-memory scratch connection used to query remote sources (httpfs)
+pub const REMOTE_DB_PATH: &str = ":remote:";
+
+// Schema metadata key holding the generated SQL of a response, see `debug_sql_metadata`
+pub const SQL_METADATA_KEY: &str = "sql";
+
+// Schema metadata key holding the resolution (in hours) a resolution query was run with
+pub const RESOLUTION_METADATA_KEY: &str = "resolution";
+
+// Connection pool for multi-database support, each connection locked on its own so queries on different databases
+// run in parallel and a running query can be cancelled
+static CONN_HANDLER: Lazy<ConnectionHandler> = Lazy::new(ConnectionHandler::new);
 
 // serializes result from apache arrow query
 pub fn serialize_recordbatch(rec_batch: Vec<RecordBatch>, schema: Schema) -> Result<Response, String> {
     let mut vec_writer = Cursor::new(Vec::new()); // creates a writer to save the result    
 
+    let schema_ref = Arc::new(schema.clone());
     let mut writer: StreamWriter<_> = StreamWriter::try_new(&mut vec_writer, &schema)
         .map_err(|e| format!("write error: {}", e))?;
     for batch in rec_batch {
+        let batch: RecordBatch = align_recordbatch(batch, &schema_ref)?;
         writer.write(&batch).map_err(|e| format!("write error: {}", e))?;
     }
     writer.finish().map_err(|e| format!("finish error: {}", e))?;
@@ -24,27 +37,245 @@ pub fn serialize_recordbatch(rec_batch: Vec<RecordBatch>, schema: Schema) -> Res
     Ok(response)
 }
 
+// casts a batch to the stream schema when only its column types differ
+// (e.g. DuckDB promoting types across the `UNION ALL` of an empty fallback),
+// any other difference is reported by field name
+fn align_recordbatch(batch: RecordBatch, schema: &Arc<Schema>) -> Result<RecordBatch, String> {
+    if batch.schema().fields() == schema.fields() {
+        return Ok(batch);
+    }
+    if batch.num_columns() != schema.fields().len() {
+        return Err(format!(
+            "schema mismatch: batch has {} columns, expected {}",
+            batch.num_columns(), schema.fields().len()
+        ));
+    }
+
+    let batch_schema = batch.schema();
+    let mut columns: Vec<ArrayRef> = Vec::with_capacity(batch.num_columns());
+    for (field, (batch_field, column)) in schema.fields().iter().zip(batch_schema.fields().iter().zip(batch.columns())) {
+        if field.name() != batch_field.name() {
+            return Err(format!("schema mismatch: expected field '{}', got '{}'", field.name(), batch_field.name()));
+        }
+        if field.data_type() == column.data_type() {
+            columns.push(column.clone());
+        } else if can_cast_types(column.data_type(), field.data_type()) {
+            columns.push(cast(column, field.data_type())
+                .map_err(|e| format!("schema mismatch on field '{}': {}", field.name(), e))?);
+        } else {
+            return Err(format!(
+                "schema mismatch on field '{}': expected {}, got {}",
+                field.name(), field.data_type(), column.data_type()
+            ));
+        }
+    }
+
+    RecordBatch::try_new(schema.clone(), columns)
+        .map_err(|e| format!("schema mismatch: {}", e))
+}
+
+// serializes like `serialize_recordbatch`, additionally attaching key-value pairs as schema metadata
+// (read with `table.schema.metadata.get(key)` on the frontend)
+pub fn serialize_recordbatch_with_metadata(rec_batch: Vec<RecordBatch>, schema: Schema, metadata: HashMap<String, String>) -> Result<Response, String> {
+    if metadata.is_empty() {
+        return serialize_recordbatch(rec_batch, schema);
+    }
+
+    let mut schema_metadata: HashMap<String, String> = schema.metadata().clone();
+    schema_metadata.extend(metadata);
+    let schema: Schema = schema.with_metadata(schema_metadata);
+
+    // the batches have to carry the same schema as the stream
+    let schema_ref = Arc::new(schema.clone());
+    let rec_batch: Vec<RecordBatch> = rec_batch.into_iter()
+        .map(|rb| rb.with_schema(schema_ref.clone()))
+        .collect::<Result<Vec<RecordBatch>, _>>()
+        .map_err(|e| format!("write error: {}", e))?;
+
+    serialize_recordbatch(rec_batch, schema)
+}
+
+// the SQL that produced a result as response metadata, for debugging only: left out in release builds
+pub fn debug_sql_metadata(sql: &str, include_sql: bool) -> HashMap<String, String> {
+    let mut metadata: HashMap<String, String> = HashMap::new();
+    if include_sql && cfg!(debug_assertions) {
+        metadata.insert(SQL_METADATA_KEY.to_string(), sql.to_string());
+    }
+    metadata
+}
 
 // public methods for querying, which use singleton underneath which does not need to be tested
 pub fn run_query_rb(db_path: String, q: String, args: Vec<Value>) -> Result<(Vec<RecordBatch>, Schema), String> {
-    CONN_HANDLER.lock().unwrap().run_query_rb(db_path, q, args)
+    CONN_HANDLER.run_query_rb(db_path, q, args)
 }
 
 pub fn run_query_row<F, T>(db_path: String, q: String, args: Vec<Value>, row_mapper: F) -> Result<Vec<T>, String> 
 where 
     F: FnMut(&duckdb::Row<'_>) -> Result<T, duckdb::Error>
 {
-    CONN_HANDLER.lock().unwrap().run_query_row(db_path, q, args, row_mapper)
+    CONN_HANDLER.run_query_row(db_path, q, args, row_mapper)
+}
+
+// resolves the output schema of a query without executing it
+pub fn describe_query(db_path: String, q: String) -> Result<Vec<QueryColumn>, String> {
+    CONN_HANDLER.describe_query(db_path, q)
 }
 
 // executes multiple queries which take no arguments 
 pub fn execute_batch(db_path: String, q: String) -> Result<(), String> {
-    CONN_HANDLER.lock().unwrap().execute_batch(db_path, q)
+    CONN_HANDLER.execute_batch(db_path, q)
+}
+
+// executes a single statement with arguments, returns the number of changed rows
+pub fn execute(db_path: String, q: String, args: Vec<Value>) -> Result<usize, String> {
+    CONN_HANDLER.execute(db_path, q, args)
+}
+
+// whether a connection to `db_path` is open in the pool, without opening one
+pub fn is_connection_pooled(db_path: &str) -> bool {
+    CONN_HANDLER.db_pool.lock().is_ok_and(|pool| pool.contains_key(&pool_key(db_path)))
+}
+
+// interrupts the statement currently running on the connection of `db_path`, which then fails with an
+// interrupt error; statements queued after it still run. Returns whether anything was running
+pub fn cancel_queries(db_path: &str) -> Result<bool, String> {
+    CONN_HANDLER.cancel_queries(db_path)
+}
+
+// the key of `db_path` in the connection pool: different spellings of one file (relative, `./`, separators
+// and, on case-insensitive platforms, casing) have to share the connection, as a second one can't lock the file.
+// Paths which can't be resolved, e.g. missing files, are kept as is
+fn pool_key(db_path: &str) -> String {
+    if db_path == REMOTE_DB_PATH {
+        return db_path.to_string();
+    }
+    let key: String = std::fs::canonicalize(db_path)
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_else(|_| db_path.to_string());
+
+    if cfg!(any(windows, target_os = "macos")) {
+        key.to_lowercase()
+    } else {
+        key
+    }
+}
+
+// another process (e.g. a Tulipa run) can hold the file lock for a moment after it's done with the database,
+// so opening retries lock errors up to `OPEN_RETRY_COUNT` times, waiting `OPEN_RETRY_DELAY_MS` and doubling it after
+// each attempt (750ms in total). Other errors (missing or corrupt file, ...) fail right away
+const OPEN_RETRY_COUNT: u32 = 4;
+const OPEN_RETRY_DELAY_MS: u64 = 50;
+
+fn open_with_retry<T, F>(mut open: F) -> Result<T, String>
+where
+    F: FnMut() -> Result<T, String>
+{
+    let mut delay: Duration = Duration::from_millis(OPEN_RETRY_DELAY_MS);
+    let mut retries: u32 = 0;
+    loop {
+        match open() {
+            Err(e) if retries < OPEN_RETRY_COUNT && is_transient_lock_error(&e) => {
+                println!("Database is locked, retrying in {:?}: {}", delay, e);
+                std::thread::sleep(delay);
+                delay *= 2;
+                retries += 1;
+            }
+            res => return res,
+        }
+    }
+}
+
+fn is_transient_lock_error(error: &str) -> bool {
+    let error: String = error.to_lowercase();
+    error.contains("could not set lock") || error.contains("conflicting lock")
+}
+
+// number of non-empty statements in `sql`, splitting on semicolons outside of string literals, quoted identifiers,
+// dollar-quoted strings and comments, without handing anything to DuckDB
+fn count_statements(sql: &str) -> usize {
+    let bytes: &[u8] = sql.as_bytes();
+    let mut count: usize = 0;
+    let mut has_content: bool = false;
+    let mut i: usize = 0;
+    while i < bytes.len() {
+        let next: Option<u8> = bytes.get(i + 1).copied();
+        match bytes[i] {
+            quote @ (b'\'' | b'"') => {
+                // a doubled quote just closes and reopens the literal
+                has_content = true;
+                i = skip_past(bytes, i + 1, &[quote]);
+            }
+            b'-' if next == Some(b'-') => i = skip_past(bytes, i + 2, b"\n"),
+            b'/' if next == Some(b'*') => i = skip_past(bytes, i + 2, b"*/"),
+            b'$' => {
+                has_content = true;
+                // `$tag$ ... $tag$` quoting, `$1` is a parameter
+                let tag_end: usize = i + 1 + bytes[i + 1..].iter().take_while(|c| c.is_ascii_alphanumeric() || **c == b'_').count();
+                let is_tag: bool = bytes.get(tag_end) == Some(&b'$') && !next.is_some_and(|c| c.is_ascii_digit());
+                i = if is_tag { skip_past(bytes, tag_end + 1, &bytes[i..=tag_end]) } else { i + 1 };
+            }
+            b';' => {
+                if has_content {
+                    count += 1;
+                    has_content = false;
+                }
+                i += 1;
+            }
+            c => {
+                has_content |= !c.is_ascii_whitespace();
+                i += 1;
+            }
+        }
+    }
+
+    count + usize::from(has_content)
+}
+
+// index right after the first `end` at or after `from`, or the end of `bytes` if it's not closed
+fn skip_past(bytes: &[u8], from: usize, end: &[u8]) -> usize {
+    bytes[from..].windows(end.len())
+        .position(|window| window == end)
+        .map_or(bytes.len(), |pos| from + pos + end.len())
+}
+
+// single output column of a query, as resolved by `describe_query`
+#[derive(Serialize, Debug, PartialEq)]
+pub struct QueryColumn {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
 }
 
 #[derive(Default)]
 struct ConnectionHandler {
-    db_pool: Mutex<HashMap<String, Connection>>,
+    // only locked to look up (or open) a connection, the query then holds the lock of its connection alone
+    db_pool: Mutex<HashMap<String, Arc<Mutex<Connection>>>>,
+    // interrupt handles of the connections with a query running, kept outside the pool lock
+    in_flight: Mutex<HashMap<String, InFlightQueries>>,
+}
+
+struct InFlightQueries {
+    interrupt_handle: Arc<InterruptHandle>,
+    count: usize,
+}
+
+// ends an in-flight query when dropped, so the count also goes down when the query panics
+struct InFlightGuard<'a> {
+    handler: &'a ConnectionHandler,
+    db_path: String,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        // the map is only touched under its lock, so it's still consistent when another thread panicked holding it
+        let mut in_flight = self.handler.in_flight.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Entry::Occupied(mut entry) = in_flight.entry(self.db_path.clone()) {
+            entry.get_mut().count -= 1;
+            if entry.get().count == 0 {
+                entry.remove();
+            }
+        }
+    }
 }
 
 impl ConnectionHandler {
@@ -56,28 +287,64 @@ impl ConnectionHandler {
     where
         F: FnOnce(&Connection) -> Result<T, String> 
     {
+        // the remote scratch connection is not backed by a file
+        let is_remote: bool = db_path == REMOTE_DB_PATH;
+
         // assert path is plausible
-        if !db_path.ends_with(".duckdb") {
+        if !is_remote && !db_path.ends_with(".duckdb") {
             return Err("Database path must end with .duckdb".to_string());
         }
 
-        // Check if we need to create a new connection or use existing one
-        let mut pool = self.db_pool.lock().unwrap();
-        if !pool.contains_key(db_path) {
-            if !Path::new(db_path).exists() {
-                return Err(format!("Error<file not found>` connecting to: '{}'", db_path));
+        // Look up and open the connection through one entry, so the same file can never be opened twice
+        let key: String = pool_key(db_path);
+        let conn: Arc<Mutex<Connection>> = {
+            let mut pool = self.db_pool.lock().map_err(|_| "Connection pool lock poisoned".to_string())?;
+            match pool.entry(key.clone()) {
+                Entry::Occupied(entry) => {
+                    println!("Reusing existing connection for '{}'", db_path);
+                    Arc::clone(entry.get())
+                }
+                Entry::Vacant(entry) => {
+                    let new_conn = if is_remote {
+                        Connection::open_in_memory()
+                            .map_err(|e| format!("Failed to open remote scratch database: {}", e))?
+                    } else {
+                        if !Path::new(db_path).exists() {
+                            return Err(format!("Error<file not found>` connecting to: '{}'", db_path));
+                        }
+                        open_with_retry(|| Connection::open(db_path).map_err(|e| e.to_string()))
+                            .map_err(|e| format!("Failed to open database '{}': {}", db_path, e))?
+                    };
+                    println!("Opened new database succesfully: '{}'", db_path);
+                    Arc::clone(entry.insert(Arc::new(Mutex::new(new_conn))))
+                }
             }
-            let new_conn = Connection::open(&db_path)
-                .map_err(|e| format!("Failed to open database '{}': {}", db_path, e))?;
-            pool.insert(db_path.clone(), new_conn);
-            println!("Opened new database succesfully: '{}'", db_path);
-        } else {
-            println!("Reusing existing connection for '{}'", db_path);
-        }
+        };
 
-        // Always use the connection from the pool
-        let conn: &Connection = pool.get(db_path).expect("Connection should exist in pool");
-        with_conn(conn)
+        // the pool lock is released by now, only queries on this same connection wait here
+        let conn = conn.lock().map_err(|_| format!("Connection lock of '{}' poisoned", db_path))?;
+        let _in_flight: InFlightGuard<'_> = self.start_in_flight(&key, &conn)?;
+        with_conn(&conn)
+    }
+
+    fn start_in_flight(&self, db_path: &str, conn: &Connection) -> Result<InFlightGuard<'_>, String> {
+        let mut in_flight = self.in_flight.lock().map_err(|_| "In-flight query lock poisoned".to_string())?;
+        in_flight.entry(db_path.to_string())
+            .or_insert_with(|| InFlightQueries { interrupt_handle: conn.interrupt_handle(), count: 0 })
+            .count += 1;
+        Ok(InFlightGuard { handler: self, db_path: db_path.to_string() })
+    }
+
+    fn cancel_queries(&self, db_path: &str) -> Result<bool, String> {
+        let in_flight = self.in_flight.lock().map_err(|_| "In-flight query lock poisoned".to_string())?;
+        match in_flight.get(&pool_key(db_path)) {
+            Some(queries) => {
+                println!("Interrupting running query on '{}'", db_path);
+                queries.interrupt_handle.interrupt();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
     }
 
     fn run_query_rb(&self, db_path: String, q: String, args: Vec<Value>) -> Result<(Vec<RecordBatch>, Schema), String> {
@@ -117,6 +384,51 @@ impl ConnectionHandler {
         })
     }
 
+    fn describe_query(&self, db_path: String, q: String) -> Result<Vec<QueryColumn>, String> {
+        println!("\n<<QUERY>>\ndescribing query on db [{}]:\n{}\n", db_path, q);
+        let q: &str = q.trim().trim_end_matches(';');
+        // DuckDB runs all but the last statement of a string while preparing it
+        let statement_count: usize = count_statements(q);
+        if statement_count != 1 {
+            return Err(format!("Expected a single statement to describe, got {}", statement_count));
+        }
+
+        self.fetch_connection(&db_path, |conn| {
+            // preparing a single statement only parses and binds it, nothing is executed
+            let statement = conn.prepare(q).map_err(|e| format!("error parsing query: '{}'", e.to_string()))?;
+            if statement.parameter_count() > 0 {
+                return Err(format!("Can't describe a query with parameters ({} found), fill in their values first", statement.parameter_count()));
+            }
+
+            // a prepared statement only exposes its schema after execution, DESCRIBE resolves it up front
+            let mut describe_statement = conn.prepare(&format!("DESCRIBE {}", q))
+                .map_err(|e| format!("error parsing query: '{}'", e.to_string()))?;
+            let res: Vec<QueryColumn> = describe_statement
+                .query_map([], |row| Ok(QueryColumn {
+                    name: row.get::<&str, String>("column_name")?,
+                    data_type: row.get::<&str, String>("column_type")?,
+                    nullable: row.get::<&str, String>("null")? == "YES",
+                }))
+                .map_err(|e| format!("error describing query: '{}'", e.to_string()))?
+                .filter_map(Result::ok)
+                .collect();
+
+            println!("described succesfully!");
+            return Ok(res);
+        })
+    }
+
+    fn execute(&self, db_path: String, q: String, args: Vec<Value>) -> Result<usize, String> {
+        println!("\n<<QUERY>>\nexecution on [{}]:\n{}\n", db_path, q);
+
+        self.fetch_connection(&db_path, |conn| {
+            let changed: usize = conn.execute(&q, duckdb::params_from_iter(args.iter()))
+                .map_err(|e| format!("error executing query: '{}'", e.to_string()))?;
+            println!("executed succesfully!");
+            return Ok(changed);
+        })
+    }
+
     fn execute_batch(&self, db_path: String, q: String) -> Result<(), String> {
         println!("\n<<QUERY>>\nbatch execution on [{}]:\n{}\n", db_path, q);
 
@@ -152,11 +464,11 @@ mod tests {
             let mut pool = conn_handler.db_pool.lock().unwrap();
 
             // open DB-connection in memory
-            pool.insert(MEM_DB_PATH.to_string(), Connection::open_in_memory().unwrap());
-            let conn: &Connection = pool.get(MEM_DB_PATH).unwrap();
+            let conn: Connection = Connection::open_in_memory().unwrap();
 
             let _ = conn.execute(CREATE_DEMO_TABLE_SQL, [])?;
             let _ = conn.execute(INSERT_DEMO_DATA_SQL, [])?;
+            pool.insert(MEM_DB_PATH.to_string(), Arc::new(Mutex::new(conn)));
         }
 
         Ok(conn_handler)
@@ -165,7 +477,7 @@ mod tests {
     mod serialize_recordbatch {
         use super::*;
         use arrow_ipc::reader::StreamReader;
-        use duckdb::{arrow::array::{Array, StringArray}, Row};
+        use duckdb::{arrow::{array::{Array, Float64Array, Int32Array, StringArray}, datatypes::{DataType, Field}}, Row};
         use tauri::ipc::{ IpcResponse, Response };
 
         // deserializes response on one column
@@ -229,6 +541,37 @@ mod tests {
         fn serialize_recordbatch_empty_test() {
             let _ = serialize_recordbatch_test_helper(SINGLE_ARG_SQL.to_string(), vec![Value::from(0)]).unwrap();
         }
+
+        #[test]
+        fn serialize_recordbatch_cast_mismatched_type_test() {
+            let schema: Schema = Schema::new(vec![Field::new("y_axis", DataType::Float64, true)]);
+            let batch_schema = Arc::new(Schema::new(vec![Field::new("y_axis", DataType::Int32, true)]));
+            let batch: RecordBatch = RecordBatch::try_new(batch_schema, vec![Arc::new(Int32Array::from(vec![1, 2]))]).unwrap();
+
+            let response: Response = serialize_recordbatch(vec![batch], schema).unwrap();
+            let serial_data: Vec<u8> = Response::body(response).unwrap().deserialize().unwrap();
+            let rb_vec: Vec<RecordBatch> = StreamReader::try_new(Cursor::new(serial_data), None).unwrap()
+                .filter_map(Result::ok)
+                .collect();
+            let values: Vec<Option<f64>> = rb_vec[0].column(0)
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .expect("column was not cast to the stream schema")
+                .iter()
+                .collect();
+            assert_eq!(values, vec![Some(1.0), Some(2.0)]);
+        }
+
+        #[test]
+        fn serialize_recordbatch_mismatched_field_test() {
+            let schema: Schema = Schema::new(vec![Field::new("y_axis", DataType::Float64, true)]);
+            let batch_schema = Arc::new(Schema::new(vec![Field::new("dual_value", DataType::Float64, true)]));
+            let batch: RecordBatch = RecordBatch::try_new(batch_schema, vec![Arc::new(Float64Array::from(vec![1.0]))]).unwrap();
+
+            let res: Result<Response, String> = serialize_recordbatch(vec![batch], schema);
+            let err: String = res.err().unwrap();
+            assert!(err.contains("y_axis") && err.contains("dual_value"), "unexpected error: {}", err);
+        }
     }
 
     mod fetch_connection {
@@ -260,6 +603,68 @@ mod tests {
             assert_eq!(conn_handler.db_pool.lock().unwrap().len(), 1);
         }
 
+        #[test]
+        fn fetch_connection_remote_scratch_test() {
+            let conn_handler: ConnectionHandler = ConnectionHandler::new();
+            let res: Result<(), String> = conn_handler.fetch_connection(&REMOTE_DB_PATH.to_string(), |_| Ok(()));
+            assert!(res.is_ok(), "{:?}", res.unwrap_err());
+            assert!(conn_handler.db_pool.lock().unwrap().contains_key(REMOTE_DB_PATH));
+        }
+
+        #[test]
+        fn fetch_connection_concurrent_same_path_test() {
+            let db_path: String = crate::test_utils::setup_test_db("fetch_connection_concurrent", CREATE_DEMO_TABLE_SQL);
+            let conn_handler: ConnectionHandler = ConnectionHandler::new();
+
+            let results: Vec<Result<(), String>> = std::thread::scope(|scope| {
+                let handles: Vec<_> = (0..2)
+                    .map(|_| scope.spawn(|| conn_handler.fetch_connection(&db_path, |_| Ok(()))))
+                    .collect();
+                handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+            });
+
+            for res in results {
+                assert!(res.is_ok(), "{:?}", res.unwrap_err());
+            }
+            assert_eq!(conn_handler.db_pool.lock().unwrap().len(), 1);
+        }
+
+        #[test]
+        fn fetch_connection_equivalent_paths_test() {
+            let db_path: String = crate::test_utils::setup_test_db("fetch_connection_equivalent_paths", CREATE_DEMO_TABLE_SQL);
+            let path: &Path = Path::new(&db_path);
+            let dotted: String = path.parent().unwrap().join(".").join(path.file_name().unwrap()).to_string_lossy().to_string();
+            let conn_handler: ConnectionHandler = ConnectionHandler::new();
+
+            for spelling in [&db_path, &dotted] {
+                let res: Result<(), String> = conn_handler.fetch_connection(spelling, |_| Ok(()));
+                assert!(res.is_ok(), "{:?}", res.unwrap_err());
+            }
+            assert_eq!(conn_handler.db_pool.lock().unwrap().len(), 1);
+        }
+
+        #[test]
+        #[cfg(any(windows, target_os = "macos"))]
+        fn fetch_connection_case_insensitive_paths_test() {
+            let db_path: String = crate::test_utils::setup_test_db("fetch_connection_case_insensitive", CREATE_DEMO_TABLE_SQL);
+            let path: &Path = Path::new(&db_path);
+            let upper: String = path.with_file_name(path.file_name().unwrap().to_string_lossy().to_uppercase().replace(".DUCKDB", ".duckdb"))
+                .to_string_lossy().to_string();
+            let conn_handler: ConnectionHandler = ConnectionHandler::new();
+
+            for spelling in [&db_path, &upper] {
+                let res: Result<(), String> = conn_handler.fetch_connection(spelling, |_| Ok(()));
+                assert!(res.is_ok(), "{:?}", res.unwrap_err());
+            }
+            assert_eq!(conn_handler.db_pool.lock().unwrap().len(), 1);
+        }
+
+        #[test]
+        fn fetch_connection_missing_file_keeps_path_test() {
+            assert_eq!(pool_key("fakeFile.duckdb"), if cfg!(any(windows, target_os = "macos")) { "fakefile.duckdb" } else { "fakeFile.duckdb" });
+            assert_eq!(pool_key(REMOTE_DB_PATH), REMOTE_DB_PATH);
+        }
+
         #[test]
         fn fetch_connection_file_missing_test() {
             let conn_handler: ConnectionHandler = ConnectionHandler::new();
@@ -270,6 +675,127 @@ mod tests {
         }
     }
 
+    mod open_with_retry {
+        use super::*;
+
+        const LOCK_ERROR: &str = "IO Error: Could not set lock on file \"db.duckdb\": Conflicting lock is held";
+
+        #[test]
+        fn open_with_retry_transient_lock_test() {
+            let mut attempts: u32 = 0;
+            let res: Result<&str, String> = open_with_retry(|| {
+                attempts += 1;
+                if attempts < 3 { Err(LOCK_ERROR.to_string()) } else { Ok("connection") }
+            });
+
+            assert_eq!(res, Ok("connection"));
+            assert_eq!(attempts, 3);
+        }
+
+        #[test]
+        fn open_with_retry_permanent_error_test() {
+            let mut attempts: u32 = 0;
+            let res: Result<(), String> = open_with_retry(|| {
+                attempts += 1;
+                Err("IO Error: The file is not a valid DuckDB database file!".to_string())
+            });
+
+            assert!(res.is_err());
+            assert_eq!(attempts, 1);
+        }
+
+        #[test]
+        fn open_with_retry_gives_up_test() {
+            let mut attempts: u32 = 0;
+            let res: Result<(), String> = open_with_retry(|| {
+                attempts += 1;
+                Err(LOCK_ERROR.to_string())
+            });
+
+            assert_eq!(res, Err(LOCK_ERROR.to_string()));
+            assert_eq!(attempts, OPEN_RETRY_COUNT + 1);
+        }
+    }
+
+    mod cancel_queries {
+        use super::*;
+        use std::time::{Duration, Instant};
+
+        // cross join of a billion rows, runs far longer than the test
+        const LONG_RUNNING_SQL: &str = "SELECT count(*) FROM range(1000000000) AS a CROSS JOIN range(1000) AS b";
+
+        #[test]
+        fn cancel_queries_nothing_running_test() {
+            let conn_handler: ConnectionHandler = mem_db_setup().unwrap();
+            assert_eq!(conn_handler.cancel_queries(MEM_DB_PATH), Ok(false));
+        }
+
+        #[test]
+        fn cancel_queries_interrupts_running_query_test() {
+            let conn_handler: ConnectionHandler = mem_db_setup().unwrap();
+
+            let res: Result<Vec<i64>, String> = std::thread::scope(|scope| {
+                let handle = scope.spawn(|| conn_handler.run_query_row(
+                    MEM_DB_PATH.to_string(), LONG_RUNNING_SQL.to_string(), vec![], |r| r.get::<usize, i64>(0),
+                ));
+
+                // an interrupt before execution started is lost, so keep cancelling until the query gave up
+                let deadline: Instant = Instant::now() + Duration::from_secs(30);
+                while !handle.is_finished() && Instant::now() < deadline {
+                    let _ = conn_handler.cancel_queries(MEM_DB_PATH);
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                handle.join().unwrap()
+            });
+
+            let err: String = res.err().expect("query should have been interrupted");
+            assert!(err.to_lowercase().contains("interrupt"), "unexpected error: {}", err);
+            assert_eq!(conn_handler.cancel_queries(MEM_DB_PATH), Ok(false));
+        }
+
+        #[test]
+        fn cancel_queries_after_panic_test() {
+            let conn_handler: ConnectionHandler = mem_db_setup().unwrap();
+            let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                conn_handler.fetch_connection(&MEM_DB_PATH.to_string(), |_| -> Result<(), String> { panic!("query panicked") })
+            }));
+
+            assert!(res.is_err());
+            assert!(conn_handler.in_flight.lock().unwrap().is_empty());
+            assert_eq!(conn_handler.cancel_queries(MEM_DB_PATH), Ok(false));
+        }
+
+        #[test]
+        fn cancel_queries_other_database_not_blocked_test() {
+            let conn_handler: ConnectionHandler = mem_db_setup().unwrap();
+            conn_handler.db_pool.lock().unwrap()
+                .insert("other.duckdb".to_string(), Arc::new(Mutex::new(Connection::open_in_memory().unwrap())));
+
+            std::thread::scope(|scope| {
+                let handle = scope.spawn(|| conn_handler.run_query_row(
+                    MEM_DB_PATH.to_string(), LONG_RUNNING_SQL.to_string(), vec![], |r| r.get::<usize, i64>(0),
+                ));
+                let deadline: Instant = Instant::now() + Duration::from_secs(30);
+                while !conn_handler.in_flight.lock().unwrap().contains_key(MEM_DB_PATH) && Instant::now() < deadline {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+
+                // the long query holds its own connection only, not the pool
+                let res: Result<Vec<i32>, String> = conn_handler.run_query_row(
+                    "other.duckdb".to_string(), "SELECT 42".to_string(), vec![], |r| r.get::<usize, i32>(0),
+                );
+                assert_eq!(res, Ok(vec![42]));
+                assert!(!handle.is_finished(), "the long query should still be running");
+
+                while !handle.is_finished() && Instant::now() < deadline {
+                    let _ = conn_handler.cancel_queries(MEM_DB_PATH);
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                assert!(handle.join().unwrap().is_err());
+            });
+        }
+    }
+
     mod run_query_rb {
         use super::*;
 
@@ -325,6 +851,56 @@ mod tests {
         }
     }
 
+    mod describe_query {
+        use super::*;
+
+        #[test]
+        fn describe_query_ok_test() {
+            let conn_handler: ConnectionHandler = mem_db_setup().unwrap();
+            let res = conn_handler.describe_query(MEM_DB_PATH.to_string(), "SELECT id, name FROM demoTable;".to_string());
+            assert_eq!(res.unwrap(), vec![
+                QueryColumn { name: "id".to_string(), data_type: "INTEGER".to_string(), nullable: true },
+                QueryColumn { name: "name".to_string(), data_type: "VARCHAR".to_string(), nullable: true },
+            ]);
+        }
+
+        #[test]
+        fn describe_query_multiple_statements_test() {
+            let conn_handler: ConnectionHandler = mem_db_setup().unwrap();
+            let res = conn_handler.describe_query(MEM_DB_PATH.to_string(), "DROP TABLE demoTable; SELECT 1;".to_string());
+            assert_eq!(res, Err("Expected a single statement to describe, got 2".to_string()));
+
+            // the DROP never ran
+            let rows = conn_handler.run_query_row(MEM_DB_PATH.to_string(), SIMPLE_DEMO_SQL.to_string(), vec![], |r| r.get::<usize, String>(0));
+            assert_eq!(rows.map(|rows| rows.len()), Ok(4));
+        }
+
+        #[test]
+        fn describe_query_parameters_test() {
+            let conn_handler: ConnectionHandler = mem_db_setup().unwrap();
+            let res = conn_handler.describe_query(MEM_DB_PATH.to_string(), SINGLE_ARG_SQL.to_string());
+            assert!(res.as_ref().unwrap_err().contains("parameters (1 found)"), "{:?}", res);
+        }
+
+        #[test]
+        fn count_statements_test() {
+            assert_eq!(count_statements("SELECT 1"), 1);
+            assert_eq!(count_statements("SELECT 1;;\n ; "), 1);
+            assert_eq!(count_statements("DROP TABLE t; SELECT 1"), 2);
+            assert_eq!(count_statements("SELECT ';' AS \"a;b\", 'it''s; fine' -- not; a statement\n/* nor; this */"), 1);
+            assert_eq!(count_statements("SELECT $$a;b$$, $x$;$x$ WHERE id = $1; SELECT 2"), 2);
+            assert_eq!(count_statements("  -- only a comment;\n"), 0);
+        }
+
+        #[test]
+        fn describe_query_syntax_err_test() {
+            let conn_handler: ConnectionHandler = mem_db_setup().unwrap();
+            let res = conn_handler.describe_query(MEM_DB_PATH.to_string(), SYNTAX_ERR_SQL.to_string());
+            let exp_err: &str = "syntax";
+            assert!(res.as_ref().unwrap_err().contains(exp_err), "expected Err with '{}', but got: {:?}", exp_err, res);
+        }
+    }
+
     mod run_query_row {
         use super::*;
 
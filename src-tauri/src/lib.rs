@@ -1,5 +1,7 @@
 mod services;
 mod duckdb_conn;
+#[cfg(test)]
+mod test_utils;
 use tauri_plugin_dialog;
 use services::*;
 
@@ -10,19 +12,73 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .invoke_handler(tauri::generate_handler![
+            analysis_bundle::export_analysis_bundle,
             capacity::get_capacity,
+            capacity::get_capacity_factor,
+            capacity::get_capacity_by_carrier,
+            capacity::get_capacity_deltas,
+            capacity::get_investment_trajectory,
+            capacity::get_capacity_age,
+            capacity::get_capacity_range,
+            capacity::diff_capacity,
+            dashboard::run_dashboard,
+            energy_balance::get_energy_balance,
+            energy_balance::get_storage_throughput,
+            energy_balance::get_generation_concentration,
+            energy_balance::get_emissions,
             import_export::get_all_aggregate_flows,
             import_export::get_all_detailed_flows,
+            import_export::get_flows_by_rep_period,
+            import_export::get_trade_balance,
             import_export::get_available_years_flows,
             metadata::get_assets,
+            metadata::get_asset_count,
+            metadata::get_assets_without_results,
+            metadata::search_assets,
+            metadata::get_asset_types,
             metadata::get_tables,
+            metadata::get_tables_overview,
             metadata::get_assets_carriers,
             metadata::get_years,
+            metadata::get_rep_period_info,
+            metadata::get_period_coverage,
+            metadata::get_annual_scaling_check,
+            metadata::get_available_years_for,
             metadata::get_categories,
+            metadata::get_category_tree_text,
+            metadata::get_asset_categories,
+            metadata::get_other_bucket_assets,
             metadata::has_metadata,
+            metadata::get_metadata_bootstrap,
+            metadata::has_solution_data,
+            metadata::get_solver_summary,
+            metadata::get_carrier_capabilities,
+            metadata::get_resolution_summary,
+            metadata::get_min_resolution,
+            metadata::preload_database,
+            metadata::check_referential_integrity,
+            metadata::check_time_block_integrity,
+            metadata::create_recommended_indexes,
             production_price::get_production_price_resolution,
+            production_price::get_demand_weighted_price,
+            production_price::get_price_volatility,
+            production_price::diagnose_production_price,
+            production_price::get_price_method_diff,
+            production_price::get_system_price,
+            production_price::get_marginal_asset,
+            profiles::get_rep_period_profile,
             query::run_serialize_query_on_db,
+            query::validate_query,
+            query::get_query_schema,
+            query::estimate_query_rows,
+            query::compare_queries,
+            query::cancel_queries,
+            raw_duals::get_raw_duals,
+            remote::enable_remote_sources,
             residual_load::get_supply,
+            residual_load::get_supply_duration_curve,
+            statistics::get_column_stats,
+            statistics::get_column_histogram,
             storage_price::get_storage_price_resolution,
             system_cost::get_fixed_asset_cost,
             system_cost::get_fixed_flow_cost,
@@ -0,0 +1,137 @@
+use duckdb::types::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use crate::duckdb_conn::{execute, execute_batch};
+use crate::services::capacity::{build_year_capacity_query, ensure_solution_columns};
+use crate::services::import_export::build_flows_by_rep_period_query;
+use crate::services::metadata::{resolve_resolution, AUTO_RESOLUTION};
+use crate::services::production_price::build_system_price_query;
+use crate::services::system_cost::{build_fixed_asset_cost_query, build_fixed_flow_cost_query};
+
+// writes the capacity, asset flows (per representative period), system price and fixed cost analyses of one year,
+// with default (unfiltered, non-grouped) settings, as tables of a new DuckDB file at `out_path`; returns the names
+// of the written tables. A failed export leaves no file behind
+#[tauri::command]
+pub fn export_analysis_bundle(db_path: String, year: u32, out_path: String) -> Result<Vec<String>, String> {
+    if Path::new(&out_path).exists() {
+        return Err(format!("Bundle file '{}' already exists", out_path));
+    }
+    let tables: Vec<(&str, String, Vec<Value>)> = bundle_queries(&db_path, year)?;
+
+    execute_batch(db_path.clone(), ATTACH_BUNDLE_SQL.replace("{out_path}", &out_path.replace('\'', "''")))?;
+    let res: Result<(), String> = tables.iter().try_for_each(|(table, sql, args)| {
+        let create_sql: String = CREATE_BUNDLE_TABLE_SQL
+            .replace("{table}", table)
+            .replace("{sql}", sql.trim().trim_end_matches(';'));
+        execute(db_path.clone(), create_sql, args.clone()).map(|_| ())
+    });
+    // the connection is shared with the other commands, so the bundle is detached whatever happened
+    let detached: Result<(), String> = execute_batch(db_path, DETACH_BUNDLE_SQL.to_string());
+
+    if let Err(e) = res.and(detached) {
+        let _ = std::fs::remove_file(&out_path);
+        let _ = std::fs::remove_file(format!("{}.wal", out_path));
+        return Err(format!("Failed to export analysis bundle: {}", e));
+    }
+    Ok(tables.into_iter().map(|(table, _, _)| table.to_string()).collect())
+}
+
+// (table name, query, parameters) of every analysis in the bundle, built by the analyses' own query builders
+fn bundle_queries(db_path: &str, year: u32) -> Result<Vec<(&'static str, String, Vec<Value>)>, String> {
+    ensure_solution_columns(db_path)?;
+    let capacity_sql: String = build_year_capacity_query();
+    let flows_sql: String = build_flows_by_rep_period_query();
+
+    let resolution: u32 = resolve_resolution(db_path, year, AUTO_RESOLUTION)?;
+    let (price_sql, price_args): (String, Vec<Value>) = build_system_price_query(db_path, year, "all".to_string(), resolution)?;
+
+    let (asset_cost_sql, asset_cost_args): (String, Vec<Value>) =
+        build_fixed_asset_cost_query(db_path, HashMap::new(), &[], false, None, Some(year), None)?;
+    let (flow_cost_sql, flow_cost_args): (String, Vec<Value>) = build_fixed_flow_cost_query(db_path, None, Some(year))?;
+
+    Ok(vec![
+        ("capacity", capacity_sql, vec![Value::from(year)]),
+        ("flows", flows_sql, vec![Value::from(year)]),
+        ("system_price", price_sql, price_args),
+        ("fixed_asset_cost", asset_cost_sql, asset_cost_args),
+        ("fixed_flow_cost", flow_cost_sql, flow_cost_args),
+    ])
+}
+
+// --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use duckdb::Row;
+    use std::path::PathBuf;
+    use crate::duckdb_conn::run_query_row;
+    use crate::test_utils::setup_tulipa_db;
+
+    fn bundle_path(name: &str) -> String {
+        let path: PathBuf = std::env::temp_dir().join(format!("tulipa_test_{}_bundle.duckdb", name));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn export_analysis_bundle_test() {
+        let db_path: String = setup_tulipa_db("analysis_bundle", "");
+        let out_path: String = bundle_path("analysis_bundle");
+        let tables: Vec<String> = export_analysis_bundle(db_path, 2030, out_path.clone()).unwrap();
+        assert_eq!(tables, vec!["capacity", "flows", "system_price", "fixed_asset_cost", "fixed_flow_cost"]);
+
+        // 4 assets with capacity and fixed costs, 7 asset pairs with flows in the single rep period, electricity and
+        // gas priced for 2 hours, one transport flow
+        let row_counts: Vec<i64> = tables.iter()
+            .map(|table| run_query_row(
+                out_path.clone(),
+                format!("SELECT COUNT(*) FROM {}", table),
+                vec![],
+                |row: &Row<'_>| row.get::<usize, i64>(0),
+            ).unwrap()[0])
+            .collect();
+        assert_eq!(row_counts, vec![4, 7, 4, 4, 1]);
+
+        let flow_columns: Vec<String> = run_query_row(
+            out_path.clone(),
+            "SELECT column_name FROM (DESCRIBE flows)".to_string(),
+            vec![],
+            |row: &Row<'_>| row.get::<usize, String>(0),
+        ).unwrap();
+        assert_eq!(flow_columns, vec!["from_asset", "to_asset", "rep_period", "weight", "unweighted_flow", "weighted_flow"]);
+
+        // the transport of 5 and 4 over two hours
+        let transport: Vec<f64> = run_query_row(
+            out_path,
+            "SELECT weighted_flow FROM flows WHERE from_asset = 'hub_nl' AND to_asset = 'hub_be'".to_string(),
+            vec![],
+            |row: &Row<'_>| row.get::<usize, f64>(0),
+        ).unwrap();
+        assert_eq!(transport, vec![9.0]);
+    }
+
+    #[test]
+    fn export_analysis_bundle_existing_file_test() {
+        let db_path: String = setup_tulipa_db("analysis_bundle_existing_file", "");
+        let res = export_analysis_bundle(db_path.clone(), 2030, db_path.clone());
+        assert_eq!(res.err().unwrap(), format!("Bundle file '{}' already exists", db_path));
+    }
+
+    #[test]
+    fn export_analysis_bundle_failed_test() {
+        // without 'flow_both' the last table fails once the others are written, and no file is left behind
+        let db_path: String = setup_tulipa_db("analysis_bundle_failed", "DROP TABLE flow_both;");
+        let out_path: String = bundle_path("analysis_bundle_failed");
+        let res = export_analysis_bundle(db_path, 2030, out_path.clone());
+        assert!(res.err().unwrap().starts_with("Failed to export analysis bundle"));
+        assert!(!Path::new(&out_path).exists());
+        assert!(!Path::new(&format!("{}.wal", out_path)).exists());
+    }
+}
+
+// --- QUERIES ---
+const ATTACH_BUNDLE_SQL: &str = "ATTACH '{out_path}' AS analysis_bundle;";
+
+const DETACH_BUNDLE_SQL: &str = "DETACH analysis_bundle;";
+
+const CREATE_BUNDLE_TABLE_SQL: &str = "CREATE TABLE analysis_bundle.{table} AS {sql}";
@@ -1,10 +1,13 @@
 use duckdb::arrow::datatypes::Schema;
 use duckdb::arrow::array::RecordBatch;
+use duckdb::types::Value;
+use duckdb::Row;
+use serde::Serialize;
 use tauri::ipc::Response;
-use std::collections::HashMap;
-use crate::services::metadata::check_column_in_table;
-use crate::duckdb_conn::{execute_batch, run_query_rb, serialize_recordbatch};
-use crate::services::query_builder::{build_breakdown_case_conditions, build_breakdown_group_by, build_breakdown_joins, build_breakdown_selects, build_filter_conditions};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use crate::services::metadata::{check_column_in_table, check_series_cap, ensure_metadata_available, fetch_pivot_series};
+use crate::duckdb_conn::{execute_batch, run_query_rb, run_query_row, serialize_recordbatch};
+use crate::services::query_builder::{build_breakdown_case_conditions, build_breakdown_group_by, build_breakdown_joins, build_breakdown_selects, build_filter_conditions, build_carrier_group, build_carrier_join, build_pivot_query, with_category_descendants, with_rounding};
 
 #[tauri::command]
 pub fn get_capacity(
@@ -12,58 +15,599 @@ pub fn get_capacity(
     filters: HashMap<i32, Vec<i32>>,
     grouper: Vec<i32>,
     enable_metadata: bool,
+    round_digits: Option<u32>,
+    pivot: Option<bool>,
+    respect_lifetime: Option<bool>,
+    value: Option<String>,
+    max_series: Option<u32>,
 ) -> Result<Response, String> {
-    // Check for solution columns
-    let inv_has = check_column_in_table(db_path.clone(), "var_assets_investment", "solution")?;
-    let dec_has = check_column_in_table(db_path.clone(), "var_assets_decommission", "solution")?;
+    ensure_solution_columns(&db_path)?;
+    if enable_metadata {
+        ensure_metadata_available(&db_path)?;
+    }
 
-    // if not exists, add columns with all nulls to table
-    if !inv_has {
-        println!("COLUMN '{}' MISSING FROM '{}', INSERTING NULL-COLUMN\n", "solution", "var_assets_investment");
-        let _ = execute_batch(db_path.clone(), ADD_INV_COL_SQL.to_string());
+    let respect_lifetime: bool = respect_lifetime.unwrap_or(false);
+    let query: String = build_capacity_query(&filters, &grouper, enable_metadata);
+    let query: String = if respect_lifetime {
+        // retirement is decided per asset, which a breakdown no longer has
+        if enable_metadata && !grouper.is_empty() {
+            return Err("Lifetime-aware capacity is not available with a breakdown".to_string());
+        }
+        if !check_column_in_table(db_path.clone(), "asset", "technical_lifetime")? {
+            return Err("Lifetime-aware capacity needs column 'technical_lifetime' in table 'asset'".to_string());
+        }
+        CAPACITY_WITHIN_LIFETIME_SQL.replace("{capacity_sql}", query.trim().trim_end_matches(';'))
+    } else {
+        query
+    };
+    let query: String = match value.as_deref().unwrap_or("capacity") {
+        "capacity" => query,
+        "capex" => {
+            // the investment cost is per asset, which a breakdown no longer has
+            if (enable_metadata && !grouper.is_empty()) || respect_lifetime {
+                return Err("The capex view is not available with a breakdown or lifetime-aware capacity".to_string());
+            }
+            if !check_column_in_table(db_path.clone(), "asset_commission", "investment_cost")? {
+                return Err("The capex view needs column 'investment_cost' in table 'asset_commission'".to_string());
+            }
+            CAPACITY_CAPEX_SQL.replace("{capacity_sql}", query.trim().trim_end_matches(';'))
+        }
+        other => return Err(format!("Invalid value '{}', expected 'capacity' or 'capex'", other)),
+    };
+    let query: String = with_rounding(&query, &CAPACITY_VALUE_COLUMNS, round_digits)?;
+    check_series_cap(&db_path, &query, "asset", vec![], max_series)?;
+    let query: String = if pivot.unwrap_or(false) {
+        let series: Vec<String> = fetch_pivot_series(&db_path, &query, "asset", vec![])?;
+        build_pivot_query(&query, "asset", &CAPACITY_VALUE_COLUMNS, &["year"], &series)?
+    } else {
+        query
+    };
+
+    // Execute query
+    let res: (Vec<RecordBatch>, Schema) =
+        run_query_rb(db_path, query, vec![])?;
+    serialize_recordbatch(res.0, res.1)
+}
+
+// change in final capacity since the previous milestone year per asset (or breakdown group),
+// in the first year relative to the capacity before that year's investments and decommissions
+#[tauri::command]
+pub fn get_capacity_deltas(
+    db_path: String,
+    filters: HashMap<i32, Vec<i32>>,
+    grouper: Vec<i32>,
+    enable_metadata: bool,
+    max_series: Option<u32>,
+) -> Result<Response, String> {
+    ensure_solution_columns(&db_path)?;
+    if enable_metadata {
+        ensure_metadata_available(&db_path)?;
     }
 
-    if !dec_has {
-        println!("COLUMN '{}' MISSING FROM '{}', INSERTING NULL-COLUMN\n", "solution", "var_assets_decommission");
-        let _ = execute_batch(db_path.clone(), ADD_DEC_COL_SQL.to_string());
+    let query: String = CAPACITY_DELTAS_SQL.replace("{capacity_sql}", &build_capacity_query(&filters, &grouper, enable_metadata));
+    check_series_cap(&db_path, &query, "asset", vec![], max_series)?;
+    let res: (Vec<RecordBatch>, Schema) =
+        run_query_rb(db_path, query, vec![])?;
+    serialize_recordbatch(res.0, res.1)
+}
+
+#[derive(Serialize)]
+pub struct CapacityRange {
+    asset: String,
+    year: i64,
+    min: f64,
+    mean: f64,
+    max: f64,
+    // scenarios reporting a final capacity for this asset and year
+    scenario_count: usize,
+}
+
+// min, mean and max final capacity per asset (or breakdown group) and year across scenario databases,
+// for uncertainty bands; scenarios without the asset or year are left out of its range
+#[tauri::command]
+pub fn get_capacity_range(
+    db_paths: Vec<String>,
+    filters: HashMap<i32, Vec<i32>>,
+    grouper: Vec<i32>,
+    enable_metadata: bool,
+) -> Result<Vec<CapacityRange>, String> {
+    let query: String = build_capacity_query(&filters, &grouper, enable_metadata);
+
+    let mut capacities: BTreeMap<(String, i64), Vec<f64>> = BTreeMap::new();
+    for db_path in db_paths {
+        ensure_solution_columns(&db_path)?;
+        if enable_metadata {
+            ensure_metadata_available(&db_path)?;
+        }
+
+        let rows: Vec<(String, i64, Option<f64>)> = run_query_row(db_path, query.clone(), vec![], |row: &Row<'_>| {
+            Ok((row.get::<&str, String>("asset")?, row.get::<&str, i64>("year")?, row.get::<&str, Option<f64>>("final_capacity")?))
+        })?;
+        for (asset, year, final_capacity) in rows {
+            if let Some(final_capacity) = final_capacity {
+                capacities.entry((asset, year)).or_default().push(final_capacity);
+            }
+        }
     }
 
+    Ok(capacities.into_iter()
+        .map(|((asset, year), values)| CapacityRange {
+            asset,
+            year,
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            scenario_count: values.len(),
+        })
+        .collect())
+}
+
+#[derive(Serialize)]
+pub struct CapacityDiff {
+    asset: String,
+    // None when the asset is absent from that database
+    capacity_a: Option<f64>,
+    capacity_b: Option<f64>,
+    // capacity in B minus A, an absent asset counts as zero capacity
+    difference: f64,
+}
+
+// final capacity per asset in one milestone year of two scenario databases, aligned on asset name
+#[tauri::command]
+pub fn diff_capacity(db_path_a: String, db_path_b: String, year: u32) -> Result<Vec<CapacityDiff>, String> {
+    let capacities_a: BTreeMap<String, f64> = fetch_final_capacities(&db_path_a, year)?;
+    let capacities_b: BTreeMap<String, f64> = fetch_final_capacities(&db_path_b, year)?;
+
+    let assets: BTreeSet<&String> = capacities_a.keys().chain(capacities_b.keys()).collect();
+    Ok(assets.into_iter()
+        .map(|asset| {
+            let capacity_a: Option<f64> = capacities_a.get(asset).copied();
+            let capacity_b: Option<f64> = capacities_b.get(asset).copied();
+            CapacityDiff {
+                asset: asset.clone(),
+                capacity_a,
+                capacity_b,
+                difference: capacity_b.unwrap_or(0.0) - capacity_a.unwrap_or(0.0),
+            }
+        })
+        .collect())
+}
+
+fn fetch_final_capacities(db_path: &str, year: u32) -> Result<BTreeMap<String, f64>, String> {
+    ensure_solution_columns(db_path)?;
+
+    let query: String = YEAR_FINAL_CAPACITY_SQL.replace("{capacity_sql}", CAPACITY_SQL);
+    let rows: Vec<(String, Option<f64>)> = run_query_row(db_path.to_string(), query, vec![Value::from(year)], |row: &Row<'_>| {
+        Ok((row.get::<usize, String>(0)?, row.get::<usize, Option<f64>>(1)?))
+    })?;
+
+    Ok(rows.into_iter()
+        .map(|(asset, final_capacity)| (asset, final_capacity.unwrap_or(0.0)))
+        .collect())
+}
+
+pub fn build_capacity_query(filters: &HashMap<i32, Vec<i32>>, grouper: &[i32], enable_metadata: bool) -> String {
     // Build the SQL query with optional filters and breakdown
-    let query = if enable_metadata && (!filters.is_empty() || !grouper.is_empty()) {
+    if enable_metadata && grouper.is_empty() && !filters.is_empty() {
+        // With filters without breakdown, every asset stays its own series
+        let filters_sql = CAPACITY_SQL_WITH_FILTERS
+            .replace("{capacity_sql}", CAPACITY_SQL)
+            .replace("{filter_conditions}", &build_filter_conditions(filters, "cap.asset".to_string()));
+        with_category_descendants(&filters_sql, filters, grouper)
+    } else if enable_metadata && !grouper.is_empty() {
         // Build breakdown components
-        let breakdown_case_conditions = build_breakdown_case_conditions(&grouper, "bc.asset".to_string());
-        let breakdown_joins = build_breakdown_joins(&grouper);
-        let breakdown_selects = build_breakdown_selects(&grouper);
-        let breakdown_group_by = build_breakdown_group_by(&grouper);
+        let breakdown_case_conditions = build_breakdown_case_conditions(grouper, "bc.asset".to_string());
+        let breakdown_joins = build_breakdown_joins(grouper);
+        let breakdown_selects = build_breakdown_selects(grouper);
+        let breakdown_group_by = build_breakdown_group_by(grouper);
         
         // Build filter conditions
         let filter_conditions = if !filters.is_empty() {
-            build_filter_conditions(&filters, "af.asset".to_string())
+            build_filter_conditions(filters, "af.asset".to_string())
         } else {
             String::new()
         };
 
         // Create the modified query with breakdown and filters
-        CAPACITY_SQL_WITH_BREAKDOWN
+        let breakdown_sql = CAPACITY_SQL_WITH_BREAKDOWN
             .replace("{breakdown_case_conditions}", &breakdown_case_conditions)
             .replace("{breakdown_selects}", &breakdown_selects)
             .replace("{breakdown_joins}", &breakdown_joins)
             .replace("{filter_conditions}", &filter_conditions)
-            .replace("{breakdown_group_by}", &breakdown_group_by)
+            .replace("{breakdown_group_by}", &breakdown_group_by);
+        with_category_descendants(&breakdown_sql, filters, grouper)
     } else {
         CAPACITY_SQL.to_string()
-    };
+    }
+}
 
-    // Execute query
+// the unfiltered, non-grouped capacity of every asset in a single year, the year being its only parameter
+pub fn build_year_capacity_query() -> String {
+    YEAR_CAPACITY_SQL.replace("{capacity_sql}", &build_capacity_query(&HashMap::new(), &[], false))
+}
+
+// capacity factor per asset for one milestone year: produced energy over the energy at full capacity,
+// both weighted by the representative periods (weight * resolution * block length) like the flow queries
+#[tauri::command]
+pub fn get_capacity_factor(db_path: String, year: u32) -> Result<Response, String> {
+    ensure_solution_columns(&db_path)?;
+
+    let query: String = CAPACITY_FACTOR_SQL.replace("{capacity_sql}", CAPACITY_SQL);
+    let res: (Vec<RecordBatch>, Schema) =
+        run_query_rb(db_path, query, vec![Value::from(year)])?;
+    serialize_recordbatch(res.0, res.1)
+}
+
+// final and initial capacity per milestone year, summed per carrier inferred from the assets' outgoing flows
+#[tauri::command]
+pub fn get_capacity_by_carrier(db_path: String, round_digits: Option<u32>) -> Result<Response, String> {
+    ensure_solution_columns(&db_path)?;
+
+    let query: String = CAPACITY_BY_CARRIER_SQL
+        .replace("{capacity_sql}", CAPACITY_SQL)
+        .replace("{carrier_join}", &build_carrier_join("cap.asset".to_string()))
+        .replace("{carrier_group}", &build_carrier_group(&["cap.year"]));
+    let query: String = with_rounding(&query, &["final_capacity", "initial_capacity"], round_digits)?;
     let res: (Vec<RecordBatch>, Schema) =
         run_query_rb(db_path, query, vec![])?;
     serialize_recordbatch(res.0, res.1)
 }
 
+// cumulative invested capacity per asset (or breakdown group) over the milestone years, a staircase that only goes up,
+// decommissions are left out; milestone years without investments (also those with only decommissions or initial
+// units) repeat the previous total
+#[tauri::command]
+pub fn get_investment_trajectory(
+    db_path: String,
+    filters: HashMap<i32, Vec<i32>>,
+    grouper: Vec<i32>,
+    enable_metadata: bool,
+    max_series: Option<u32>,
+) -> Result<Response, String> {
+    ensure_solution_columns(&db_path)?;
+    if enable_metadata {
+        ensure_metadata_available(&db_path)?;
+    }
+    let (filters, grouper): (HashMap<i32, Vec<i32>>, Vec<i32>) = if enable_metadata { (filters, grouper) } else { (HashMap::new(), vec![]) };
+
+    let query: String = INVESTMENT_TRAJECTORY_SQL
+        .replace("{breakdown_case_conditions}", &build_breakdown_case_conditions(&grouper, "i.asset".to_string()))
+        .replace("{breakdown_joins}", &build_breakdown_joins(&grouper))
+        .replace("{filter_conditions}", &build_filter_conditions(&filters, "i.asset".to_string()));
+    let query: String = with_category_descendants(&query, &filters, &grouper);
+    check_series_cap(&db_path, &query, "asset", vec![], max_series)?;
+    let res: (Vec<RecordBatch>, Schema) =
+        run_query_rb(db_path, query, vec![])?;
+    serialize_recordbatch(res.0, res.1)
+}
+
+// capacity-weighted average age (year minus commission year) of the units installed in one milestone year, per asset
+// (or breakdown group): the initial units of each vintage plus the investments, less the decommissioned units;
+// units without a commission year are left out of the average and reported as `unknown_age_capacity`
+#[tauri::command]
+pub fn get_capacity_age(
+    db_path: String,
+    year: u32,
+    filters: HashMap<i32, Vec<i32>>,
+    grouper: Vec<i32>,
+    enable_metadata: bool,
+    max_series: Option<u32>,
+) -> Result<Response, String> {
+    ensure_solution_columns(&db_path)?;
+    if enable_metadata {
+        ensure_metadata_available(&db_path)?;
+    }
+    let (filters, grouper): (HashMap<i32, Vec<i32>>, Vec<i32>) = if enable_metadata { (filters, grouper) } else { (HashMap::new(), vec![]) };
+
+    let query: String = CAPACITY_AGE_SQL
+        .replace("{breakdown_case_conditions}", &build_breakdown_case_conditions(&grouper, "vc.asset".to_string()))
+        .replace("{breakdown_joins}", &build_breakdown_joins(&grouper))
+        .replace("{filter_conditions}", &build_filter_conditions(&filters, "vc.asset".to_string()));
+    let query: String = with_category_descendants(&query, &filters, &grouper);
+    check_series_cap(&db_path, &query, "asset", vec![Value::from(year)], max_series)?;
+    let res: (Vec<RecordBatch>, Schema) =
+        run_query_rb(db_path, query, vec![Value::from(year)])?;
+    serialize_recordbatch(res.0, res.1)
+}
+
+// capacity queries need the solution columns, which are missing from unsolved databases
+pub fn ensure_solution_columns(db_path: &str) -> Result<(), String> {
+    // Check for solution columns
+    let inv_has = check_column_in_table(db_path.to_string(), "var_assets_investment", "solution")?;
+    let dec_has = check_column_in_table(db_path.to_string(), "var_assets_decommission", "solution")?;
+
+    // if not exists, add columns with all nulls to table
+    if !inv_has {
+        println!("COLUMN '{}' MISSING FROM '{}', INSERTING NULL-COLUMN\n", "solution", "var_assets_investment");
+        let _ = execute_batch(db_path.to_string(), ADD_INV_COL_SQL.to_string());
+    }
+
+    if !dec_has {
+        println!("COLUMN '{}' MISSING FROM '{}', INSERTING NULL-COLUMN\n", "solution", "var_assets_decommission");
+        let _ = execute_batch(db_path.to_string(), ADD_DEC_COL_SQL.to_string());
+    }
+
+    Ok(())
+}
+
 // --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{setup_test_db, setup_tulipa_db, response_to_batches, column_f64, column_string};
+
+    // 'wind' has 10 MW, rep period 1 (weight 3) runs at full capacity, rep period 2 (weight 1) is idle
+    const CAPACITY_FACTOR_FIXTURE_SQL: &str = "
+        CREATE TABLE asset(asset VARCHAR, type VARCHAR, capacity DOUBLE);
+        INSERT INTO asset VALUES ('wind', 'producer', 10);
+        CREATE TABLE asset_both(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, initial_units DOUBLE);
+        INSERT INTO asset_both VALUES ('wind', 2030, 2030, 1);
+        CREATE TABLE var_assets_investment(asset VARCHAR, milestone_year INTEGER, solution DOUBLE);
+        CREATE TABLE var_assets_decommission(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, solution DOUBLE);
+        CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+        INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 3), (2030, 2, 2, 1);
+        CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+        INSERT INTO rep_periods_data VALUES (2030, 1, 2, 1), (2030, 2, 2, 1);
+        CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, solution DOUBLE);
+        INSERT INTO var_flow VALUES ('wind', 'demand', 2030, 1, 1, 2, 10), ('wind', 'demand', 2030, 2, 1, 2, 0);
+    ";
+
+    #[test]
+    fn get_capacity_factor_weighted_test() {
+        let db_path: String = setup_test_db("capacity_factor_weighted", CAPACITY_FACTOR_FIXTURE_SQL);
+        let batches = response_to_batches(get_capacity_factor(db_path, 2030).unwrap());
+
+        // weighted: 60 MWh / (10 MW * 8 h), an unweighted hour count would give 0.5
+        assert_eq!(column_f64(&batches, "capacity_factor"), vec![Some(0.75)]);
+        assert_eq!(column_f64(&batches, "year_hours"), vec![Some(8.0)]);
+    }
+
+    #[test]
+    fn get_capacity_metadata_missing_test() {
+        let db_path: String = setup_test_db("capacity_metadata_missing", CAPACITY_FACTOR_FIXTURE_SQL);
+        let res = get_capacity(db_path, HashMap::from([(1, vec![2])]), vec![], true, None, None, None, None, None);
+        assert!(res.is_err() && res.err().unwrap().contains("Metadata not available in this database (missing table 'category')"));
+    }
+
+    #[test]
+    fn get_capacity_metadata_disabled_test() {
+        let db_path: String = setup_test_db("capacity_metadata_disabled", CAPACITY_FACTOR_FIXTURE_SQL);
+        let batches = response_to_batches(get_capacity(db_path, HashMap::new(), vec![], false, None, None, None, None, None).unwrap());
+        assert_eq!(column_f64(&batches, "final_capacity"), vec![Some(10.0)]);
+    }
+
+    // 'wind' invests 2 units in 2040, 1 in 2050 and decommissions half a unit in 2050
+    const THREE_YEARS_FIXTURE_SQL: &str = "
+        CREATE TABLE asset(asset VARCHAR, type VARCHAR, capacity DOUBLE);
+        INSERT INTO asset VALUES ('wind', 'producer', 10);
+        CREATE TABLE asset_both(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, initial_units DOUBLE);
+        INSERT INTO asset_both VALUES ('wind', 2030, 2030, 1), ('wind', 2040, 2030, 1), ('wind', 2050, 2030, 1);
+        CREATE TABLE var_assets_investment(asset VARCHAR, milestone_year INTEGER, solution DOUBLE);
+        INSERT INTO var_assets_investment VALUES ('wind', 2040, 2), ('wind', 2050, 1);
+        CREATE TABLE var_assets_decommission(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, solution DOUBLE);
+        INSERT INTO var_assets_decommission VALUES ('wind', 2050, 2030, 0.5);
+    ";
+
+    #[test]
+    fn get_capacity_deltas_three_years_test() {
+        let db_path: String = setup_test_db("capacity_deltas_three_years", THREE_YEARS_FIXTURE_SQL);
+        let batches = response_to_batches(get_capacity_deltas(db_path, HashMap::new(), vec![], false, None).unwrap());
+
+        assert_eq!(column_f64(&batches, "year"), vec![Some(2030.0), Some(2040.0), Some(2050.0)]);
+        assert_eq!(column_f64(&batches, "final_capacity"), vec![Some(10.0), Some(30.0), Some(35.0)]);
+        // nothing changes within the first year, then +2 units and +1 - 0.5 units
+        assert_eq!(column_f64(&batches, "capacity_delta"), vec![Some(0.0), Some(20.0), Some(5.0)]);
+    }
+
+    #[test]
+    fn get_investment_trajectory_three_years_test() {
+        let db_path: String = setup_test_db("investment_trajectory_three_years", THREE_YEARS_FIXTURE_SQL);
+        let batches = response_to_batches(get_investment_trajectory(db_path, HashMap::new(), vec![], false, None).unwrap());
+
+        assert_eq!(column_f64(&batches, "year"), vec![Some(2030.0), Some(2040.0), Some(2050.0)]);
+        assert_eq!(column_f64(&batches, "investment"), vec![Some(0.0), Some(20.0), Some(10.0)]);
+        // the decommissioned half unit in 2050 doesn't lower the trajectory
+        assert_eq!(column_f64(&batches, "cumulative_investment"), vec![Some(0.0), Some(20.0), Some(30.0)]);
+    }
+
+    #[test]
+    fn get_capacity_age_test() {
+        // in 2050 'wind' has 1 unit from 2030 and invested 2 units in 2040, 'old' has a unit of unknown age
+        let db_path: String = setup_test_db("capacity_age", "
+            CREATE TABLE asset(asset VARCHAR, type VARCHAR, capacity DOUBLE);
+            INSERT INTO asset VALUES ('wind', 'producer', 10), ('old', 'producer', 5);
+            CREATE TABLE asset_both(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, initial_units DOUBLE);
+            INSERT INTO asset_both VALUES ('wind', 2050, 2030, 1), ('old', 2050, NULL, 1);
+            CREATE TABLE var_assets_investment(asset VARCHAR, milestone_year INTEGER, solution DOUBLE);
+            INSERT INTO var_assets_investment VALUES ('wind', 2040, 2), ('wind', 2060, 4);
+            CREATE TABLE var_assets_decommission(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, solution DOUBLE);
+        ");
+        let batches = response_to_batches(get_capacity_age(db_path, 2050, HashMap::new(), vec![], false, None).unwrap());
+
+        assert_eq!(column_string(&batches, "asset"), vec![Some("old".to_string()), Some("wind".to_string())]);
+        assert_eq!(column_f64(&batches, "capacity"), vec![Some(0.0), Some(30.0)]);
+        // (10 MW * 20 years + 20 MW * 10 years) / 30 MW
+        let average_age: Vec<Option<f64>> = column_f64(&batches, "average_age");
+        assert_eq!(average_age[0], None);
+        assert!((average_age[1].unwrap() - 400.0 / 30.0).abs() < 1e-9, "unexpected average age: {:?}", average_age[1]);
+        assert_eq!(column_f64(&batches, "unknown_age_capacity"), vec![Some(5.0), Some(0.0)]);
+    }
+
+    const TWO_SERIES_FIXTURE_SQL: &str = "
+        CREATE TABLE asset(asset VARCHAR, type VARCHAR, capacity DOUBLE);
+        INSERT INTO asset VALUES ('wind', 'producer', 10), ('solar', 'producer', 5);
+        CREATE TABLE asset_both(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, initial_units DOUBLE);
+        INSERT INTO asset_both VALUES ('wind', 2030, 2030, 1), ('solar', 2030, 2030, 2), ('wind', 2050, 2030, 1), ('solar', 2050, 2030, 2);
+        CREATE TABLE var_assets_investment(asset VARCHAR, milestone_year INTEGER, solution DOUBLE);
+        INSERT INTO var_assets_investment VALUES ('wind', 2050, 1);
+        CREATE TABLE var_assets_decommission(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, solution DOUBLE);
+    ";
+
+    #[test]
+    fn get_capacity_pivot_test() {
+        let db_path: String = setup_test_db("capacity_pivot", TWO_SERIES_FIXTURE_SQL);
+        let batches = response_to_batches(get_capacity(db_path, HashMap::new(), vec![], false, None, Some(true), None, None, None).unwrap());
+
+        // one row per year, one column per asset and value
+        let schema = batches[0].schema();
+        let columns: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(columns.len(), 1 + 2 * CAPACITY_VALUE_COLUMNS.len());
+        assert_eq!(columns[0], "year");
+        assert_eq!(column_f64(&batches, "year"), vec![Some(2030.0), Some(2050.0)]);
+        assert_eq!(column_f64(&batches, "solar_final_capacity"), vec![Some(10.0), Some(10.0)]);
+        assert_eq!(column_f64(&batches, "wind_final_capacity"), vec![Some(10.0), Some(20.0)]);
+    }
+
+    #[test]
+    fn get_capacity_respect_lifetime_test() {
+        // 'solar' is commissioned in 2030 with a 10 year lifetime, so it is retired by 2050
+        let fixture: String = TWO_SERIES_FIXTURE_SQL.to_string() + "
+            ALTER TABLE asset ADD COLUMN technical_lifetime INTEGER;
+            UPDATE asset SET technical_lifetime = CASE asset WHEN 'solar' THEN 10 ELSE 30 END;
+        ";
+        let db_path: String = setup_test_db("capacity_respect_lifetime", &fixture);
+        let final_capacity = |respect_lifetime: bool| -> Vec<Option<f64>> {
+            column_f64(&response_to_batches(get_capacity(db_path.clone(), HashMap::new(), vec![], false, None, None, Some(respect_lifetime), None, None).unwrap()), "final_capacity")
+        };
+
+        // solar 2030, solar 2050, wind 2030, wind 2050
+        assert_eq!(final_capacity(false), vec![Some(10.0), Some(10.0), Some(10.0), Some(20.0)]);
+        assert_eq!(final_capacity(true), vec![Some(10.0), Some(0.0), Some(10.0), Some(20.0)]);
+    }
+
+    #[test]
+    fn get_capacity_capex_test() {
+        // 'wind' (10 MW units, discount rate 5%) invests 2 units in 2030 at 100 per MW and 1 unit in 2050 at 80 per MW
+        let db_path: String = setup_test_db("capacity_capex", "
+            CREATE TABLE asset(asset VARCHAR, capacity DOUBLE, discount_rate DOUBLE);
+            INSERT INTO asset VALUES ('wind', 10, 0.05);
+            CREATE TABLE asset_commission(asset VARCHAR, commission_year INTEGER, investment_cost DOUBLE);
+            INSERT INTO asset_commission VALUES ('wind', 2030, 100), ('wind', 2050, 80);
+            CREATE TABLE asset_both(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, initial_units DOUBLE);
+            CREATE TABLE var_assets_investment(asset VARCHAR, milestone_year INTEGER, solution DOUBLE);
+            INSERT INTO var_assets_investment VALUES ('wind', 2030, 2), ('wind', 2050, 1);
+            CREATE TABLE var_assets_decommission(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, solution DOUBLE);
+        ");
+        let view = |value: &str| -> Vec<RecordBatch> {
+            response_to_batches(get_capacity(db_path.clone(), HashMap::new(), vec![], false, None, None, None, Some(value.to_string()), None).unwrap())
+        };
+
+        let capacity = view("capacity");
+        assert_eq!(column_f64(&capacity, "investment"), vec![Some(20.0), Some(10.0)]);
+        assert_eq!(column_f64(&capacity, "final_capacity"), vec![Some(20.0), Some(30.0)]);
+
+        // the 2050 investment is discounted over the 20 years since the first milestone year
+        let capex = view("capex");
+        let capex_2050: f64 = 10.0 * 80.0 * 1.05_f64.powi(-20);
+        let expected: [(&str, [f64; 2]); 3] = [
+            ("investment", [2000.0, capex_2050]),
+            ("final_capacity", [2000.0, 2000.0 + capex_2050]),
+            ("initial_capacity", [0.0, 2000.0]),
+        ];
+        for (column, values) in expected {
+            let actual: Vec<f64> = column_f64(&capex, column).into_iter().flatten().collect();
+            assert_eq!(actual.len(), 2);
+            for (actual, value) in actual.iter().zip(values) {
+                assert!((actual - value).abs() < 1e-9, "unexpected {}: {} instead of {}", column, actual, value);
+            }
+        }
+        assert!(get_capacity(db_path, HashMap::new(), vec![], false, None, None, None, Some("euro".to_string()), None).is_err());
+    }
+
+    #[test]
+    fn get_capacity_filters_without_breakdown_test() {
+        // 'wind' and 'solar' are in NL, 'gas' in BE
+        let fixture: String = TWO_SERIES_FIXTURE_SQL.to_string() + "
+            INSERT INTO asset VALUES ('gas', 'producer', 1);
+            INSERT INTO asset_both VALUES ('gas', 2030, 2030, 1), ('gas', 2050, 2030, 1);
+            CREATE TABLE category(id INTEGER, name VARCHAR, parent_id INTEGER, level INTEGER);
+            INSERT INTO category VALUES (1, 'location', NULL, 1), (2, 'NL', 1, 0), (3, 'BE', 1, 0);
+            CREATE TABLE asset_category(asset VARCHAR, root_id INTEGER, leaf_id INTEGER);
+            INSERT INTO asset_category VALUES ('wind', 1, 2), ('solar', 1, 2), ('gas', 1, 3);
+        ";
+        let db_path: String = setup_test_db("capacity_filters_without_breakdown", &fixture);
+        let batches = response_to_batches(get_capacity(db_path, HashMap::from([(1, vec![2])]), vec![], true, None, None, None, None, None).unwrap());
+
+        let assets: Vec<Option<String>> = ["solar", "solar", "wind", "wind"].iter().map(|a| Some(a.to_string())).collect();
+        assert_eq!(column_string(&batches, "asset"), assets);
+        assert_eq!(column_f64(&batches, "final_capacity"), vec![Some(10.0), Some(10.0), Some(10.0), Some(20.0)]);
+    }
+
+    #[test]
+    fn get_capacity_range_two_scenarios_test() {
+        // the second scenario invests in an extra unit of 'wind' in 2050
+        let low_path: String = setup_test_db("capacity_range_low", TWO_SERIES_FIXTURE_SQL);
+        let high_path: String = setup_test_db("capacity_range_high", &(TWO_SERIES_FIXTURE_SQL.to_string()
+            + "UPDATE var_assets_investment SET solution = 2 WHERE asset = 'wind';"));
+        let ranges: Vec<CapacityRange> = get_capacity_range(vec![low_path, high_path], HashMap::new(), vec![], false).unwrap();
+
+        let wind_2050: &CapacityRange = ranges.iter().find(|r| r.asset == "wind" && r.year == 2050).unwrap();
+        assert_eq!((wind_2050.min, wind_2050.mean, wind_2050.max), (20.0, 25.0, 30.0));
+        assert_eq!(wind_2050.scenario_count, 2);
+
+        let solar_2030: &CapacityRange = ranges.iter().find(|r| r.asset == "solar" && r.year == 2030).unwrap();
+        assert_eq!((solar_2030.min, solar_2030.mean, solar_2030.max), (10.0, 10.0, 10.0));
+        assert_eq!(ranges.len(), 4);
+    }
+
+    #[test]
+    fn diff_capacity_test() {
+        // B doubles the capacity per unit of 'solar' and adds 'gas'
+        let path_a: String = setup_test_db("diff_capacity_a", TWO_SERIES_FIXTURE_SQL);
+        let path_b: String = setup_test_db("diff_capacity_b", &(TWO_SERIES_FIXTURE_SQL.to_string() + "
+            UPDATE asset SET capacity = 10 WHERE asset = 'solar';
+            INSERT INTO asset VALUES ('gas', 'producer', 4);
+            INSERT INTO asset_both VALUES ('gas', 2030, 2030, 1);
+        "));
+        let diffs: Vec<CapacityDiff> = diff_capacity(path_a, path_b, 2030).unwrap();
+
+        let assets: Vec<&str> = diffs.iter().map(|d| d.asset.as_str()).collect();
+        assert_eq!(assets, vec!["gas", "solar", "wind"]);
+        assert_eq!((diffs[0].capacity_a, diffs[0].capacity_b, diffs[0].difference), (None, Some(4.0), 4.0));
+        assert_eq!((diffs[1].capacity_a, diffs[1].capacity_b, diffs[1].difference), (Some(10.0), Some(20.0), 10.0));
+        assert_eq!((diffs[2].capacity_a, diffs[2].capacity_b, diffs[2].difference), (Some(10.0), Some(10.0), 0.0));
+    }
+
+    // 'wind' exports both electricity and hydrogen, 'battery' has no outgoing flows
+    const CAPACITY_BY_CARRIER_FIXTURE_SQL: &str = "
+        CREATE TABLE asset(asset VARCHAR, type VARCHAR, capacity DOUBLE);
+        INSERT INTO asset VALUES ('wind', 'producer', 10), ('solar', 'producer', 5), ('battery', 'storage', 2);
+        CREATE TABLE asset_both(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, initial_units DOUBLE);
+        INSERT INTO asset_both VALUES ('wind', 2030, 2030, 1), ('solar', 2030, 2030, 1), ('battery', 2030, 2030, 1);
+        CREATE TABLE var_assets_investment(asset VARCHAR, milestone_year INTEGER, solution DOUBLE);
+        CREATE TABLE var_assets_decommission(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, solution DOUBLE);
+        CREATE TABLE flow(from_asset VARCHAR, to_asset VARCHAR, carrier VARCHAR);
+        INSERT INTO flow VALUES ('wind', 'demand', 'electricity'), ('wind', 'electrolyzer', 'hydrogen'), ('solar', 'demand', 'electricity');
+    ";
+
+    #[test]
+    fn get_capacity_by_carrier_test() {
+        let db_path: String = setup_test_db("capacity_by_carrier", CAPACITY_BY_CARRIER_FIXTURE_SQL);
+        let batches = response_to_batches(get_capacity_by_carrier(db_path, None).unwrap());
+
+        // wind is only counted once, the battery lands in the null-carrier bucket
+        assert_eq!(column_string(&batches, "carrier"), vec![Some("electricity".to_string()), None]);
+        assert_eq!(column_f64(&batches, "final_capacity"), vec![Some(15.0), Some(2.0)]);
+    }
+
+    #[test]
+    fn get_capacity_factor_tulipa_fixture_test() {
+        let db_path: String = setup_tulipa_db("capacity_factor_tulipa_fixture", "");
+        let batches = response_to_batches(get_capacity_factor(db_path, 2030).unwrap());
+
+        // 'solar' doubled its capacity with the investment, 'wind' is not built; over 2 hours
+        assert_eq!(column_string(&batches, "asset"), vec![
+            Some("battery".to_string()), Some("ccgt".to_string()), Some("gas_well".to_string()), Some("solar".to_string()),
+        ]);
+        assert_eq!(column_f64(&batches, "final_capacity"), vec![Some(2.0), Some(5.0), Some(10.0), Some(20.0)]);
+        assert_eq!(column_f64(&batches, "capacity_factor"), vec![Some(0.5), Some(0.3), Some(0.3), Some(0.15)]);
+    }
+}
 
 // --- QUERIES ---
 
+const CAPACITY_VALUE_COLUMNS: [&str; 4] = ["investment", "decommission", "final_capacity", "initial_capacity"];
+
 const CAPACITY_SQL: &str = "
 WITH years AS (
   SELECT DISTINCT year FROM (
@@ -126,6 +670,174 @@ WHERE af.asset IN (SELECT asset FROM assets)
 GROUP BY af.asset, y.year
 ORDER BY af.asset, y.year";
 
+const YEAR_CAPACITY_SQL: &str = "
+    SELECT * FROM (
+        {capacity_sql}
+    ) AS capacity
+    WHERE year = ?
+";
+
+// zeroes the capacity of an asset in the years after its youngest vintage (initial units or investment) exceeded
+// its technical lifetime, using the same window as the fixed cost: a missing or zero lifetime only covers the
+// commission year itself
+const CAPACITY_WITHIN_LIFETIME_SQL: &str = "
+WITH vintages AS (
+  SELECT asset, commission_year FROM asset_both WHERE initial_units > 0
+  UNION
+  SELECT asset, milestone_year AS commission_year FROM var_assets_investment WHERE solution > 0
+),
+end_of_life AS (
+  SELECT
+    v.asset,
+    MAX(v.commission_year + GREATEST(COALESCE(a.technical_lifetime, 0), 0)) AS last_year
+  FROM vintages AS v
+  JOIN asset AS a ON a.asset = v.asset
+  GROUP BY v.asset
+)
+SELECT cap.* REPLACE (
+  CASE WHEN cap.year > eol.last_year THEN 0 ELSE cap.final_capacity END AS final_capacity,
+  CASE WHEN cap.year > eol.last_year THEN 0 ELSE cap.initial_capacity END AS initial_capacity
+)
+FROM ({capacity_sql}) AS cap
+LEFT JOIN end_of_life AS eol ON eol.asset = cap.asset
+ORDER BY cap.asset, cap.year";
+
+// the capacity columns in money: investments cost their investment cost per MW of the commission year, discounted
+// to the first milestone year; the final and initial capacity become the CAPEX invested up to and before the year,
+// decommissioning recovers nothing
+const CAPACITY_CAPEX_SQL: &str = "
+WITH capex AS (
+  SELECT
+    cap.*,
+    CASE WHEN cap.investment > 0 THEN
+      cap.investment
+      * COALESCE(ac.investment_cost, 0)
+      -- a missing discount rate means no discounting, like in the cost queries, as does a rate of -1 or below
+      * POWER(1 + CASE WHEN COALESCE(a.discount_rate, 0) > -1 THEN COALESCE(a.discount_rate, 0) ELSE 0 END, -(cap.year - MIN(cap.year) OVER ()))
+    ELSE 0 END AS invested_capex
+  FROM ({capacity_sql}) AS cap
+  JOIN asset AS a ON a.asset = cap.asset
+  LEFT JOIN asset_commission AS ac ON ac.asset = cap.asset AND ac.commission_year = cap.year
+)
+SELECT capex.* EXCLUDE (invested_capex) REPLACE (
+  CASE WHEN capex.investment < 0 THEN capex.investment ELSE capex.invested_capex END AS investment,
+  CASE WHEN capex.decommission < 0 THEN capex.decommission ELSE 0 END AS decommission,
+  SUM(capex.invested_capex) OVER (PARTITION BY capex.asset ORDER BY capex.year) AS final_capacity,
+  SUM(capex.invested_capex) OVER (PARTITION BY capex.asset ORDER BY capex.year) - capex.invested_capex AS initial_capacity
+)
+FROM capex
+ORDER BY capex.asset, capex.year";
+
+const INVESTMENT_TRAJECTORY_SQL: &str = "
+WITH investments AS (
+  SELECT
+    CASE
+      {breakdown_case_conditions}
+      ELSE 'Other'
+    END AS asset,
+    i.milestone_year AS year,
+    SUM(COALESCE(i.solution, 0) * COALESCE(a.capacity, 0)) AS investment
+  FROM var_assets_investment AS i
+  JOIN asset AS a ON a.asset = i.asset
+  {breakdown_joins}
+  WHERE 1 {filter_conditions}
+  GROUP BY
+    CASE
+      {breakdown_case_conditions}
+      ELSE 'Other'
+    END,
+    i.milestone_year
+),
+years AS (
+  SELECT milestone_year AS year FROM var_assets_investment
+  UNION
+  SELECT milestone_year AS year FROM var_assets_decommission
+  UNION
+  SELECT milestone_year AS year FROM asset_both
+),
+groups AS (
+  SELECT DISTINCT asset FROM investments
+)
+SELECT
+  g.asset,
+  y.year,
+  COALESCE(inv.investment, 0) AS investment,
+  SUM(COALESCE(inv.investment, 0)) OVER (PARTITION BY g.asset ORDER BY y.year) AS cumulative_investment
+FROM groups AS g
+CROSS JOIN years AS y
+LEFT JOIN investments AS inv ON inv.asset = g.asset AND inv.year = y.year
+ORDER BY g.asset, y.year";
+
+const CAPACITY_AGE_SQL: &str = "
+WITH vintages AS (
+  SELECT asset, commission_year, initial_units AS units FROM asset_both WHERE milestone_year = $1
+  UNION ALL
+  SELECT asset, milestone_year AS commission_year, solution AS units FROM var_assets_investment WHERE milestone_year <= $1
+  UNION ALL
+  SELECT asset, commission_year, -solution AS units FROM var_assets_decommission WHERE milestone_year <= $1
+),
+vintage_capacity AS (
+  SELECT
+    v.asset,
+    v.commission_year,
+    SUM(COALESCE(v.units, 0)) * ANY_VALUE(a.capacity) AS capacity
+  FROM vintages AS v
+  JOIN asset AS a ON a.asset = v.asset
+  GROUP BY v.asset, v.commission_year
+  HAVING SUM(COALESCE(v.units, 0)) > 0
+)
+SELECT
+  CASE
+    {breakdown_case_conditions}
+    ELSE 'Other'
+  END AS asset,
+  $1 AS year,
+  COALESCE(SUM(vc.capacity) FILTER (WHERE vc.commission_year IS NOT NULL), 0) AS capacity,
+  SUM(vc.capacity * ($1 - vc.commission_year)) FILTER (WHERE vc.commission_year IS NOT NULL)
+    / NULLIF(SUM(vc.capacity) FILTER (WHERE vc.commission_year IS NOT NULL), 0) AS average_age,
+  COALESCE(SUM(vc.capacity) FILTER (WHERE vc.commission_year IS NULL), 0) AS unknown_age_capacity
+FROM vintage_capacity AS vc
+{breakdown_joins}
+WHERE 1 {filter_conditions}
+GROUP BY
+  CASE
+    {breakdown_case_conditions}
+    ELSE 'Other'
+  END
+ORDER BY 1";
+
+const CAPACITY_DELTAS_SQL: &str = "
+SELECT
+  cap.*,
+  cap.final_capacity - COALESCE(
+    LAG(cap.final_capacity) OVER (PARTITION BY cap.asset ORDER BY cap.year),
+    cap.initial_capacity
+  ) AS capacity_delta
+FROM ({capacity_sql}) AS cap
+ORDER BY cap.asset, cap.year";
+
+const YEAR_FINAL_CAPACITY_SQL: &str = "
+SELECT cap.asset, cap.final_capacity
+FROM ({capacity_sql}) AS cap
+WHERE cap.year = ?";
+
+const CAPACITY_BY_CARRIER_SQL: &str = "
+SELECT
+  inferred_carrier.carrier AS carrier,
+  cap.year,
+  SUM(cap.final_capacity) AS final_capacity,
+  SUM(cap.initial_capacity) AS initial_capacity
+FROM ({capacity_sql}) AS cap
+{carrier_join}
+{carrier_group}
+ORDER BY cap.year, carrier NULLS LAST";
+
+const CAPACITY_SQL_WITH_FILTERS: &str = "
+SELECT cap.*
+FROM ({capacity_sql}) AS cap
+WHERE 1 {filter_conditions}
+ORDER BY cap.asset, cap.year";
+
 const CAPACITY_SQL_WITH_BREAKDOWN: &str = "
 WITH years AS (
   SELECT DISTINCT year FROM (
@@ -213,6 +925,41 @@ ORDER BY
     ELSE 'Other'
   END, bc.year";
 
+const CAPACITY_FACTOR_SQL: &str = "
+WITH capacities AS (
+  SELECT asset, final_capacity
+  FROM ({capacity_sql}) AS c
+  WHERE c.year = $1
+),
+year_hours AS ( -- total hours represented in the year
+  SELECT SUM(rpm.weight * rpd.num_timesteps * rpd.resolution) AS hours
+  FROM rep_periods_mapping rpm
+  JOIN rep_periods_data rpd ON rpd.year = rpm.year AND rpd.rep_period = rpm.rep_period
+  WHERE rpm.year = $1
+),
+production AS ( -- weighted energy leaving each asset
+  SELECT
+    vf.from_asset AS asset,
+    SUM(vf.solution * rpm.weight * rpd.resolution * (vf.time_block_end - vf.time_block_start + 1)) AS energy
+  FROM var_flow vf
+  JOIN rep_periods_mapping rpm ON rpm.year = vf.year AND rpm.rep_period = vf.rep_period
+  JOIN rep_periods_data rpd ON rpd.year = vf.year AND rpd.rep_period = vf.rep_period
+  WHERE vf.year = $1
+  GROUP BY vf.from_asset
+)
+
+SELECT
+  c.asset,
+  $1 AS year,
+  COALESCE(p.energy, 0) AS energy,
+  c.final_capacity,
+  yh.hours AS year_hours,
+  COALESCE(p.energy, 0) / NULLIF(c.final_capacity * yh.hours, 0) AS capacity_factor
+FROM capacities c
+CROSS JOIN year_hours yh
+LEFT JOIN production p ON p.asset = c.asset
+ORDER BY c.asset";
+
 // TNO specified columns should ALWAYS be present (but can be null)
 // defaults to NULL
 const ADD_INV_COL_SQL: &str = "
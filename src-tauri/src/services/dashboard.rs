@@ -0,0 +1,151 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use duckdb::{arrow::{array::RecordBatch, datatypes::Schema}, types::Value};
+use serde::{Deserialize, Serialize};
+use tauri::ipc::{IpcResponse, Response};
+use crate::duckdb_conn::{run_query_rb, serialize_recordbatch};
+use crate::services::{production_price::get_production_price_resolution, system_cost::get_fixed_asset_cost};
+use crate::services::capacity::{build_year_capacity_query, ensure_solution_columns};
+use crate::services::metadata::AUTO_RESOLUTION;
+use std::collections::HashMap;
+
+#[derive(Deserialize)]
+pub struct DashboardConfig {
+    // names of the analyses to run, in order, see `DASHBOARD_ANALYSES`
+    analyses: Vec<String>,
+    // resolution of the time series analyses, automatic by default
+    #[serde(default)]
+    resolution: u32,
+    #[serde(default = "default_carrier")]
+    carrier: String,
+}
+
+fn default_carrier() -> String {
+    "all".to_string()
+}
+
+#[derive(Serialize)]
+pub struct DashboardSection {
+    analysis: String,
+    // the serialized record batches as returned by the analysis' own command, base64 encoded since a plain byte
+    // array is serialized to JSON as an array of numbers
+    data: Option<String>,
+    error: Option<String>,
+}
+
+// runs several analyses for one year in a single call, with default (unfiltered, non-grouped) settings; an analysis
+// failing only gives an error section, the other sections are still returned
+#[tauri::command]
+pub fn run_dashboard(db_path: String, year: u32, config: DashboardConfig) -> Result<Vec<DashboardSection>, String> {
+    let mut sections: Vec<DashboardSection> = Vec::new();
+    for analysis in &config.analyses {
+        let res: Result<Vec<u8>, String> = run_analysis(&db_path, year, &config, analysis).and_then(response_bytes);
+        let (data, error): (Option<String>, Option<String>) = match res {
+            Ok(data) => (Some(STANDARD.encode(data)), None),
+            Err(e) => (None, Some(e)),
+        };
+        sections.push(DashboardSection { analysis: analysis.clone(), data, error });
+    }
+
+    Ok(sections)
+}
+
+fn run_analysis(db_path: &str, year: u32, config: &DashboardConfig, analysis: &str) -> Result<Response, String> {
+    match analysis {
+        "capacity" => get_year_capacity(db_path, year),
+        "production_price" => get_production_price_resolution(
+            db_path.to_string(), year, config.resolution, config.carrier.clone(), HashMap::new(), vec![], false, None,
+        ),
+        "system_cost" => get_fixed_asset_cost(db_path.to_string(), HashMap::new(), vec![], false, None, Some(year), None, None),
+        _ => Err(format!("Unknown analysis '{}', expected one of: {}", analysis, DASHBOARD_ANALYSES.join(", "))),
+    }
+}
+
+// the capacity of the dashboard's year only, `get_capacity` returns every year
+fn get_year_capacity(db_path: &str, year: u32) -> Result<Response, String> {
+    ensure_solution_columns(db_path)?;
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path.to_string(), build_year_capacity_query(), vec![Value::from(year)])?;
+
+    serialize_recordbatch(res.0, res.1)
+}
+
+fn response_bytes(response: Response) -> Result<Vec<u8>, String> {
+    response.body()
+        .map_err(|e| format!("Failed to read analysis result: {}", e))?
+        .deserialize::<Vec<u8>>()
+        .map_err(|e| format!("Failed to read analysis result: {}", e))
+}
+
+// --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use arrow_ipc::reader::StreamReader;
+    use crate::test_utils::{setup_test_db, setup_tulipa_db, num_rows, column_f64};
+
+    const DASHBOARD_FIXTURE_SQL: &str = "
+        CREATE TABLE year_data(year INTEGER, is_milestone BOOLEAN);
+        INSERT INTO year_data VALUES (2030, TRUE);
+        CREATE TABLE asset(asset VARCHAR, type VARCHAR, capacity DOUBLE, capacity_storage_energy DOUBLE, discount_rate DOUBLE, technical_lifetime INTEGER);
+        INSERT INTO asset VALUES ('wind', 'producer', 5, 0, 0.05, 20);
+        CREATE TABLE asset_commission(asset VARCHAR, commission_year INTEGER, fixed_cost DOUBLE, fixed_cost_storage_energy DOUBLE);
+        INSERT INTO asset_commission VALUES ('wind', 2030, 4, 0);
+        CREATE TABLE asset_both(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, initial_units DOUBLE, initial_storage_units DOUBLE);
+        INSERT INTO asset_both VALUES ('wind', 2030, 2030, 1, 0);
+    ";
+
+    fn section_batches(section: &DashboardSection) -> Vec<RecordBatch> {
+        let data: Vec<u8> = STANDARD.decode(section.data.as_deref().unwrap()).unwrap();
+        StreamReader::try_new(Cursor::new(data), None)
+            .unwrap()
+            .filter_map(Result::ok)
+            .collect()
+    }
+
+    fn config(analyses: &[&str]) -> DashboardConfig {
+        DashboardConfig {
+            analyses: analyses.iter().map(|a| a.to_string()).collect(),
+            resolution: AUTO_RESOLUTION,
+            carrier: default_carrier(),
+        }
+    }
+
+    #[test]
+    fn run_dashboard_partial_results_test() {
+        // the fixture has no investment solution, so the capacity analysis fails
+        let db_path: String = setup_test_db("dashboard_partial_results", DASHBOARD_FIXTURE_SQL);
+        let sections: Vec<DashboardSection> = run_dashboard(db_path, 2030, config(&["capacity", "system_cost"])).unwrap();
+
+        let analyses: Vec<&str> = sections.iter().map(|s| s.analysis.as_str()).collect();
+        assert_eq!(analyses, vec!["capacity", "system_cost"]);
+        assert!(sections[0].data.is_none() && sections[0].error.is_some());
+
+        assert!(sections[1].error.is_none(), "{:?}", sections[1].error);
+        assert_eq!(num_rows(&section_batches(&sections[1])), 1);
+    }
+
+    #[test]
+    fn run_dashboard_capacity_year_test() {
+        // 'solar' is still standing in 2050, the 2050 capacity must not be part of the 2030 dashboard
+        let db_path: String = setup_tulipa_db("dashboard_capacity_year", "
+            INSERT INTO year_data VALUES (2050, 8760, TRUE);
+            INSERT INTO asset_both VALUES ('solar', 2050, 2030, 1, 0);
+        ");
+        let sections: Vec<DashboardSection> = run_dashboard(db_path, 2030, config(&["capacity"])).unwrap();
+
+        assert!(sections[0].error.is_none(), "{:?}", sections[0].error);
+        assert_eq!(column_f64(&section_batches(&sections[0]), "year"), vec![Some(2030.0); 4]);
+    }
+
+    #[test]
+    fn run_dashboard_unknown_analysis_test() {
+        let db_path: String = setup_test_db("dashboard_unknown_analysis", DASHBOARD_FIXTURE_SQL);
+        let sections: Vec<DashboardSection> = run_dashboard(db_path, 2030, config(&["weather"])).unwrap();
+
+        assert_eq!(sections.len(), 1);
+        assert!(sections[0].error.as_deref().is_some_and(|e| e.contains("Unknown analysis 'weather'")));
+    }
+}
+
+// --- QUERIES ---
+const DASHBOARD_ANALYSES: [&str; 3] = ["capacity", "production_price", "system_cost"];
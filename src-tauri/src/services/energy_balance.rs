@@ -0,0 +1,384 @@
+use duckdb::arrow::{array::RecordBatch, datatypes::Schema};
+use duckdb::types::Value;
+use tauri::ipc::Response;
+use std::collections::HashMap;
+use crate::duckdb_conn::{run_query_rb, serialize_recordbatch};
+use crate::services::metadata::{check_column_in_table, check_series_cap, check_table_exists, ensure_metadata_available};
+use crate::services::query_builder::{build_breakdown_case_conditions, build_breakdown_joins, build_filter_conditions, with_category_descendants};
+
+// per location and carrier: production, consumption, imports and exports in one milestone year, with the same
+// weighting as the flow queries (resolution * weight * block length). A location is the asset's leaf category under
+// 'location', without metadata the whole system is one location (and nothing is imported or exported). Production
+// is what leaves the location's producer, conversion and storage assets, consumption what enters its consumer,
+// conversion and storage assets, and imports/exports are the transport flows entering/leaving the location; the
+// residual is what the location's hubs gain (or lose) and should net to ~0
+#[tauri::command]
+pub fn get_energy_balance(db_path: String, year: u32) -> Result<Response, String> {
+    let has_metadata: bool = check_table_exists(db_path.clone(), "category")? && check_table_exists(db_path.clone(), "asset_category")?;
+    let asset_location_sql: &str = if has_metadata { ASSET_LOCATION_SQL } else { NO_ASSET_LOCATION_SQL };
+    let query: String = ENERGY_BALANCE_SQL.replace("{asset_location}", asset_location_sql);
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, query, vec![Value::from(year)])?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// per storage asset: energy charged into and discharged out of it in one milestone year, weighted like
+// the energy balance, and the implied round-trip efficiency (None when nothing was charged)
+#[tauri::command]
+pub fn get_storage_throughput(db_path: String, year: u32) -> Result<Response, String> {
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, STORAGE_THROUGHPUT_SQL.to_string(), vec![Value::from(year)])?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// per carrier: the Herfindahl-Hirschman index of the producer and conversion assets' generation in one milestone
+// year, the sum of their squared shares, 1 for a single producer; carriers without any generation are left out
+#[tauri::command]
+pub fn get_generation_concentration(db_path: String, year: u32) -> Result<Response, String> {
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, GENERATION_CONCENTRATION_SQL.to_string(), vec![Value::from(year)])?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// per asset (or breakdown group): the generation of the producer and conversion assets in one milestone year,
+// weighted like the energy balance, times their `emission_factor`; a missing factor counts as zero emissions.
+// Filtering or grouping needs the metadata, a database without emission factors gives an empty result
+#[tauri::command]
+pub fn get_emissions(db_path: String, year: u32, filters: HashMap<i32, Vec<i32>>, grouper: Vec<i32>, max_series: Option<u32>) -> Result<Response, String> {
+    if !check_column_in_table(db_path.clone(), "asset", "emission_factor")? {
+        let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, EMPTY_EMISSIONS_SQL.to_string(), vec![Value::from(year)])?;
+        return serialize_recordbatch(res.0, res.1);
+    }
+    if !filters.is_empty() || !grouper.is_empty() {
+        ensure_metadata_available(&db_path)?;
+    }
+
+    let query: String = EMISSIONS_SQL
+        .replace("{breakdown_case_conditions}", &build_breakdown_case_conditions(&grouper, "p.asset".to_string()))
+        .replace("{breakdown_joins}", &build_breakdown_joins(&grouper))
+        .replace("{filter_conditions}", &build_filter_conditions(&filters, "p.asset".to_string()));
+    let query: String = with_category_descendants(&query, &filters, &grouper);
+    check_series_cap(&db_path, &query, "asset", vec![Value::from(year)], max_series)?;
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, query, vec![Value::from(year)])?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{setup_test_db, setup_tulipa_db, response_to_batches, num_rows, column_f64, column_string};
+
+    // electricity: 'solar' (NL) produces 5 per hour, transported through the hubs to 'demand' (BE),
+    // gas: 'gas_well' feeds 'ccgt' which converts it to electricity for the same demand
+    const BALANCED_FIXTURE_SQL: &str = "
+        CREATE TABLE asset(asset VARCHAR, type VARCHAR);
+        INSERT INTO asset VALUES ('solar', 'producer'), ('hub_nl', 'hub'), ('hub_be', 'hub'), ('demand', 'consumer'),
+            ('gas_well', 'producer'), ('ccgt', 'conversion');
+        CREATE TABLE flow(from_asset VARCHAR, to_asset VARCHAR, carrier VARCHAR, is_transport BOOLEAN);
+        INSERT INTO flow VALUES ('solar', 'hub_nl', 'electricity', FALSE), ('hub_nl', 'hub_be', 'electricity', TRUE),
+            ('hub_be', 'demand', 'electricity', FALSE), ('gas_well', 'ccgt', 'gas', FALSE), ('ccgt', 'demand', 'electricity', FALSE);
+        CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, solution DOUBLE);
+        INSERT INTO var_flow VALUES
+            ('solar', 'hub_nl', 2030, 1, 1, 2, 5), ('hub_nl', 'hub_be', 2030, 1, 1, 2, 5), ('hub_be', 'demand', 2030, 1, 1, 2, 5),
+            ('gas_well', 'ccgt', 2030, 1, 1, 2, 4), ('ccgt', 'demand', 2030, 1, 1, 2, 2);
+        CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+        INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 2), (2030, 2, 1, 1);
+        CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+        INSERT INTO rep_periods_data VALUES (2030, 1, 2, 1);
+    ";
+
+    #[test]
+    fn get_energy_balance_balanced_test() {
+        let db_path: String = setup_test_db("energy_balance_balanced", BALANCED_FIXTURE_SQL);
+        let batches = response_to_batches(get_energy_balance(db_path, 2030).unwrap());
+
+        // total weight 3 over 2 hours; without metadata the system is one location, which trades with no other
+        assert_eq!(column_string(&batches, "carrier"), vec![Some("electricity".to_string()), Some("gas".to_string())]);
+        assert_eq!(column_string(&batches, "location"), vec![None, None]);
+        assert_eq!(column_f64(&batches, "production"), vec![Some(42.0), Some(24.0)]);
+        assert_eq!(column_f64(&batches, "consumption"), vec![Some(42.0), Some(24.0)]);
+        assert_eq!(column_f64(&batches, "imports"), vec![Some(0.0), Some(0.0)]);
+        assert_eq!(column_f64(&batches, "exports"), vec![Some(0.0), Some(0.0)]);
+        for residual in column_f64(&batches, "residual").into_iter().flatten() {
+            assert!(residual.abs() < 1e-9, "unbalanced residual: {}", residual);
+        }
+    }
+
+    #[test]
+    fn get_generation_concentration_test() {
+        // electricity is generated by 'solar' (30) and 'ccgt' (12), gas only by 'gas_well'
+        let db_path: String = setup_test_db("generation_concentration", BALANCED_FIXTURE_SQL);
+        let batches = response_to_batches(get_generation_concentration(db_path, 2030).unwrap());
+
+        assert_eq!(column_string(&batches, "carrier"), vec![Some("electricity".to_string()), Some("gas".to_string())]);
+        assert_eq!(column_f64(&batches, "producers"), vec![Some(2.0), Some(1.0)]);
+        let hhi: Vec<f64> = column_f64(&batches, "hhi").into_iter().flatten().collect();
+        let exp_hhi: f64 = (30.0_f64 / 42.0).powi(2) + (12.0_f64 / 42.0).powi(2);
+        assert!((hhi[0] - exp_hhi).abs() < 1e-9, "unexpected index: {}", hhi[0]);
+        assert!((hhi[1] - 1.0).abs() < 1e-9, "unexpected index: {}", hhi[1]);
+    }
+
+    #[test]
+    fn get_storage_throughput_test() {
+        // 'battery' charges 10 and discharges 8 per hour over 2 hours with weight 3, 'idle' never charges
+        let db_path: String = setup_test_db("storage_throughput", "
+            CREATE TABLE asset(asset VARCHAR, type VARCHAR);
+            INSERT INTO asset VALUES ('solar', 'producer'), ('battery', 'storage'), ('idle', 'storage'), ('demand', 'consumer');
+            CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, solution DOUBLE);
+            INSERT INTO var_flow VALUES
+                ('solar', 'battery', 2030, 1, 1, 2, 10), ('battery', 'demand', 2030, 1, 1, 2, 8), ('battery', 'demand', 2050, 1, 1, 2, 8);
+            CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+            INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 3);
+            CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+            INSERT INTO rep_periods_data VALUES (2030, 1, 2, 1);
+        ");
+        let batches = response_to_batches(get_storage_throughput(db_path, 2030).unwrap());
+
+        assert_eq!(column_string(&batches, "asset"), vec![Some("battery".to_string()), Some("idle".to_string())]);
+        assert_eq!(column_f64(&batches, "charged"), vec![Some(60.0), Some(0.0)]);
+        assert_eq!(column_f64(&batches, "discharged"), vec![Some(48.0), Some(0.0)]);
+        assert_eq!(column_f64(&batches, "efficiency"), vec![Some(0.8), None]);
+    }
+
+    // 'gas_well' emits 0.25 and 'ccgt' 0.5 per unit produced, 'solar' has no factor
+    const EMISSION_FACTORS_SQL: &str = "
+        ALTER TABLE asset ADD COLUMN emission_factor DOUBLE;
+        UPDATE asset SET emission_factor = 0.25 WHERE asset = 'gas_well';
+        UPDATE asset SET emission_factor = 0.5 WHERE asset = 'ccgt';
+    ";
+
+    #[test]
+    fn get_emissions_test() {
+        let db_path: String = setup_tulipa_db("emissions", EMISSION_FACTORS_SQL);
+        let batches = response_to_batches(get_emissions(db_path.clone(), 2030, HashMap::new(), vec![], None).unwrap());
+
+        // 'gas_well' produces 6 and 'ccgt' 3, storage and hubs only pass energy on
+        assert_eq!(column_string(&batches, "asset"), vec![
+            Some("ccgt".to_string()), Some("gas_well".to_string()), Some("solar".to_string()),
+        ]);
+        assert_eq!(column_f64(&batches, "emissions"), vec![Some(1.5), Some(1.5), Some(0.0)]);
+
+        let grouped = response_to_batches(get_emissions(db_path, 2030, HashMap::new(), vec![7, 8], None).unwrap());
+        assert_eq!(column_string(&grouped, "asset"), vec![Some("renewable".to_string()), Some("thermal".to_string())]);
+        assert_eq!(column_f64(&grouped, "production"), vec![Some(6.0), Some(9.0)]);
+        assert_eq!(column_f64(&grouped, "emissions"), vec![Some(0.0), Some(3.0)]);
+    }
+
+    #[test]
+    fn get_emissions_without_factors_test() {
+        let db_path: String = setup_tulipa_db("emissions_without_factors", "");
+        let batches = response_to_batches(get_emissions(db_path, 2030, HashMap::new(), vec![], None).unwrap());
+        assert_eq!(num_rows(&batches), 0);
+    }
+
+    #[test]
+    fn get_energy_balance_tulipa_fixture_test() {
+        let db_path: String = setup_tulipa_db("energy_balance_tulipa_fixture", "");
+        let batches = response_to_batches(get_energy_balance(db_path, 2030).unwrap());
+
+        // BE: 'battery' 2 and the 9 imported from NL against 'demand' 10 and the battery charge 1,
+        // NL: 'solar' 6 and 'ccgt' 3 all exported, the gas of 'gas_well' burnt by 'ccgt'
+        assert_eq!(column_string(&batches, "location"), vec![
+            Some("BE_nodes".to_string()), Some("NL_nodes".to_string()), Some("NL_nodes".to_string()),
+        ]);
+        assert_eq!(column_string(&batches, "carrier"), vec![
+            Some("electricity".to_string()), Some("electricity".to_string()), Some("gas".to_string()),
+        ]);
+        assert_eq!(column_f64(&batches, "production"), vec![Some(2.0), Some(9.0), Some(6.0)]);
+        assert_eq!(column_f64(&batches, "consumption"), vec![Some(11.0), Some(0.0), Some(6.0)]);
+        assert_eq!(column_f64(&batches, "imports"), vec![Some(9.0), Some(0.0), Some(0.0)]);
+        assert_eq!(column_f64(&batches, "exports"), vec![Some(0.0), Some(9.0), Some(0.0)]);
+        assert_eq!(column_f64(&batches, "residual"), vec![Some(0.0), Some(0.0), Some(0.0)]);
+    }
+
+    #[test]
+    fn get_energy_balance_unbalanced_test() {
+        // 'hub_be' passes only 3 of the 6 it receives in the second hour on to 'demand'
+        let db_path: String = setup_tulipa_db("energy_balance_unbalanced", "
+            UPDATE var_flow SET solution = 3 WHERE from_asset = 'hub_be' AND to_asset = 'demand' AND time_block_start = 2;
+        ");
+        let batches = response_to_batches(get_energy_balance(db_path, 2030).unwrap());
+
+        assert_eq!(column_f64(&batches, "consumption"), vec![Some(8.0), Some(0.0), Some(6.0)]);
+        assert_eq!(column_f64(&batches, "residual"), vec![Some(3.0), Some(0.0), Some(0.0)]);
+    }
+}
+
+// --- QUERIES ---
+const ENERGY_BALANCE_SQL: &str = "
+    WITH asset_location AS (
+        {asset_location}
+    ), weighted_flows AS ( -- energy of every flow, weighted like in the import/export and residual load queries
+        SELECT
+            f.carrier,
+            vf.year,
+            f.is_transport,
+            a_from.type AS from_type,
+            a_to.type AS to_type,
+            l_from.location AS from_location,
+            l_to.location AS to_location,
+            rpd.resolution * rpm.weight * (vf.time_block_end - vf.time_block_start + 1) * vf.solution AS energy
+        FROM var_flow AS vf
+        JOIN flow AS f ON f.from_asset = vf.from_asset AND f.to_asset = vf.to_asset
+        JOIN asset AS a_from ON a_from.asset = vf.from_asset
+        JOIN asset AS a_to ON a_to.asset = vf.to_asset
+        JOIN asset_location AS l_from ON l_from.asset = vf.from_asset
+        JOIN asset_location AS l_to ON l_to.asset = vf.to_asset
+        JOIN rep_periods_mapping AS rpm ON rpm.year = vf.year AND rpm.rep_period = vf.rep_period
+        JOIN rep_periods_data AS rpd ON rpd.year = rpm.year AND rpd.rep_period = rpm.rep_period
+        WHERE vf.year = $1
+    ), location_flows AS ( -- every flow once from the sending and once from the receiving location
+        SELECT
+            from_location AS location,
+            carrier,
+            year,
+            -- hubs only pass energy on, storage discharging produces
+            CASE WHEN from_type IN ('producer', 'conversion', 'storage') THEN energy ELSE 0 END AS production,
+            0 AS consumption,
+            0 AS imports,
+            CASE WHEN is_transport AND to_location IS DISTINCT FROM from_location THEN energy ELSE 0 END AS exports
+        FROM weighted_flows
+        UNION ALL
+        SELECT
+            to_location AS location,
+            carrier,
+            year,
+            0 AS production,
+            -- storage charging consumes
+            CASE WHEN to_type IN ('consumer', 'conversion', 'storage') THEN energy ELSE 0 END AS consumption,
+            CASE WHEN is_transport AND to_location IS DISTINCT FROM from_location THEN energy ELSE 0 END AS imports,
+            0 AS exports
+        FROM weighted_flows
+    ), balance AS (
+        SELECT
+            location,
+            carrier,
+            year AS milestone_year,
+            SUM(production) AS production,
+            SUM(consumption) AS consumption,
+            SUM(imports) AS imports,
+            SUM(exports) AS exports
+        FROM location_flows
+        GROUP BY location, carrier, year
+    )
+    SELECT
+        *,
+        production + imports - consumption - exports AS residual
+    FROM balance
+    ORDER BY milestone_year, location, carrier;
+";
+
+// the location of every asset for ENERGY_BALANCE_SQL: its leaf category under 'location', NULL outside the tree
+const ASSET_LOCATION_SQL: &str = "
+        SELECT a.asset, ANY_VALUE(c.name) AS location
+        FROM asset AS a
+        LEFT JOIN asset_category AS ac ON ac.asset = a.asset
+            AND ac.root_id IN (SELECT id FROM category WHERE name = 'location')
+        LEFT JOIN category AS c ON c.id = ac.leaf_id
+        GROUP BY a.asset";
+
+const NO_ASSET_LOCATION_SQL: &str = "SELECT asset, CAST(NULL AS VARCHAR) AS location FROM asset";
+
+const STORAGE_THROUGHPUT_SQL: &str = "
+    WITH weighted_flows AS (
+        SELECT
+            vf.from_asset,
+            vf.to_asset,
+            rpd.resolution * rpm.weight * (vf.time_block_end - vf.time_block_start + 1) * vf.solution AS energy
+        FROM var_flow AS vf
+        JOIN rep_periods_mapping AS rpm ON rpm.year = vf.year AND rpm.rep_period = vf.rep_period
+        JOIN rep_periods_data AS rpd ON rpd.year = rpm.year AND rpd.rep_period = rpm.rep_period
+        WHERE vf.year = $1
+    ), throughput AS (
+        SELECT
+            a.asset,
+            COALESCE(SUM(wf.energy) FILTER (WHERE wf.to_asset = a.asset), 0) AS charged,
+            COALESCE(SUM(wf.energy) FILTER (WHERE wf.from_asset = a.asset), 0) AS discharged
+        FROM asset AS a
+        LEFT JOIN weighted_flows AS wf ON wf.to_asset = a.asset OR wf.from_asset = a.asset
+        WHERE a.type = 'storage'
+        GROUP BY a.asset
+    )
+    SELECT
+        asset,
+        $1 AS milestone_year,
+        charged,
+        discharged,
+        -- no efficiency without any charge
+        CASE WHEN charged > 0 THEN discharged / charged END AS efficiency
+    FROM throughput
+    ORDER BY asset;
+";
+
+
+const GENERATION_CONCENTRATION_SQL: &str = "
+    WITH generation AS ( -- energy generated by each producer and conversion asset, weighted like the energy balance
+        SELECT
+            f.carrier,
+            vf.from_asset AS asset,
+            SUM(rpd.resolution * rpm.weight * (vf.time_block_end - vf.time_block_start + 1) * vf.solution) AS energy
+        FROM var_flow AS vf
+        JOIN flow AS f ON f.from_asset = vf.from_asset AND f.to_asset = vf.to_asset
+        JOIN asset AS a ON a.asset = vf.from_asset
+        JOIN rep_periods_mapping AS rpm ON rpm.year = vf.year AND rpm.rep_period = vf.rep_period
+        JOIN rep_periods_data AS rpd ON rpd.year = rpm.year AND rpd.rep_period = rpm.rep_period
+        WHERE vf.year = $1 AND a.type IN ('producer', 'conversion')
+        GROUP BY f.carrier, vf.from_asset
+    ), shares AS (
+        SELECT
+            carrier,
+            energy / SUM(energy) OVER (PARTITION BY carrier) AS share
+        FROM generation
+        WHERE energy > 0
+    )
+    SELECT
+        carrier,
+        $1 AS milestone_year,
+        COUNT(*) AS producers,
+        SUM(share * share) AS hhi
+    FROM shares
+    GROUP BY carrier
+    ORDER BY carrier;
+";
+
+const EMISSIONS_SQL: &str = "
+    WITH production AS ( -- energy leaving each asset, weighted like the energy balance
+        SELECT
+            vf.from_asset AS asset,
+            SUM(rpd.resolution * rpm.weight * (vf.time_block_end - vf.time_block_start + 1) * vf.solution) AS production
+        FROM var_flow AS vf
+        JOIN rep_periods_mapping AS rpm ON rpm.year = vf.year AND rpm.rep_period = vf.rep_period
+        JOIN rep_periods_data AS rpd ON rpd.year = rpm.year AND rpd.rep_period = rpm.rep_period
+        WHERE vf.year = $1
+        GROUP BY vf.from_asset
+    )
+    SELECT
+        CASE
+            {breakdown_case_conditions}
+            ELSE 'Other'
+        END AS asset,
+        $1 AS year,
+        SUM(p.production) AS production,
+        SUM(p.production * COALESCE(a.emission_factor, 0)) AS emissions
+    FROM production AS p
+    JOIN asset AS a ON a.asset = p.asset
+    {breakdown_joins}
+    WHERE a.type IN ('producer', 'conversion') {filter_conditions}
+    GROUP BY
+        CASE
+            {breakdown_case_conditions}
+            ELSE 'Other'
+        END
+    ORDER BY 1;
+";
+
+const EMPTY_EMISSIONS_SQL: &str = "
+    SELECT
+        CAST(NULL AS VARCHAR) AS asset,
+        CAST(? AS INTEGER) AS year,
+        CAST(NULL AS DOUBLE) AS production,
+        CAST(NULL AS DOUBLE) AS emissions
+    WHERE FALSE;
+";
\ No newline at end of file
@@ -1,38 +1,344 @@
-use duckdb::types::Value;
+use duckdb::{ types::Value, Row };
 use serde::Deserialize;
 use tauri::ipc::Response;
 
-use crate::duckdb_conn::{run_query_rb, serialize_recordbatch};
+use crate::duckdb_conn::{run_query_rb, run_query_row, serialize_recordbatch};
 
 #[derive(Deserialize)]
 pub struct EnergyFlowOptions {
     level: u32,
     year: u32,
+    // also report the flows within each location group (aggregate flows only)
+    #[serde(default)]
+    include_internal: bool,
+    // "energy" (total MWh, default) or "power" (average MW over the represented hours)
+    #[serde(default = "default_unit")]
+    unit: String,
+    // a deeper level also reported, each of its groups with the id of its group at `level` (aggregate flows only)
+    #[serde(default)]
+    child_level: Option<u32>,
+    // each flow as a fraction of the largest flow in the result, all zero when there is no flow (detailed flows only)
+    #[serde(default)]
+    normalize: bool,
+}
+
+fn default_unit() -> String {
+    "energy".to_string()
 }
 
 #[tauri::command]
 pub fn get_all_aggregate_flows(db_path: String, options: EnergyFlowOptions) -> Result<Response, String> {
-    let res = run_query_rb(db_path, RESULT_INCOMPLETE_SQL.to_string() + AGGREGATE_FLOW_SQL, vec![Value::from(options.level), Value::from(options.year)])?;
+    validate_level(&db_path, options.level)?;
+    if let Some(child_level) = options.child_level {
+        validate_level(&db_path, child_level)?;
+        if child_level >= options.level {
+            return Err(format!("Child level {} must be below level {}", child_level, options.level));
+        }
+    }
+    let sql: String = if options.include_internal {
+        RESULT_INCOMPLETE_SQL.to_string() + INTERNAL_FLOW_SQL + &AGGREGATE_FLOW_SQL.replace("{internal_select}", INTERNAL_FLOW_SELECT_SQL)
+    } else {
+        RESULT_INCOMPLETE_SQL.to_string() + &AGGREGATE_FLOW_SQL.replace("{internal_select}", "")
+    };
+    let sql: String = with_child_level(&sql, options.child_level);
+    let sql: String = with_flow_unit(&sql, &options.unit)?;
+    let res = run_query_rb(db_path, sql, vec![Value::from(options.level), Value::from(options.year)])?;
     return serialize_recordbatch(res.0, res.1);
 }
 
 #[tauri::command]
 pub fn get_all_detailed_flows(db_path: String, options: EnergyFlowOptions) -> Result<Response, String> {
-    let res = run_query_rb(db_path, RESULT_INCOMPLETE_SQL.to_string() + DETAILED_FLOW_SQL, vec![Value::from(options.level), Value::from(options.year)])?;
+    validate_level(&db_path, options.level)?;
+    let sql: String = with_child_level(&(RESULT_INCOMPLETE_SQL.to_string() + DETAILED_FLOW_SQL), None);
+    let sql: String = with_flow_unit(&sql, &options.unit)?;
+    let sql: String = if options.normalize { NORMALIZED_FLOW_SQL.replace("{source}", &sql) } else { sql };
+    let res = run_query_rb(db_path, sql, vec![Value::from(options.level), Value::from(options.year)])?;
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// total import, export and net balance (import minus export) of each location group at `level`, in energy
+#[tauri::command]
+pub fn get_trade_balance(db_path: String, year: u32, level: u32) -> Result<Response, String> {
+    validate_level(&db_path, level)?;
+    let sql: String = with_child_level(&(RESULT_INCOMPLETE_SQL.to_string() + TRADE_BALANCE_SQL), None);
+    let sql: String = with_flow_unit(&sql, "energy")?;
+    let res = run_query_rb(db_path, sql, vec![Value::from(level), Value::from(year)])?;
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// flow per asset pair and representative period, once for a single occurrence of the rep period (unweighted)
+// and once multiplied by its total weight in the year, summing the weighted flows gives the annual flow
+#[tauri::command]
+pub fn get_flows_by_rep_period(db_path: String, year: u32) -> Result<Response, String> {
+    let res = run_query_rb(db_path, build_flows_by_rep_period_query(), vec![Value::from(year)])?;
     return serialize_recordbatch(res.0, res.1);
 }
 
+// the query of `get_flows_by_rep_period`, shared with the analysis bundle, takes the year as its only parameter
+pub fn build_flows_by_rep_period_query() -> String {
+    FLOWS_BY_REP_PERIOD_SQL.to_string()
+}
+
 #[tauri::command]
 pub fn get_available_years_flows(db_path: String) -> Result<Response, String> {
     let res = run_query_rb(db_path, FETCH_YEARS_SQL.to_string(), vec![])?;
     return serialize_recordbatch(res.0, res.1);
 }
 
+// a level missing from the category tree would silently return no flows at all
+fn validate_level(db_path: &str, level: u32) -> Result<(), String> {
+    let levels: Vec<i64> = run_query_row(
+        db_path.to_string(),
+        CATEGORY_LEVELS_SQL.to_string(),
+        vec![],
+        |row: &Row<'_>| Ok(row.get::<usize, i64>(0)?),
+    )?;
+
+    if levels.contains(&i64::from(level)) {
+        Ok(())
+    } else {
+        let valid: Vec<String> = levels.iter().map(|l| l.to_string()).collect();
+        Err(format!("Invalid level {}, available levels are: {}", level, valid.join(", ")))
+    }
+}
+
+// the groups are built at both `level` ($1) and the child level, flows are only counted between groups of the same
+// level; without a child level only `level` is used and no parent column is added
+fn with_child_level(sql: &str, child_level: Option<u32>) -> String {
+    match child_level {
+        Some(child_level) => sql
+            .replace("{child_level}", &child_level.to_string())
+            .replace("{parent_select}", PARENT_SELECT_SQL),
+        None => sql.replace("{child_level}", "$1").replace("{parent_select}", ""),
+    }
+}
+
+// flows are summed to energy, power divides that by the hours the year's representative periods stand for
+fn with_flow_unit(sql: &str, unit: &str) -> Result<String, String> {
+    match unit {
+        "energy" => Ok(sql.replace("{unit_divisor}", "")),
+        "power" => Ok(sql.replace("{unit_divisor}", POWER_DIVISOR_SQL)),
+        _ => Err(format!("Invalid unit '{}', expected 'energy' or 'power'", unit)),
+    }
+}
+
 // --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{setup_test_db, setup_tulipa_db, response_to_batches, column_f64, column_string};
+
+    // two countries under 'location', 'nl_a' sends 2 to 'nl_b' (same country) and 3 to 'be_a'
+    const FLOWS_FIXTURE_SQL: &str = "
+        CREATE TABLE category(id INTEGER, name VARCHAR, parent_id INTEGER, level INTEGER);
+        INSERT INTO category VALUES (1, 'location', NULL, 2), (2, 'NL', 1, 1), (3, 'BE', 1, 1), (4, 'NL_nodes', 2, 0), (5, 'BE_nodes', 3, 0);
+        CREATE TABLE asset(asset VARCHAR);
+        INSERT INTO asset VALUES ('nl_a'), ('nl_b'), ('be_a');
+        CREATE TABLE asset_category(asset VARCHAR, root_id INTEGER, leaf_id INTEGER);
+        INSERT INTO asset_category VALUES ('nl_a', 1, 4), ('nl_b', 1, 4), ('be_a', 1, 5);
+        CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, solution DOUBLE);
+        INSERT INTO var_flow VALUES ('nl_a', 'nl_b', 2030, 1, 1, 1, 2), ('nl_a', 'be_a', 2030, 1, 1, 1, 3);
+        CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+        INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 1);
+        CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+        INSERT INTO rep_periods_data VALUES (2030, 1, 1, 1);
+    ";
+
+    #[test]
+    fn get_all_aggregate_flows_excludes_internal_test() {
+        let db_path: String = setup_test_db("aggregate_flows_default", FLOWS_FIXTURE_SQL);
+        let options = EnergyFlowOptions { level: 1, year: 2030, include_internal: false, unit: default_unit(), child_level: None, normalize: false };
+        let batches = response_to_batches(get_all_aggregate_flows(db_path, options).unwrap());
+
+        assert_eq!(column_string(&batches, "group"), vec![Some("NL".to_string()), Some("BE".to_string())]);
+        assert_eq!(column_f64(&batches, "totalExport"), vec![Some(3.0), Some(0.0)]);
+        assert_eq!(column_f64(&batches, "totalImport"), vec![Some(0.0), Some(3.0)]);
+        assert!(batches[0].column_by_name("internalFlow").is_none());
+    }
+
+    #[test]
+    fn get_all_aggregate_flows_child_level_test() {
+        let db_path: String = setup_test_db("aggregate_flows_child_level", FLOWS_FIXTURE_SQL);
+        let options = EnergyFlowOptions { level: 1, year: 2030, include_internal: false, unit: default_unit(), child_level: Some(0), normalize: false };
+        let batches = response_to_batches(get_all_aggregate_flows(db_path, options).unwrap());
+
+        // countries first, then their node groups with the country they belong to
+        assert_eq!(column_string(&batches, "group"), vec![
+            Some("NL".to_string()), Some("BE".to_string()), Some("NL_nodes".to_string()), Some("BE_nodes".to_string()),
+        ]);
+        assert_eq!(column_f64(&batches, "parentId"), vec![None, None, Some(2.0), Some(3.0)]);
+        assert_eq!(column_f64(&batches, "totalExport"), vec![Some(3.0), Some(0.0), Some(3.0), Some(0.0)]);
+        assert_eq!(column_f64(&batches, "totalImport"), vec![Some(0.0), Some(3.0), Some(0.0), Some(3.0)]);
+    }
+
+    #[test]
+    fn get_all_aggregate_flows_child_level_above_level_test() {
+        let db_path: String = setup_test_db("aggregate_flows_child_level_above", FLOWS_FIXTURE_SQL);
+        let options = EnergyFlowOptions { level: 1, year: 2030, include_internal: false, unit: default_unit(), child_level: Some(2), normalize: false };
+        let res = get_all_aggregate_flows(db_path, options);
+        assert!(res.is_err() && res.unwrap_err().contains("must be below level"));
+    }
+
+    #[test]
+    fn get_trade_balance_test() {
+        // 'be_a' also sends 1 back to 'nl_b'
+        let fixture: String = FLOWS_FIXTURE_SQL.to_string() + "INSERT INTO var_flow VALUES ('be_a', 'nl_b', 2030, 1, 1, 1, 1);";
+        let db_path: String = setup_test_db("trade_balance", &fixture);
+        let batches = response_to_batches(get_trade_balance(db_path, 2030, 1).unwrap());
+
+        assert_eq!(column_string(&batches, "group"), vec![Some("NL".to_string()), Some("BE".to_string())]);
+        assert_eq!(column_f64(&batches, "totalImport"), vec![Some(1.0), Some(3.0)]);
+        assert_eq!(column_f64(&batches, "totalExport"), vec![Some(3.0), Some(1.0)]);
+        let expected: Vec<Option<f64>> = column_f64(&batches, "totalImport").into_iter()
+            .zip(column_f64(&batches, "totalExport"))
+            .map(|(import, export)| Some(import.unwrap() - export.unwrap()))
+            .collect();
+        assert_eq!(column_f64(&batches, "netBalance"), expected);
+        assert_eq!(expected, vec![Some(-2.0), Some(2.0)]);
+    }
+
+    #[test]
+    fn get_flows_by_rep_period_sums_to_annual_test() {
+        // a second rep period, standing for 3 periods, in which 'nl_a' sends 4 to 'be_a' for 2 hours
+        let fixture: String = FLOWS_FIXTURE_SQL.to_string() + "
+            INSERT INTO rep_periods_mapping VALUES (2030, 2, 2, 3);
+            INSERT INTO rep_periods_data VALUES (2030, 2, 2, 1);
+            INSERT INTO var_flow VALUES ('nl_a', 'be_a', 2030, 2, 1, 2, 4);
+        ";
+        let db_path: String = setup_test_db("flows_by_rep_period", &fixture);
+        let by_rep_period = response_to_batches(get_flows_by_rep_period(db_path.clone(), 2030).unwrap());
+        let annual = response_to_batches(get_all_detailed_flows(
+            db_path, EnergyFlowOptions { level: 1, year: 2030, include_internal: false, unit: default_unit(), child_level: None, normalize: false },
+        ).unwrap());
+
+        let pairs: Vec<(Option<String>, Option<String>)> = column_string(&by_rep_period, "from_asset").into_iter()
+            .zip(column_string(&by_rep_period, "to_asset"))
+            .collect();
+        let nl_be_weighted: f64 = pairs.iter().zip(column_f64(&by_rep_period, "weighted_flow"))
+            .filter(|((from, to), _)| from.as_deref() == Some("nl_a") && to.as_deref() == Some("be_a"))
+            .map(|(_, weighted)| weighted.unwrap())
+            .sum();
+        let nl_be_annual: Option<f64> = column_string(&annual, "fromName").into_iter()
+            .zip(column_string(&annual, "toName"))
+            .zip(column_f64(&annual, "totFlow"))
+            .find(|((from, to), _)| from.as_deref() == Some("NL") && to.as_deref() == Some("BE"))
+            .and_then(|(_, tot_flow)| tot_flow);
+
+        assert_eq!(column_f64(&by_rep_period, "unweighted_flow"), vec![Some(3.0), Some(8.0), Some(2.0)]);
+        assert_eq!(nl_be_weighted, 27.0);
+        assert_eq!(nl_be_annual, Some(nl_be_weighted));
+    }
+
+    #[test]
+    fn get_all_detailed_flows_power_test() {
+        // a year of 365 days represented by a single day of 24 hourly blocks
+        let fixture: String = FLOWS_FIXTURE_SQL.to_string() + "
+            UPDATE rep_periods_mapping SET weight = 365;
+            UPDATE rep_periods_data SET num_timesteps = 24;
+        ";
+        let db_path: String = setup_test_db("detailed_flows_power", &fixture);
+        let energy = response_to_batches(get_all_detailed_flows(
+            db_path.clone(), EnergyFlowOptions { level: 1, year: 2030, include_internal: false, unit: "energy".to_string(), child_level: None, normalize: false },
+        ).unwrap());
+        let power = response_to_batches(get_all_detailed_flows(
+            db_path, EnergyFlowOptions { level: 1, year: 2030, include_internal: false, unit: "power".to_string(), child_level: None, normalize: false },
+        ).unwrap());
+
+        let exp_power: Vec<Option<f64>> = column_f64(&energy, "totFlow").iter().map(|e| e.map(|e| e / 8760.0)).collect();
+        assert_eq!(column_f64(&power, "totFlow"), exp_power);
+        assert!(column_f64(&power, "totFlow").contains(&Some(0.125)));
+    }
+
+    #[test]
+    fn get_all_detailed_flows_normalize_test() {
+        // 'nl_a' sends another 1.5 to 'be_a' and 'be_a' 1.5 back to 'nl_b', the largest flow is NL -> BE with 4.5
+        let fixture: String = FLOWS_FIXTURE_SQL.to_string() + "
+            INSERT INTO var_flow VALUES ('nl_a', 'be_a', 2030, 1, 2, 2, 1.5), ('be_a', 'nl_b', 2030, 1, 1, 1, 1.5);
+        ";
+        let db_path: String = setup_test_db("detailed_flows_normalize", &fixture);
+        let options = EnergyFlowOptions { level: 1, year: 2030, include_internal: false, unit: default_unit(), child_level: None, normalize: true };
+        let batches = response_to_batches(get_all_detailed_flows(db_path, options).unwrap());
+
+        // NL -> NL, NL -> BE, BE -> NL, BE -> BE
+        assert_eq!(column_f64(&batches, "totFlow"), vec![Some(0.0), Some(1.0), Some(1.5 / 4.5), Some(0.0)]);
+    }
+
+    #[test]
+    fn get_all_detailed_flows_normalize_no_flow_test() {
+        let fixture: String = FLOWS_FIXTURE_SQL.to_string() + "UPDATE var_flow SET solution = 0;";
+        let db_path: String = setup_test_db("detailed_flows_normalize_no_flow", &fixture);
+        let options = EnergyFlowOptions { level: 1, year: 2030, include_internal: false, unit: default_unit(), child_level: None, normalize: true };
+        let batches = response_to_batches(get_all_detailed_flows(db_path, options).unwrap());
+
+        assert_eq!(column_f64(&batches, "totFlow"), vec![Some(0.0); 4]);
+    }
+
+    #[test]
+    fn get_all_detailed_flows_invalid_unit_test() {
+        let db_path: String = setup_test_db("detailed_flows_invalid_unit", FLOWS_FIXTURE_SQL);
+        let options = EnergyFlowOptions { level: 1, year: 2030, include_internal: false, unit: "joule".to_string(), child_level: None, normalize: false };
+        assert!(get_all_detailed_flows(db_path, options).err().unwrap().contains("Invalid unit"));
+    }
+
+    #[test]
+    fn get_all_detailed_flows_invalid_level_test() {
+        let db_path: String = setup_test_db("detailed_flows_invalid_level", FLOWS_FIXTURE_SQL);
+        let options = EnergyFlowOptions { level: 7, year: 2030, include_internal: false, unit: default_unit(), child_level: None, normalize: false };
+        let res = get_all_detailed_flows(db_path, options);
+        assert!(res.is_err() && res.err().unwrap() == "Invalid level 7, available levels are: 0, 1, 2");
+    }
+
+    #[test]
+    fn get_all_aggregate_flows_include_internal_test() {
+        let db_path: String = setup_test_db("aggregate_flows_internal", FLOWS_FIXTURE_SQL);
+        let options = EnergyFlowOptions { level: 1, year: 2030, include_internal: true, unit: default_unit(), child_level: None, normalize: false };
+        let batches = response_to_batches(get_all_aggregate_flows(db_path, options).unwrap());
+
+        // the internal flow is reported separately, imports and exports stay between groups
+        assert_eq!(column_f64(&batches, "internalFlow"), vec![Some(2.0), Some(0.0)]);
+        assert_eq!(column_f64(&batches, "totalExport"), vec![Some(3.0), Some(0.0)]);
+        assert_eq!(column_f64(&batches, "totalImport"), vec![Some(0.0), Some(3.0)]);
+    }
+
+    #[test]
+    fn get_trade_balance_tulipa_fixture_test() {
+        // only the transport from 'hub_nl' to 'hub_be' crosses a border
+        let db_path: String = setup_tulipa_db("trade_balance_tulipa_fixture", "");
+        let batches = response_to_batches(get_trade_balance(db_path, 2030, 1).unwrap());
+
+        assert_eq!(column_string(&batches, "group"), vec![Some("NL".to_string()), Some("BE".to_string())]);
+        assert_eq!(column_f64(&batches, "totalImport"), vec![Some(0.0), Some(9.0)]);
+        assert_eq!(column_f64(&batches, "totalExport"), vec![Some(9.0), Some(0.0)]);
+        assert_eq!(column_f64(&batches, "netBalance"), vec![Some(-9.0), Some(9.0)]);
+    }
+}
 
 // --- QUERIES ---
 const FETCH_YEARS_SQL: &str = "SELECT DISTINCT year FROM rep_periods_mapping";
 
+const FLOWS_BY_REP_PERIOD_SQL: &str = "
+    WITH rep_period_weights AS ( -- total weight of each rep period in the year
+        SELECT rpm.year, rpm.rep_period, SUM(rpm.weight) AS weight
+            FROM rep_periods_mapping rpm
+            WHERE rpm.year = $1
+            GROUP BY rpm.year, rpm.rep_period
+    )
+    SELECT
+        vf.from_asset,
+        vf.to_asset,
+        vf.rep_period,
+        rpw.weight,
+        SUM(rpd.resolution * (vf.time_block_end - vf.time_block_start + 1) * vf.solution) AS unweighted_flow,
+        SUM(rpd.resolution * (vf.time_block_end - vf.time_block_start + 1) * vf.solution) * rpw.weight AS weighted_flow
+        FROM var_flow vf
+        JOIN rep_period_weights rpw ON rpw.year = vf.year AND rpw.rep_period = vf.rep_period
+        JOIN rep_periods_data rpd ON rpd.year = vf.year AND rpd.rep_period = vf.rep_period
+        GROUP BY vf.from_asset, vf.to_asset, vf.rep_period, rpw.weight
+        ORDER BY vf.from_asset, vf.to_asset, vf.rep_period
+";
+
+const CATEGORY_LEVELS_SQL: &str = "SELECT DISTINCT level FROM category WHERE level IS NOT NULL ORDER BY level";
+
 const RESULT_INCOMPLETE_SQL: &str = "
     WITH RECURSIVE location_nodes AS ( -- all nodes which are under location
         -- base case
@@ -60,12 +366,12 @@ const RESULT_INCOMPLETE_SQL: &str = "
             JOIN category c ON c.parent_id = rt.id
             WHERE c.id IN (SELECT id FROM location_nodes)
     ), root_leaf AS ( -- goes from our target level (i.e. countries) to their leaves
-        SELECT rt.root_id, rt.id
+        SELECT rt.root_id, rt.root_level, rt.id
             FROM recursive_trees rt
             WHERE rt.level = 0
-            AND rt.root_level = $1
+            AND rt.root_level IN ($1, {child_level})
     ), root_asset AS ( -- from target level to assets
-        SELECT rl.root_id, a.asset
+        SELECT rl.root_id, rl.root_level, a.asset
             FROM asset_category ac
             RIGHT JOIN root_leaf rl ON rl.id = ac.leaf_id
             JOIN asset a ON a.asset = ac.asset
@@ -73,13 +379,14 @@ const RESULT_INCOMPLETE_SQL: &str = "
         SELECT 
             ra_from.root_id AS from_id,
             ra_to.root_id AS to_id,
-            SUM(rpd.resolution * rpm.weight * (vf.time_block_end - vf.time_block_start + 1) * vf.solution) AS tot_flow
+            SUM(rpd.resolution * rpm.weight * (vf.time_block_end - vf.time_block_start + 1) * vf.solution){unit_divisor} AS tot_flow
             FROM var_flow vf
             LEFT JOIN root_asset ra_from ON ra_from.asset = vf.from_asset
             LEFT JOIN root_asset ra_to ON ra_to.asset = vf.to_asset
             JOIN rep_periods_mapping rpm ON rpm.rep_period = vf.rep_period
             JOIN rep_periods_data rpd ON rpd.rep_period = rpm.rep_period
             WHERE ra_from.root_id != ra_to.root_id AND 
+            ra_from.root_level = ra_to.root_level AND
             rpm.year = $2
             GROUP BY ra_from.root_id, ra_to.root_id
     )
@@ -91,15 +398,62 @@ const AGGREGATE_FLOW_SQL: &str = "
         c.name AS group,
         COALESCE(SUM(ri_import.tot_flow), 0) AS totalImport, -- sum from ALL countries, in 1 year, to 1 country
         COALESCE(SUM(ri_export.tot_flow), 0) AS totalExport -- sum from 1 country, in 1 year, to ALL countries
+        {internal_select}
+        {parent_select}
         FROM location_nodes ln
         LEFT JOIN result_incomplete ri_import ON ri_import.to_id = ln.id
         LEFT JOIN result_incomplete ri_export ON ri_export.from_id = ln.id
         JOIN category c ON c.id = ln.id
-        WHERE ln.level = $1
+        WHERE ln.level IN ($1, {child_level})
         GROUP BY ln.id, c.name
         ORDER BY ln.id
 ";
 
+const TRADE_BALANCE_SQL: &str = "
+    , balance AS (
+        SELECT
+            ln.id AS id,
+            c.name AS group,
+            COALESCE((SELECT SUM(ri.tot_flow) FROM result_incomplete ri WHERE ri.to_id = ln.id), 0) AS totalImport,
+            COALESCE((SELECT SUM(ri.tot_flow) FROM result_incomplete ri WHERE ri.from_id = ln.id), 0) AS totalExport
+            FROM location_nodes ln
+            JOIN category c ON c.id = ln.id
+            WHERE ln.level = $1
+    )
+    SELECT *, totalImport - totalExport AS netBalance
+        FROM balance
+        ORDER BY id
+";
+
+// appended to RESULT_INCOMPLETE_SQL, the flows excluded there since they stay within one location group
+const INTERNAL_FLOW_SQL: &str = "
+    , internal_flow AS ( -- calculates the total flow within each location group
+        SELECT
+            ra_from.root_id AS root_id,
+            SUM(rpd.resolution * rpm.weight * (vf.time_block_end - vf.time_block_start + 1) * vf.solution){unit_divisor} AS tot_flow
+            FROM var_flow vf
+            JOIN root_asset ra_from ON ra_from.asset = vf.from_asset
+            JOIN root_asset ra_to ON ra_to.asset = vf.to_asset
+            JOIN rep_periods_mapping rpm ON rpm.rep_period = vf.rep_period
+            JOIN rep_periods_data rpd ON rpd.rep_period = rpm.rep_period
+            WHERE ra_from.root_id = ra_to.root_id AND
+            rpm.year = $2
+            GROUP BY ra_from.root_id
+    )
+";
+
+// weighted hours of the year ($2) in the representative periods, replaces {unit_divisor} for power
+const POWER_DIVISOR_SQL: &str = "
+            / (SELECT SUM(rpm_h.weight * rpd_h.num_timesteps * rpd_h.resolution)
+                FROM rep_periods_mapping rpm_h
+                JOIN rep_periods_data rpd_h ON rpd_h.year = rpm_h.year AND rpd_h.rep_period = rpm_h.rep_period
+                WHERE rpm_h.year = $2)";
+
+// id of the ancestor at `level` ($1) of a child level group, NULL for the groups at `level` themselves
+const PARENT_SELECT_SQL: &str = ", (SELECT rt.root_id FROM recursive_trees rt WHERE rt.id = ln.id AND rt.root_level = $1 AND rt.root_id != ln.id) AS parentId";
+
+const INTERNAL_FLOW_SELECT_SQL: &str = ", COALESCE((SELECT inf.tot_flow FROM internal_flow inf WHERE inf.root_id = ln.id), 0) AS internalFlow";
+
 const DETAILED_FLOW_SQL: &str = "
     SELECT -- fills in any missing information
         root_from.id AS fromId,
@@ -116,4 +470,11 @@ const DETAILED_FLOW_SQL: &str = "
             res.to_id = root_to.id
         ) WHERE root_from.level = $1 AND root_to.level = $1
         ORDER BY root_from.id, root_to.id
+";
+
+// divides by the largest flow of the detailed flow query, NULLIF keeps an all-zero result at zero
+const NORMALIZED_FLOW_SQL: &str = "
+    SELECT * REPLACE (COALESCE(totFlow / NULLIF(MAX(totFlow) OVER (), 0), 0) AS totFlow)
+    FROM ({source}) AS detailed
+    ORDER BY fromId, toId
 ";
\ No newline at end of file
@@ -1,10 +1,60 @@
-use duckdb::{ arrow::{array::RecordBatch, datatypes::Schema}, Row };
+use std::collections::{BTreeMap, HashMap, HashSet};
+use duckdb::{ arrow::{array::RecordBatch, datatypes::Schema}, types::Value, Row };
+use serde::Serialize;
 use tauri::ipc::Response;
-use crate::duckdb_conn::{run_query_rb, run_query_row, serialize_recordbatch};
+use crate::duckdb_conn::{execute_batch, run_query_rb, run_query_row, serialize_recordbatch};
+use crate::services::query_builder::{build_breakdown_case_conditions, build_breakdown_joins, build_filter_conditions, validate_ident, with_category_descendants};
 
+// all assets, or one page of them with `limit`/`offset` (see `get_asset_count` for the total)
 #[tauri::command]
-pub fn get_assets(db_path: String) -> Result<Response, String> {
-    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, ASSET_SQL.to_string(), [].to_vec())?;
+pub fn get_assets(db_path: String, limit: Option<u32>, offset: Option<u32>) -> Result<Response, String> {
+    let mut sql: String = ASSET_SQL.trim_end_matches(';').to_string();
+    let mut args: Vec<Value> = Vec::new();
+    if let Some(limit) = limit {
+        sql.push_str(" LIMIT ?");
+        args.push(Value::from(limit));
+    }
+    if let Some(offset) = offset {
+        sql.push_str(" OFFSET ?");
+        args.push(Value::from(offset));
+    }
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, sql, args)?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+#[tauri::command]
+pub fn get_asset_count(db_path: String) -> Result<i64, String> {
+    let counts: Vec<i64> = run_query_row(db_path, ASSET_COUNT_SQL.to_string(), vec![], |row: &Row<'_>| Ok(row.get::<usize, i64>(0)?))?;
+
+    counts.first().copied().ok_or_else(|| "Could not count assets".to_string())
+}
+
+#[tauri::command]
+pub fn get_asset_types(db_path: String) -> Result<Response, String> {
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, ASSET_TYPES_SQL.to_string(), [].to_vec())?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// the assets without any solved flow (in or out) in the year, for graying them out in the pickers; without a
+// solved `var_flow` table no asset has results
+#[tauri::command]
+pub fn get_assets_without_results(db_path: String, year: u32) -> Result<Vec<String>, String> {
+    let solved: bool = check_table_exists(db_path.clone(), "var_flow")? && check_column_in_table(db_path.clone(), "var_flow", "solution")?;
+    let (sql, args): (&str, Vec<Value>) = if solved {
+        (ASSETS_WITHOUT_RESULTS_SQL, vec![Value::from(year)])
+    } else {
+        (ASSET_SQL, vec![])
+    };
+
+    run_query_row(db_path, sql.to_string(), args, |row: &Row<'_>| Ok(row.get::<usize, String>(0)?))
+}
+
+// case-insensitive substring search over asset names, prefix matches first
+#[tauri::command]
+pub fn search_assets(db_path: String, prefix: String, limit: u32) -> Result<Response, String> {
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, SEARCH_ASSETS_SQL.to_string(), vec![Value::from(prefix), Value::from(limit)])?;
 
     return serialize_recordbatch(res.0, res.1);
 }
@@ -16,19 +66,302 @@ pub fn get_tables(db_path: String) -> Result<Response, String> {
     return serialize_recordbatch(res.0, res.1); 
 }
 
+#[derive(Serialize)]
+pub struct TableOverview {
+    name: String,
+    column_count: i64,
+    row_count: Option<i64>,
+}
+
+// lists every table with its column and row count, tables which can't be counted get no row count
+#[tauri::command]
+pub fn get_tables_overview(db_path: String) -> Result<Vec<TableOverview>, String> {
+    let tables: Vec<(String, i64)> = run_query_row(db_path.clone(), TABLES_OVERVIEW_SQL.to_string(), vec![], |row: &Row<'_>| {
+        Ok((row.get::<usize, String>(0)?, row.get::<usize, i64>(1)?))
+    })?;
+
+    let overview: Vec<TableOverview> = tables.into_iter()
+        .map(|(name, column_count)| {
+            let row_count: Option<i64> = run_query_row(
+                db_path.clone(),
+                COUNT_ROWS_SQL.replace("{table}", &name.replace('"', "\"\"")),
+                vec![],
+                |row: &Row<'_>| Ok(row.get::<usize, i64>(0)?),
+            ).ok().and_then(|counts: Vec<i64>| counts.first().copied());
+
+            TableOverview { name, column_count, row_count }
+        })
+        .collect();
+
+    Ok(overview)
+}
+
+// creates indexes on the columns the resolution and cost queries join on, returns the names of the ensured indexes
+// NOTE: this mutates the database file, it is skipped for tables which don't exist and for read-only connections
+#[tauri::command]
+pub fn create_recommended_indexes(db_path: String) -> Result<Vec<String>, String> {
+    let access_mode: Vec<String> = run_query_row(db_path.clone(), ACCESS_MODE_SQL.to_string(), vec![], |row: &Row<'_>| Ok(row.get::<usize, String>(0)?))?;
+    if access_mode.iter().any(|mode: &String| mode == "read_only") {
+        println!("database '{}' is read-only, skipping index creation", db_path);
+        return Ok(Vec::new());
+    }
+
+    let mut created: Vec<String> = Vec::new();
+    let mut statements: Vec<String> = Vec::new();
+    for (table, columns) in RECOMMENDED_INDEXES {
+        if !check_table_exists(db_path.clone(), table)? {
+            continue;
+        }
+        let index_name: String = format!("idx_{}_{}", table, columns.replace(", ", "_"));
+        statements.push(format!("CREATE INDEX IF NOT EXISTS {} ON {} ({});", index_name, table, columns));
+        created.push(index_name);
+    }
+
+    if !statements.is_empty() {
+        execute_batch(db_path, statements.join("\n"))?;
+    }
+    Ok(created)
+}
+
+// `table_name` may be qualified (`schema.table`, `catalog.schema.table`) and its parts double-quoted
 pub fn check_column_in_table(db_path: String, table_name: &str, column_name: &str) -> Result<bool, String> {
-    let check: Vec<String> = run_query_row(db_path, TABLE_INFO_SQL.replace("{{1}}", table_name), vec![], |row: &Row<'_>| Ok(row.get::<&str, String>("name")?))?;
+    let table: String = quote_qualified_name(table_name)?;
+    let check: Vec<String> = run_query_row(db_path, TABLE_INFO_SQL.replace("{{1}}", &format!("'{}'", table.replace('\'', "''"))), vec![], |row: &Row<'_>| Ok(row.get::<&str, String>("name")?))
+        .map_err(|e| format!("Could not read the columns of table '{}': {}", table_name, e))?;
     
     Ok(check.iter().any(|name: &String| name == column_name))
 }
 
+// splits a possibly qualified table name on the dots outside double quotes and quotes every part,
+// e.g. `main.my table` -> `"main"."my table"`; quotes inside a quoted part are escaped by doubling them
+fn quote_qualified_name(name: &str) -> Result<String, String> {
+    let mut parts: Vec<String> = vec![String::new()];
+    let mut quoted: bool = false;
+    let mut chars = name.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                parts.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            '.' if !quoted => parts.push(String::new()),
+            _ => parts.last_mut().unwrap().push(c),
+        }
+    }
+
+    if quoted || parts.len() > 3 || parts.iter().any(|part| part.trim().is_empty()) {
+        return Err(format!("Invalid table name '{}'", name));
+    }
+    Ok(parts.iter().map(|part| format!("\"{}\"", part.replace('"', "\"\""))).collect::<Vec<String>>().join("."))
+}
+
 #[tauri::command]
 pub fn get_years(db_path: String) -> Result<Response, String> {
-    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, YEARS_SQL.to_string(), [].to_vec())?;
+    let sql: String = with_milestone_fallback(&db_path, YEARS_SQL)?;
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, sql, [].to_vec())?;
 
     return serialize_recordbatch(res.0, res.1); 
 }
 
+pub fn check_table_exists(db_path: String, table_name: &str) -> Result<bool, String> {
+    let check: Vec<String> = run_query_row(db_path, TABLE_EXISTS_SQL.to_string(), vec![Value::from(table_name.to_string())], |row: &Row<'_>| Ok(row.get::<usize, String>(0)?))?;
+
+    Ok(!check.is_empty())
+}
+
+// some exports have no `is_milestone` column in `year_data`, all of their years are then treated as milestones by
+// reading `year_data` through a subquery adding the column, the queries must reference it as `year_data AS <alias>`
+pub fn with_milestone_fallback(db_path: &str, sql: &str) -> Result<String, String> {
+    if !check_table_exists(db_path.to_string(), "year_data")? || check_column_in_table(db_path.to_string(), "year_data", "is_milestone")? {
+        return Ok(sql.to_string());
+    }
+    println!("COLUMN '{}' MISSING FROM '{}', TREATING ALL YEARS AS MILESTONES\n", "is_milestone", "year_data");
+
+    Ok(sql.replace("year_data AS ", &format!("({}) AS ", ALL_MILESTONES_SQL)))
+}
+
+// the category tables are optional, commands with metadata enabled call this up front instead of failing on a missing table
+pub fn ensure_metadata_available(db_path: &str) -> Result<(), String> {
+    for table in ["category", "asset_category"] {
+        if !check_table_exists(db_path.to_string(), table)? {
+            return Err(format!("Metadata not available in this database (missing table '{}'), disable metadata to query it", table));
+        }
+    }
+
+    Ok(())
+}
+
+// resolution `0` means auto: a bucket size (in hours) giving about `AUTO_RESOLUTION_TARGET_POINTS` points
+// over the year's total hour span, rounded up to a common duration; other resolutions are returned as is
+pub fn resolve_resolution(db_path: &str, year: u32, resolution: u32) -> Result<u32, String> {
+    if resolution != AUTO_RESOLUTION {
+        return Ok(resolution);
+    }
+
+    let span: Vec<f64> = run_query_row(db_path.to_string(), YEAR_HOUR_SPAN_SQL.to_string(), vec![Value::from(year)], |row: &Row<'_>| Ok(row.get::<usize, f64>(0)?))?;
+    let span_hours: f64 = span.first().copied().unwrap_or(0.0);
+
+    Ok(auto_resolution(span_hours))
+}
+
+fn auto_resolution(span_hours: f64) -> u32 {
+    let raw: u32 = (span_hours / AUTO_RESOLUTION_TARGET_POINTS).ceil().max(1.0) as u32;
+
+    COMMON_RESOLUTIONS.into_iter()
+        .find(|&common| common >= raw)
+        .unwrap_or(raw)
+}
+
+// distinct non-null series of a long-format query for `build_pivot_query`, capped to keep the column count sane
+pub fn fetch_pivot_series(db_path: &str, sql: &str, series_col: &str, args: Vec<Value>) -> Result<Vec<String>, String> {
+    let series_col: &str = validate_ident(series_col)?;
+    let series_sql: String = PIVOT_SERIES_SQL
+        .replace("{sql}", sql.trim().trim_end_matches(';'))
+        .replace("{series_col}", series_col)
+        .replace("{limit}", &(MAX_PIVOT_SERIES + 1).to_string());
+    let series: Vec<String> = run_query_row(db_path.to_string(), series_sql, args, |row: &Row<'_>| Ok(row.get::<usize, String>(0)?))?;
+
+    if series.len() > MAX_PIVOT_SERIES {
+        return Err(format!("Too many series to pivot (more than {}), filter or group the data first", MAX_PIVOT_SERIES));
+    }
+    Ok(series)
+}
+
+// guard for breakdown queries: errors when the query has more distinct `series_col` values than `max_series`, so the
+// frontend can ask for a narrower selection instead of receiving a huge payload; no cap when `max_series` is None
+pub fn check_series_cap(db_path: &str, sql: &str, series_col: &str, args: Vec<Value>, max_series: Option<u32>) -> Result<(), String> {
+    let Some(cap) = max_series else {
+        return Ok(());
+    };
+    let series_col: &str = validate_ident(series_col)?;
+    let count_sql: String = SERIES_COUNT_SQL
+        .replace("{sql}", sql.trim().trim_end_matches(';'))
+        .replace("{series_col}", series_col);
+    let count: i64 = run_query_row(db_path.to_string(), count_sql, args, |row: &Row<'_>| Ok(row.get::<usize, i64>(0)?))?
+        .first()
+        .copied()
+        .unwrap_or(0);
+
+    if count > cap as i64 {
+        return Err(format!("Breakdown produced {} series, exceeds cap {}", count, cap));
+    }
+    Ok(())
+}
+
+// returns the years which actually have data for the given analysis, probing only the tables present
+#[tauri::command]
+pub fn get_available_years_for(db_path: String, analysis: String) -> Result<Response, String> {
+    let sources: &[(&str, &str)] = match analysis.as_str() {
+        "capacity" => &[("asset_both", "milestone_year"), ("var_assets_investment", "milestone_year"), ("var_assets_decommission", "milestone_year")],
+        "flows" | "residual_load" => &[("var_flow", "year")],
+        "production_price" => &[("cons_capacity_outgoing_simple_method", "year"), ("cons_capacity_outgoing_compact_method", "year")],
+        "storage_price" => &[("cons_balance_storage_rep_period", "year"), ("cons_balance_storage_over_clustered_year", "year")],
+        "transport_price" => &[("cons_transport_flow_limit_simple_method", "year")],
+        _ => return Err(format!("Unknown analysis type: '{}'", analysis)),
+    };
+
+    let mut year_selects: Vec<String> = Vec::new();
+    for (table, year_col) in sources {
+        if check_table_exists(db_path.clone(), table)? {
+            year_selects.push(format!("SELECT {} AS year FROM {}", year_col, table));
+        }
+    }
+
+    let sql: String = if year_selects.is_empty() {
+        NO_YEARS_SQL.to_string()
+    } else {
+        AVAILABLE_YEARS_SQL.replace("{year_selects}", &year_selects.join("\n    UNION\n    "))
+    };
+
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, sql, [].to_vec())?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// the shortest time block (in hours) in the source tables of a time resolved analysis, None without any block,
+// a finer resolution than this has no data of its own; only the tables present are probed
+#[tauri::command]
+pub fn get_min_resolution(db_path: String, analysis: String) -> Result<Option<f64>, String> {
+    let tables: &[&str] = match analysis.as_str() {
+        "flows" | "residual_load" => &["var_flow"],
+        "production_price" => &["cons_capacity_outgoing_simple_method", "cons_capacity_outgoing_compact_method"],
+        "storage_price" => &["cons_balance_storage_rep_period"],
+        "transport_price" => &["cons_transport_flow_limit_simple_method"],
+        "capacity" => return Err("Analysis 'capacity' has no time blocks".to_string()),
+        _ => return Err(format!("Unknown analysis type: '{}'", analysis)),
+    };
+
+    let mut block_selects: Vec<String> = Vec::new();
+    for table in tables {
+        if check_table_exists(db_path.clone(), table)? {
+            block_selects.push(format!("SELECT year, rep_period, time_block_start, time_block_end FROM {}", table));
+        }
+    }
+    if block_selects.is_empty() {
+        return Ok(None);
+    }
+
+    let sql: String = MIN_BLOCK_HOURS_SQL.replace("{block_selects}", &block_selects.join("\n        UNION ALL\n        "));
+    let lengths: Vec<Option<f64>> = run_query_row(db_path, sql, vec![], |row: &Row<'_>| Ok(row.get::<usize, Option<f64>>(0)?))?;
+
+    Ok(lengths.first().copied().flatten())
+}
+
+// distribution of the time block lengths (in timesteps) of the flows in a year, e.g. how many blocks span
+// 1 timestep and how many 6, showing whether the run is modelled at a fine or a coarse resolution
+#[tauri::command]
+pub fn get_resolution_summary(db_path: String, year: u32) -> Result<Response, String> {
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, RESOLUTION_SUMMARY_SQL.to_string(), vec![Value::from(year)])?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct AnnualScaling {
+    year: i64,
+    // hours represented once every rep period is scaled by its weight: SUM(weight * num_timesteps * resolution)
+    weighted_hours: f64,
+    year_hours: f64,
+    // weighted_hours - year_hours, negative when the representation doesn't cover the full year
+    difference: f64,
+    covers_full_year: bool,
+}
+
+// whether the weighted representative periods of a year add up to a full year, which all annual totals
+// (flows, costs, capacity factors) assume when scaling rep period values with `rpm.weight`
+#[tauri::command]
+pub fn get_annual_scaling_check(db_path: String, year: u32) -> Result<AnnualScaling, String> {
+    let weighted: Vec<f64> = run_query_row(db_path, ANNUAL_WEIGHTED_HOURS_SQL.to_string(), vec![Value::from(year)], |row: &Row<'_>| Ok(row.get::<usize, f64>(0)?))?;
+    let weighted_hours: f64 = weighted.first().copied().unwrap_or(0.0);
+    let difference: f64 = weighted_hours - HOURS_PER_YEAR;
+
+    Ok(AnnualScaling {
+        year: year as i64,
+        weighted_hours,
+        year_hours: HOURS_PER_YEAR,
+        difference,
+        covers_full_year: difference.abs() < ANNUAL_SCALING_TOLERANCE_HOURS,
+    })
+}
+
+// representative periods of a year: which rep period represents each period, with its weight and resolution
+#[tauri::command]
+pub fn get_rep_period_info(db_path: String, year: u32) -> Result<Response, String> {
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, REP_PERIOD_INFO_SQL.to_string(), vec![Value::from(year)])?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// how the periods of a year are tiled by the representative periods, straight from `rep_periods_mapping`: one row
+// per (period, rep period) with its weight, and the total weight of the period (1 when it is fully represented)
+#[tauri::command]
+pub fn get_period_coverage(db_path: String, year: u32) -> Result<Response, String> {
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, PERIOD_COVERAGE_SQL.to_string(), vec![Value::from(year)])?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
 #[tauri::command]
 pub fn get_assets_carriers(db_path: String) -> Result<Response, String> {
     let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, CARRIER_SQL.to_string(), [].to_vec())?;
@@ -79,17 +412,1056 @@ pub fn get_categories(db_path: String) -> Result<Response, String> {
     return serialize_recordbatch(res.0, res.1);
 }
 
+// the category hierarchy as a plain-text outline, one name per line indented two spaces per depth, roots in id order.
+// Categories caught in a cycle are listed as roots, a child already printed is marked `(cycle)` instead of descended into
+#[tauri::command]
+pub fn get_category_tree_text(db_path: String) -> Result<String, String> {
+    if !check_table_exists(db_path.clone(), "category")? {
+        return Err("Metadata not available in this database (missing table 'category')".to_string());
+    }
+    let categories: Vec<(i64, String, Option<i64>)> = run_query_row(db_path, CATEGORY_TREE_SQL.to_string(), vec![], |row: &Row<'_>| {
+        Ok((row.get::<usize, i64>(0)?, row.get::<usize, String>(1)?, row.get::<usize, Option<i64>>(2)?))
+    })?;
+
+    let ids: HashSet<i64> = categories.iter().map(|c| c.0).collect();
+    let mut children: BTreeMap<i64, Vec<(i64, &str)>> = BTreeMap::new();
+    let mut roots: Vec<(i64, &str)> = Vec::new();
+    for (id, name, parent_id) in &categories {
+        match parent_id {
+            // a parent missing from the table makes the category a root as well
+            Some(parent) if ids.contains(parent) => children.entry(*parent).or_default().push((*id, name.as_str())),
+            _ => roots.push((*id, name.as_str())),
+        }
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut visited: HashSet<i64> = HashSet::new();
+    for root in &roots {
+        write_category_subtree(*root, 0, &children, &mut visited, &mut lines);
+    }
+    for (id, name, _) in &categories {
+        if !visited.contains(id) {
+            write_category_subtree((*id, name.as_str()), 0, &children, &mut visited, &mut lines);
+        }
+    }
+
+    Ok(lines.join("\n"))
+}
+
+fn write_category_subtree(node: (i64, &str), depth: usize, children: &BTreeMap<i64, Vec<(i64, &str)>>, visited: &mut HashSet<i64>, lines: &mut Vec<String>) {
+    let indent: String = "  ".repeat(depth);
+    if !visited.insert(node.0) {
+        lines.push(format!("{}{} (cycle)", indent, node.1));
+        return;
+    }
+    lines.push(format!("{}{}", indent, node.1));
+    for child in children.get(&node.0).into_iter().flatten() {
+        write_category_subtree(*child, depth + 1, children, visited, lines);
+    }
+}
+
+// asset -> root -> leaf category memberships with the category names, optionally for a single asset
+#[tauri::command]
+pub fn get_asset_categories(db_path: String, asset: Option<String>) -> Result<Response, String> {
+    let asset_param: Value = asset.map(Value::from).unwrap_or(Value::Null);
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, ASSET_CATEGORIES_SQL.to_string(), vec![asset_param])?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// the (filtered) assets a breakdown puts in its 'Other' bucket, i.e. those in none of the grouper nodes' subtrees
+#[tauri::command]
+pub fn get_other_bucket_assets(db_path: String, grouper: Vec<i32>, filters: HashMap<i32, Vec<i32>>) -> Result<Vec<String>, String> {
+    ensure_metadata_available(&db_path)?;
+
+    let sql: String = OTHER_BUCKET_ASSETS_SQL
+        .replace("{breakdown_joins}", &build_breakdown_joins(&grouper))
+        .replace("{filter_conditions}", &build_filter_conditions(&filters, "a.asset".to_string()))
+        .replace("{breakdown_case_conditions}", &build_breakdown_case_conditions(&grouper, "a.asset".to_string()));
+    let sql: String = with_category_descendants(&sql, &filters, &grouper);
+
+    run_query_row(db_path, sql, vec![], |row: &Row<'_>| Ok(row.get::<usize, String>(0)?))
+}
+
+#[derive(Serialize)]
+pub struct SolutionColumn {
+    table: String,
+    column: String,
+    present: bool,
+}
+
+#[derive(Serialize)]
+pub struct SolutionReport {
+    has_solution: bool,
+    columns: Vec<SolutionColumn>,
+}
+
+#[derive(Serialize)]
+pub struct CategoryNode {
+    id: i64,
+    name: String,
+    parent_id: Option<i64>,
+    level: i64,
+}
+
+#[derive(Serialize)]
+pub struct MetadataBootstrap {
+    assets: Vec<String>,
+    tables: Vec<String>,
+    years: Vec<i64>,
+    carriers: Vec<Option<String>>,
+    has_category: bool,
+    has_asset_category: bool,
+    categories: Vec<CategoryNode>,
+}
+
+// all metadata needed when opening a database in one call, fails on the first query if the database is invalid
+#[tauri::command]
+pub fn get_metadata_bootstrap(db_path: String) -> Result<MetadataBootstrap, String> {
+    let tables: Vec<String> = run_query_row(db_path.clone(), TABLES_SQL.to_string(), vec![], |row: &Row<'_>| Ok(row.get::<usize, String>(0)?))?;
+    let assets: Vec<String> = run_query_row(db_path.clone(), ASSET_SQL.to_string(), vec![], |row: &Row<'_>| Ok(row.get::<usize, String>(0)?))?;
+    let years: Vec<i64> = run_query_row(db_path.clone(), with_milestone_fallback(&db_path, YEARS_SQL)?, vec![], |row: &Row<'_>| Ok(row.get::<usize, i64>(0)?))?;
+    let carriers: Vec<Option<String>> = run_query_row(db_path.clone(), CARRIER_SQL.to_string(), vec![], |row: &Row<'_>| Ok(row.get::<usize, Option<String>>(0)?))?;
+    let meta: Vec<(bool, bool)> = run_query_row(db_path.clone(), HAS_META_SQL.to_string(), vec![], |row: &Row<'_>| {
+        Ok((row.get::<usize, bool>(0)?, row.get::<usize, bool>(1)?))
+    })?;
+    let (has_category, has_asset_category) = meta.first().copied().unwrap_or((false, false));
+
+    // the category table is optional
+    let categories: Vec<CategoryNode> = if has_category {
+        run_query_row(db_path, CATEGORY_SQL.to_string(), vec![], |row: &Row<'_>| Ok(CategoryNode {
+            id: row.get::<usize, i64>(0)?,
+            name: row.get::<usize, String>(1)?,
+            parent_id: row.get::<usize, Option<i64>>(2)?,
+            level: row.get::<usize, i64>(3)?,
+        }))?
+    } else {
+        Vec::new()
+    };
+
+    Ok(MetadataBootstrap { assets, tables, years, carriers, has_category, has_asset_category, categories })
+}
+
+// reports which of the key solution/dual columns are present, to tell solved databases from inputs-only ones
+#[tauri::command]
+pub fn has_solution_data(db_path: String) -> Result<SolutionReport, String> {
+    let mut columns: Vec<SolutionColumn> = Vec::new();
+    for (table, column) in SOLUTION_COLUMNS {
+        // check_column_in_table errors on absent tables
+        let present: bool = check_table_exists(db_path.clone(), table)?
+            && check_column_in_table(db_path.clone(), table, column)?;
+        columns.push(SolutionColumn { table: table.to_string(), column: column.to_string(), present });
+    }
+
+    Ok(SolutionReport {
+        has_solution: columns.iter().any(|c: &SolutionColumn| c.present),
+        columns,
+    })
+}
+
+#[derive(Serialize)]
+pub struct CarrierCapabilities {
+    carrier: String,
+    // some flow of the carrier is a transport flow
+    transport: bool,
+    // a producer or conversion asset outputs the carrier
+    production: bool,
+    // a storage asset charges or discharges the carrier
+    storage: bool,
+}
+
+// which analyses apply to each carrier of the flows, for the analysis selectors
+#[tauri::command]
+pub fn get_carrier_capabilities(db_path: String) -> Result<Vec<CarrierCapabilities>, String> {
+    run_query_row(db_path, CARRIER_CAPABILITIES_SQL.to_string(), vec![], |row: &Row<'_>| Ok(CarrierCapabilities {
+        carrier: row.get::<usize, String>(0)?,
+        transport: row.get::<usize, bool>(1)?,
+        production: row.get::<usize, bool>(2)?,
+        storage: row.get::<usize, bool>(3)?,
+    }))
+}
+
+#[derive(Serialize)]
+pub struct SolverSummary {
+    // false when the database has no solver table, all other fields are then None
+    available: bool,
+    objective_value: Option<f64>,
+    termination_status: Option<String>,
+    solve_time: Option<f64>,
+}
+
+// reads the objective and termination status of the run from the solver table, when the run stored one;
+// columns absent from the table are reported as None
+#[tauri::command]
+pub fn get_solver_summary(db_path: String) -> Result<SolverSummary, String> {
+    let not_available: SolverSummary = SolverSummary { available: false, objective_value: None, termination_status: None, solve_time: None };
+    if !check_table_exists(db_path.clone(), SOLVER_TABLE)? {
+        return Ok(not_available);
+    }
+
+    let mut columns: Vec<String> = Vec::new();
+    for (column, sql_type) in SOLVER_COLUMNS {
+        if check_column_in_table(db_path.clone(), SOLVER_TABLE, column)? {
+            columns.push(format!("CAST({} AS {}) AS {}", column, sql_type, column));
+        } else {
+            columns.push(format!("CAST(NULL AS {}) AS {}", sql_type, column));
+        }
+    }
+    let sql: String = SOLVER_SUMMARY_SQL
+        .replace("{table}", SOLVER_TABLE)
+        .replace("{columns}", &columns.join(", "));
+    let rows: Vec<SolverSummary> = run_query_row(db_path, sql, vec![], |row: &Row<'_>| Ok(SolverSummary {
+        available: true,
+        objective_value: row.get::<usize, Option<f64>>(0)?,
+        termination_status: row.get::<usize, Option<String>>(1)?,
+        solve_time: row.get::<usize, Option<f64>>(2)?,
+    }))?;
+
+    // an empty solver table holds no run either
+    Ok(rows.into_iter().next().unwrap_or(not_available))
+}
+
+#[derive(Serialize)]
+pub struct PreloadReport {
+    table_count: usize,
+    // core tables of a Tulipa database which are absent
+    missing_tables: Vec<String>,
+}
+
+// opens the database into the connection pool and touches the catalog and the core tables, so the first
+// analysis query does not pay for DuckDB's lazy loading; returns once the database is ready
+#[tauri::command]
+pub fn preload_database(db_path: String) -> Result<PreloadReport, String> {
+    let tables: Vec<String> = run_query_row(db_path.clone(), TABLES_SQL.to_string(), vec![], |row: &Row<'_>| Ok(row.get::<usize, String>(0)?))?;
+
+    let mut missing_tables: Vec<String> = Vec::new();
+    for table in PRELOAD_TABLES {
+        if !tables.iter().any(|t: &String| t == table) {
+            missing_tables.push(table.to_string());
+            continue;
+        }
+        run_query_row(db_path.clone(), COUNT_ROWS_SQL.replace("{table}", table), vec![], |row: &Row<'_>| Ok(row.get::<usize, i64>(0)?))?;
+    }
+
+    Ok(PreloadReport { table_count: tables.len(), missing_tables })
+}
+
+#[derive(Serialize)]
+pub struct IntegrityCheck {
+    name: String,
+    // rows referencing a missing parent row, None when one of both tables is absent
+    orphan_count: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct IntegrityReport {
+    valid: bool,
+    checks: Vec<IntegrityCheck>,
+}
+
+// counts the rows referencing a row missing from the referenced table for each of `INTEGRITY_CHECKS`,
+// to catch malformed exports before analysing them
+#[tauri::command]
+pub fn check_referential_integrity(db_path: String) -> Result<IntegrityReport, String> {
+    let mut checks: Vec<IntegrityCheck> = Vec::new();
+    for (child_table, child_cols, parent_table, parent_cols) in INTEGRITY_CHECKS {
+        let name: String = format!("{}({}) -> {}({})", child_table, child_cols.join(", "), parent_table, parent_cols.join(", "));
+        if !check_table_exists(db_path.clone(), child_table)? || !check_table_exists(db_path.clone(), parent_table)? {
+            checks.push(IntegrityCheck { name, orphan_count: None });
+            continue;
+        }
+
+        let not_null: Vec<String> = child_cols.iter().map(|col| format!("c.{} IS NOT NULL", col)).collect();
+        let join: Vec<String> = child_cols.iter().zip(parent_cols.iter())
+            .map(|(child_col, parent_col)| format!("p.{} = c.{}", parent_col, child_col))
+            .collect();
+        let sql: String = ORPHAN_COUNT_SQL
+            .replace("{child_table}", child_table)
+            .replace("{parent_table}", parent_table)
+            .replace("{not_null}", &not_null.join(" AND "))
+            .replace("{join}", &join.join(" AND "));
+        let counts: Vec<i64> = run_query_row(db_path.clone(), sql, vec![], |row: &Row<'_>| Ok(row.get::<usize, i64>(0)?))?;
+
+        checks.push(IntegrityCheck { name, orphan_count: counts.first().copied() });
+    }
+
+    Ok(IntegrityReport {
+        valid: checks.iter().all(|c: &IntegrityCheck| c.orphan_count.unwrap_or(0) == 0),
+        checks,
+    })
+}
+
+// the gaps and overlaps in the time blocks of `table` in one year, per asset (or asset pair for flow tables) and
+// rep period: each row is an uncovered or doubly covered range of timesteps, the resolution queries assume
+// the blocks tile the rep period from 1 to its number of timesteps, so no rows means the table is safe to chart
+#[tauri::command]
+pub fn check_time_block_integrity(db_path: String, table: String, year: u32) -> Result<Response, String> {
+    let table: &str = validate_ident(&table)?;
+    for column in ["year", "rep_period", "time_block_start", "time_block_end"] {
+        if !check_column_in_table(db_path.clone(), table, column)? {
+            return Err(format!("Table '{}' has no column '{}'", table, column));
+        }
+    }
+    let key_cols: &str = if check_column_in_table(db_path.clone(), table, "asset")? { "asset" } else { "from_asset, to_asset" };
+
+    let sql: String = TIME_BLOCK_INTEGRITY_SQL.replace("{table}", table).replace("{key_cols}", key_cols);
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, sql, vec![Value::from(year)])?;
+    return serialize_recordbatch(res.0, res.1);
+}
+
 #[tauri::command]
 pub fn has_metadata(db_path: String) -> Result<Response, String> {
     let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, HAS_META_SQL.to_string(), [].to_vec())?;
     return serialize_recordbatch(res.0, res.1);
 }
 
-const ASSET_SQL: &str = "SELECT asset FROM asset;";
+// --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+    use crate::duckdb_conn::is_connection_pooled;
+    use crate::test_utils::{setup_test_db, setup_tulipa_db, response_to_batches, num_rows, column_f64, column_string};
+
+    const YEARS_FIXTURE_SQL: &str = "
+        CREATE TABLE asset_both(asset VARCHAR, milestone_year INTEGER, initial_units DOUBLE);
+        INSERT INTO asset_both VALUES ('wind', 2030, 1), ('wind', 2050, 2);
+        CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, solution DOUBLE);
+        INSERT INTO var_flow VALUES ('wind', 'demand', 2030, 1, 1, 1, 5.0);
+    ";
+
+    fn available_years(db_path: &str, analysis: &str) -> Result<Vec<f64>, String> {
+        let response: Response = get_available_years_for(db_path.to_string(), analysis.to_string())?;
+        Ok(column_f64(&response_to_batches(response), "year").into_iter().flatten().collect())
+    }
+
+    #[test]
+    fn get_available_years_for_capacity_test() {
+        let db_path: String = setup_test_db("available_years_capacity", YEARS_FIXTURE_SQL);
+        assert_eq!(available_years(&db_path, "capacity").unwrap(), vec![2030.0, 2050.0]);
+    }
+
+    #[test]
+    fn get_available_years_for_flows_test() {
+        let db_path: String = setup_test_db("available_years_flows", YEARS_FIXTURE_SQL);
+        assert_eq!(available_years(&db_path, "flows").unwrap(), vec![2030.0]);
+    }
+
+    #[test]
+    fn get_years_without_milestone_column_test() {
+        let db_path: String = setup_test_db("years_without_milestone", "
+            CREATE TABLE year_data(year INTEGER, length INTEGER);
+            INSERT INTO year_data VALUES (2050, 8760), (2030, 8760);
+        ");
+        let years: Vec<Option<f64>> = column_f64(&response_to_batches(get_years(db_path.clone()).unwrap()), "year");
+        assert_eq!(years, vec![Some(2030.0), Some(2050.0)]);
+        assert_eq!(get_metadata_bootstrap(db_path).unwrap().years, vec![2030, 2050]);
+    }
+
+    #[test]
+    fn get_available_years_for_missing_tables_test() {
+        let db_path: String = setup_test_db("available_years_missing", YEARS_FIXTURE_SQL);
+        assert_eq!(available_years(&db_path, "storage_price").unwrap(), Vec::<f64>::new());
+    }
+
+    const SEARCH_FIXTURE_SQL: &str = "
+        CREATE TABLE asset(asset VARCHAR, type VARCHAR);
+        INSERT INTO asset VALUES ('ccgt_nl', 'producer'), ('wind_nl', 'producer'), ('Wind_be', 'producer'), ('offshore_wind', 'producer'), ('demand', 'consumer');
+    ";
+
+    fn search(db_path: &str, prefix: &str, limit: u32) -> Vec<String> {
+        let response: Response = search_assets(db_path.to_string(), prefix.to_string(), limit).unwrap();
+        column_string(&response_to_batches(response), "asset").into_iter().flatten().collect()
+    }
+
+    #[test]
+    fn search_assets_prefix_test() {
+        let db_path: String = setup_test_db("search_assets_prefix", SEARCH_FIXTURE_SQL);
+        assert_eq!(search(&db_path, "WIND", 10), vec!["Wind_be", "wind_nl", "offshore_wind"]);
+    }
+
+    #[test]
+    fn search_assets_limit_test() {
+        let db_path: String = setup_test_db("search_assets_limit", SEARCH_FIXTURE_SQL);
+        assert_eq!(search(&db_path, "_", 2).len(), 2);
+    }
+
+    #[test]
+    fn get_assets_sorted_test() {
+        let db_path: String = setup_test_db("assets_sorted", SEARCH_FIXTURE_SQL);
+        let assets: Vec<Option<String>> = column_string(&response_to_batches(get_assets(db_path, None, None).unwrap()), "asset");
+
+        let mut expected: Vec<Option<String>> = assets.clone();
+        expected.sort();
+        assert_eq!(assets.len(), 5);
+        assert_eq!(assets, expected);
+    }
+
+    #[test]
+    fn preload_database_test() {
+        let db_path: String = setup_test_db("preload_database", SEARCH_FIXTURE_SQL);
+        assert!(!is_connection_pooled(&db_path));
+
+        let report: PreloadReport = preload_database(db_path.clone()).unwrap();
+        assert!(is_connection_pooled(&db_path));
+        assert_eq!(report.table_count, 1);
+        assert_eq!(report.missing_tables, vec!["flow", "var_flow", "rep_periods_mapping", "rep_periods_data"]);
+
+        // the connection is reused, so a basic query does not open the database again
+        let start: Instant = Instant::now();
+        assert_eq!(search(&db_path, "wind", 10).len(), 3);
+        assert!(start.elapsed() < Duration::from_secs(1), "query after preload took {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn check_referential_integrity_orphan_test() {
+        // 'ghost' is not an asset and category 9 does not exist, flow and rep period tables are absent
+        let db_path: String = setup_test_db("referential_integrity_orphan", "
+            CREATE TABLE asset(asset VARCHAR, type VARCHAR);
+            INSERT INTO asset VALUES ('wind', 'producer'), ('demand', 'consumer');
+            CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, solution DOUBLE);
+            INSERT INTO var_flow VALUES ('wind', 'demand', 2030, 1, 1, 1, 5.0), ('ghost', 'demand', 2030, 1, 1, 1, 1.0);
+            CREATE TABLE category(id INTEGER, name VARCHAR, parent_id INTEGER, level INTEGER);
+            INSERT INTO category VALUES (1, 'location', NULL, 1), (2, 'NL', 1, 0);
+            CREATE TABLE asset_category(asset VARCHAR, root_id INTEGER, leaf_id INTEGER);
+            INSERT INTO asset_category VALUES ('wind', 1, 2), ('demand', 1, 9);
+        ");
+        let report: IntegrityReport = check_referential_integrity(db_path).unwrap();
+        let count = |name: &str| report.checks.iter().find(|c| c.name == name).unwrap().orphan_count;
+
+        assert!(!report.valid);
+        assert_eq!(count("var_flow(from_asset) -> asset(asset)"), Some(1));
+        assert_eq!(count("var_flow(to_asset) -> asset(asset)"), Some(0));
+        assert_eq!(count("asset_category(leaf_id) -> category(id)"), Some(1));
+        assert_eq!(count("category(parent_id) -> category(id)"), Some(0));
+        assert_eq!(count("flow(from_asset) -> asset(asset)"), None);
+    }
+
+    #[test]
+    fn get_assets_page_test() {
+        let db_path: String = setup_test_db("assets_page", SEARCH_FIXTURE_SQL);
+        let page: Vec<Option<String>> = column_string(&response_to_batches(get_assets(db_path.clone(), Some(2), Some(1)).unwrap()), "asset");
+        let all: Vec<Option<String>> = column_string(&response_to_batches(get_assets(db_path, None, None).unwrap()), "asset");
+
+        assert_eq!(page, all[1..3].to_vec());
+    }
+
+    #[test]
+    fn get_asset_count_test() {
+        let db_path: String = setup_test_db("asset_count", SEARCH_FIXTURE_SQL);
+        assert_eq!(get_asset_count(db_path), Ok(5));
+    }
+
+    #[test]
+    fn get_metadata_bootstrap_test() {
+        let db_path: String = setup_test_db("metadata_bootstrap", "
+            CREATE TABLE asset(asset VARCHAR, type VARCHAR);
+            INSERT INTO asset VALUES ('wind', 'producer'), ('demand', 'consumer');
+            CREATE TABLE flow(from_asset VARCHAR, to_asset VARCHAR, carrier VARCHAR);
+            INSERT INTO flow VALUES ('wind', 'demand', 'electricity');
+            CREATE TABLE year_data(year INTEGER, is_milestone BOOLEAN);
+            INSERT INTO year_data VALUES (2030, TRUE), (2040, FALSE), (2050, TRUE);
+            CREATE TABLE category(id INTEGER, name VARCHAR, parent_id INTEGER, level INTEGER);
+            INSERT INTO category VALUES (1, 'location', NULL, 1), (2, 'NL', 1, 0);
+            CREATE TABLE asset_category(asset VARCHAR, root_id INTEGER, leaf_id INTEGER);
+            INSERT INTO asset_category VALUES ('wind', 1, 2);
+        ");
+        let mut bootstrap: MetadataBootstrap = get_metadata_bootstrap(db_path).unwrap();
+        bootstrap.tables.sort();
+
+        assert_eq!(bootstrap.assets, vec!["demand", "wind"]);
+        assert_eq!(bootstrap.tables, vec!["asset", "asset_category", "category", "flow", "year_data"]);
+        assert_eq!(bootstrap.years, vec![2030, 2050]);
+        assert_eq!(bootstrap.carriers, vec![Some("electricity".to_string())]);
+        assert!(bootstrap.has_category && bootstrap.has_asset_category);
+        assert_eq!(bootstrap.categories.len(), 2);
+        assert_eq!(bootstrap.categories[1].parent_id, Some(1));
+    }
+
+    #[test]
+    fn get_metadata_bootstrap_invalid_db_test() {
+        let res = get_metadata_bootstrap("missing_bootstrap.duckdb".to_string());
+        assert!(res.is_err() && res.err().unwrap().contains("file not found"));
+    }
+
+    #[test]
+    fn get_asset_types_test() {
+        let db_path: String = setup_test_db("asset_types", "
+            CREATE TABLE asset(asset VARCHAR, type VARCHAR);
+            INSERT INTO asset VALUES ('wind', 'producer'), ('battery', 'storage'), ('solar', 'producer'), ('demand', 'consumer');
+        ");
+        let types: Vec<String> = column_string(&response_to_batches(get_asset_types(db_path).unwrap()), "type")
+            .into_iter().flatten().collect();
+        assert_eq!(types, vec!["consumer", "producer", "storage"]);
+    }
+
+    #[test]
+    fn get_assets_carriers_sorted_test() {
+        let db_path: String = setup_test_db("carriers_sorted", "
+            CREATE TABLE flow(from_asset VARCHAR, to_asset VARCHAR, carrier VARCHAR);
+            INSERT INTO flow VALUES ('a', 'b', 'hydrogen'), ('b', 'c', 'electricity'), ('c', 'd', 'gas'), ('d', 'e', 'electricity');
+        ");
+        let carriers: Vec<String> = column_string(&response_to_batches(get_assets_carriers(db_path).unwrap()), "carrier")
+            .into_iter().flatten().collect();
+        assert_eq!(carriers, vec!["electricity", "gas", "hydrogen"]);
+    }
+
+    #[test]
+    fn create_recommended_indexes_test() {
+        let db_path: String = setup_test_db("recommended_indexes", YEARS_FIXTURE_SQL);
+        let created: Vec<String> = create_recommended_indexes(db_path.clone()).unwrap();
+        assert_eq!(created, vec!["idx_var_flow_from_asset_to_asset_year_rep_period", "idx_asset_both_asset_milestone_year"]);
+
+        // creating them again is a no-op
+        assert_eq!(create_recommended_indexes(db_path.clone()).unwrap().len(), 2);
+
+        // queries still return the same data
+        assert_eq!(available_years(&db_path, "capacity").unwrap(), vec![2030.0, 2050.0]);
+        assert_eq!(available_years(&db_path, "flows").unwrap(), vec![2030.0]);
+    }
+
+    #[test]
+    fn get_tables_overview_test() {
+        let db_path: String = setup_test_db("tables_overview", "
+            CREATE TABLE demoTable(id INTEGER, name VARCHAR);
+            INSERT INTO demoTable VALUES (1, 'Belgium'), (2, 'Netherlands'), (3, 'France'), (4, 'Germany');
+            CREATE TABLE emptyTable(id INTEGER, name VARCHAR, value DOUBLE);
+        ");
+        let overview: Vec<TableOverview> = get_tables_overview(db_path).unwrap();
+
+        let demo: &TableOverview = overview.iter().find(|t| t.name == "demoTable").unwrap();
+        assert_eq!((demo.column_count, demo.row_count), (2, Some(4)));
+        let empty: &TableOverview = overview.iter().find(|t| t.name == "emptyTable").unwrap();
+        assert_eq!((empty.column_count, empty.row_count), (3, Some(0)));
+    }
+
+    #[test]
+    fn get_rep_period_info_test() {
+        let db_path: String = setup_test_db("rep_period_info", "
+            CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+            INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 2.5), (2030, 2, 2, 0.5), (2050, 1, 1, 1);
+            CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+            INSERT INTO rep_periods_data VALUES (2030, 1, 24, 1), (2030, 2, 12, 2), (2050, 1, 24, 1);
+        ");
+        let batches = response_to_batches(get_rep_period_info(db_path, 2030).unwrap());
+
+        assert_eq!(column_f64(&batches, "period"), vec![Some(1.0), Some(2.0)]);
+        assert_eq!(column_f64(&batches, "weight"), vec![Some(2.5), Some(0.5)]);
+        assert_eq!(column_f64(&batches, "resolution"), vec![Some(1.0), Some(2.0)]);
+        assert_eq!(column_f64(&batches, "num_timesteps"), vec![Some(24.0), Some(12.0)]);
+    }
+
+    #[test]
+    fn get_period_coverage_test() {
+        // period 1 is represented by rep period 1 alone, period 2 is blended from both rep periods
+        let db_path: String = setup_test_db("period_coverage", "
+            CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+            INSERT INTO rep_periods_mapping VALUES (2030, 2, 2, 0.75), (2030, 1, 1, 1), (2030, 2, 1, 0.25), (2050, 1, 2, 1);
+        ");
+        let batches = response_to_batches(get_period_coverage(db_path, 2030).unwrap());
+
+        assert_eq!(column_f64(&batches, "period"), vec![Some(1.0), Some(2.0), Some(2.0)]);
+        assert_eq!(column_f64(&batches, "rep_period"), vec![Some(1.0), Some(1.0), Some(2.0)]);
+        assert_eq!(column_f64(&batches, "weight"), vec![Some(1.0), Some(0.25), Some(0.75)]);
+        assert_eq!(column_f64(&batches, "period_weight"), vec![Some(1.0), Some(1.0), Some(1.0)]);
+    }
+
+    #[test]
+    fn get_other_bucket_assets_test() {
+        // grouping by NL and BE leaves the two German assets ungrouped
+        let db_path: String = setup_test_db("other_bucket_assets", "
+            CREATE TABLE asset(asset VARCHAR, type VARCHAR);
+            INSERT INTO asset VALUES ('wind', 'producer'), ('solar', 'producer'), ('gas', 'producer'), ('coal', 'producer'), ('nuclear', 'producer');
+            CREATE TABLE category(id INTEGER, name VARCHAR, parent_id INTEGER, level INTEGER);
+            INSERT INTO category VALUES (1, 'location', NULL, 1), (2, 'NL', 1, 0), (3, 'BE', 1, 0), (4, 'DE', 1, 0);
+            CREATE TABLE asset_category(asset VARCHAR, root_id INTEGER, leaf_id INTEGER);
+            INSERT INTO asset_category VALUES ('wind', 1, 2), ('solar', 1, 2), ('gas', 1, 3), ('coal', 1, 4), ('nuclear', 1, 4);
+        ");
+
+        assert_eq!(get_other_bucket_assets(db_path.clone(), vec![2, 3], HashMap::new()), Ok(vec!["coal".to_string(), "nuclear".to_string()]));
+        assert_eq!(get_other_bucket_assets(db_path, vec![], HashMap::new()), Ok(vec![]));
+    }
+
+    #[test]
+    fn get_assets_without_results_test() {
+        // 'idle' has no flow at all, 'unsolved' only a NULL solution and 'late' only flows in 2050
+        let db_path: String = setup_test_db("assets_without_results", "
+            CREATE TABLE asset(asset VARCHAR, type VARCHAR);
+            INSERT INTO asset VALUES ('wind', 'producer'), ('demand', 'consumer'), ('idle', 'producer'), ('unsolved', 'producer'), ('late', 'producer');
+            CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, solution DOUBLE);
+            INSERT INTO var_flow VALUES ('wind', 'demand', 2030, 1, 1, 1, 3), ('unsolved', 'demand', 2030, 1, 1, 1, NULL), ('late', 'demand', 2050, 1, 1, 1, 1);
+        ");
+
+        assert_eq!(get_assets_without_results(db_path, 2030), Ok(vec!["idle".to_string(), "late".to_string(), "unsolved".to_string()]));
+    }
+
+    #[test]
+    fn get_assets_without_results_unsolved_test() {
+        let db_path: String = setup_test_db("assets_without_results_unsolved", "
+            CREATE TABLE asset(asset VARCHAR, type VARCHAR);
+            INSERT INTO asset VALUES ('wind', 'producer'), ('demand', 'consumer');
+            CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER);
+            INSERT INTO var_flow VALUES ('wind', 'demand', 2030, 1, 1, 1);
+        ");
+
+        assert_eq!(get_assets_without_results(db_path, 2030), Ok(vec!["demand".to_string(), "wind".to_string()]));
+    }
+
+    #[test]
+    fn get_annual_scaling_check_test() {
+        // 2030: 10 days of 24 hours with weight 36.5 make a full year, 2050: the same days with weight 30 only 7200 hours
+        let db_path: String = setup_test_db("annual_scaling_check", "
+            CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+            INSERT INTO rep_periods_mapping SELECT 2030, p, 1, 36.5 FROM range(1, 11) AS periods(p);
+            INSERT INTO rep_periods_mapping SELECT 2050, p, 1, 30 FROM range(1, 11) AS periods(p);
+            CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+            INSERT INTO rep_periods_data VALUES (2030, 1, 24, 1), (2050, 1, 24, 1);
+        ");
+
+        let full: AnnualScaling = get_annual_scaling_check(db_path.clone(), 2030).unwrap();
+        assert!(full.covers_full_year && full.weighted_hours == 8760.0);
+
+        let under: AnnualScaling = get_annual_scaling_check(db_path, 2050).unwrap();
+        assert_eq!(under, AnnualScaling { year: 2050, weighted_hours: 7200.0, year_hours: 8760.0, difference: -1560.0, covers_full_year: false });
+    }
+
+    #[test]
+    fn get_resolution_summary_test() {
+        // two 1-timestep blocks and one 6-timestep block in 2030, the 2050 block is ignored
+        let db_path: String = setup_test_db("resolution_summary", "
+            CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, solution DOUBLE);
+            INSERT INTO var_flow VALUES ('wind', 'demand', 2030, 1, 1, 1, 1), ('wind', 'demand', 2030, 1, 2, 2, 1),
+                ('gas', 'demand', 2030, 1, 1, 6, 1), ('gas', 'demand', 2050, 1, 1, 3, 1);
+        ");
+        let batches = response_to_batches(get_resolution_summary(db_path, 2030).unwrap());
+
+        assert_eq!(column_f64(&batches, "block_length"), vec![Some(1.0), Some(6.0)]);
+        assert_eq!(column_f64(&batches, "block_count"), vec![Some(2.0), Some(1.0)]);
+    }
+
+    #[test]
+    fn check_time_block_integrity_test() {
+        // 'wind' blocks 1-2 and 2-4 overlap on 2, 'solar' has nothing for 2 and stops before the end at 4
+        let db_path: String = setup_test_db("time_block_integrity", "
+            CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, solution DOUBLE);
+            INSERT INTO var_flow VALUES ('wind', 'demand', 2030, 1, 1, 2, 1), ('wind', 'demand', 2030, 1, 2, 4, 1),
+                ('solar', 'demand', 2030, 1, 1, 1, 1), ('solar', 'demand', 2030, 1, 3, 3, 1), ('gas', 'demand', 2030, 1, 1, 4, 1);
+            CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+            INSERT INTO rep_periods_data VALUES (2030, 1, 4, 1);
+        ");
+        let batches = response_to_batches(check_time_block_integrity(db_path, "var_flow".to_string(), 2030).unwrap());
+
+        assert_eq!(column_string(&batches, "from_asset"), vec![Some("solar".to_string()), Some("solar".to_string()), Some("wind".to_string())]);
+        assert_eq!(column_string(&batches, "issue"), vec![Some("gap".to_string()), Some("gap".to_string()), Some("overlap".to_string())]);
+        assert_eq!(column_f64(&batches, "range_start"), vec![Some(2.0), Some(4.0), Some(2.0)]);
+        assert_eq!(column_f64(&batches, "range_end"), vec![Some(2.0), Some(4.0), Some(2.0)]);
+    }
+
+    #[test]
+    fn check_time_block_integrity_invalid_table_test() {
+        let db_path: String = setup_test_db("time_block_integrity_invalid", "CREATE TABLE asset(asset VARCHAR);");
+
+        assert!(check_time_block_integrity(db_path.clone(), "var_flow; DROP TABLE asset".to_string(), 2030).is_err());
+        assert_eq!(check_time_block_integrity(db_path, "asset".to_string(), 2030).err().unwrap(), "Table 'asset' has no column 'year'");
+    }
+
+    #[test]
+    fn check_series_cap_invalid_series_col_test() {
+        // the crafted column would otherwise end the count query and drop the table
+        let db_path: String = setup_test_db("series_cap_invalid_series_col", "CREATE TABLE asset(asset VARCHAR);");
+        let bad: &str = "asset) FROM asset; DROP TABLE asset; --";
+
+        assert_eq!(check_series_cap(&db_path, "SELECT asset FROM asset", bad, vec![], Some(1)), Err(format!("Invalid identifier: '{}'", bad)));
+        assert!(fetch_pivot_series(&db_path, "SELECT asset FROM asset", bad, vec![]).is_err());
+        assert_eq!(check_table_exists(db_path, "asset"), Ok(true));
+    }
+
+    #[test]
+    fn get_min_resolution_test() {
+        // blocks of 3 and 2 hours in rep period 1, and a block of 2 half hour timesteps in rep period 2
+        let db_path: String = setup_test_db("min_resolution", "
+            CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, solution DOUBLE);
+            INSERT INTO var_flow VALUES ('wind', 'demand', 2030, 1, 1, 3, 1), ('wind', 'demand', 2030, 1, 4, 5, 1), ('wind', 'demand', 2030, 2, 1, 2, 1);
+            CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+            INSERT INTO rep_periods_data VALUES (2030, 1, 5, 1), (2030, 2, 2, 0.5);
+        ");
+
+        assert_eq!(get_min_resolution(db_path.clone(), "flows".to_string()), Ok(Some(1.0)));
+        assert_eq!(get_min_resolution(db_path.clone(), "storage_price".to_string()), Ok(None));
+        assert!(get_min_resolution(db_path, "capacity".to_string()).is_err());
+    }
+
+    #[test]
+    fn check_column_in_table_quoted_name_test() {
+        let db_path: String = setup_test_db("column_in_table_quoted", "CREATE TABLE \"my table\"(id INTEGER, \"odd col\" VARCHAR);");
+
+        assert_eq!(check_column_in_table(db_path.clone(), "my table", "odd col"), Ok(true));
+        assert_eq!(check_column_in_table(db_path.clone(), "\"my table\"", "id"), Ok(true));
+        assert_eq!(check_column_in_table(db_path, "my table", "name"), Ok(false));
+    }
+
+    #[test]
+    fn check_column_in_table_qualified_name_test() {
+        let db_path: String = setup_test_db("column_in_table_qualified", "
+            CREATE TABLE asset(asset VARCHAR);
+            CREATE SCHEMA other;
+            CREATE TABLE other.asset(id INTEGER);
+        ");
+
+        assert_eq!(check_column_in_table(db_path.clone(), "other.asset", "id"), Ok(true));
+        assert_eq!(check_column_in_table(db_path.clone(), "\"other\".\"asset\"", "asset"), Ok(false));
+        assert_eq!(check_column_in_table(db_path, "main.asset", "asset"), Ok(true));
+    }
+
+    #[test]
+    fn check_column_in_table_invalid_name_test() {
+        let db_path: String = setup_test_db("column_in_table_invalid", "CREATE TABLE asset(asset VARCHAR);");
+
+        assert!(check_column_in_table(db_path.clone(), "main..asset", "asset").unwrap_err().contains("Invalid table name"));
+        assert!(check_column_in_table(db_path.clone(), "\"asset", "asset").unwrap_err().contains("Invalid table name"));
+        assert!(check_column_in_table(db_path, "missing", "asset").unwrap_err().contains("Could not read the columns of table 'missing'"));
+    }
+
+    #[test]
+    fn has_solution_data_inputs_only_test() {
+        let db_path: String = setup_test_db("has_solution_inputs_only", "
+            CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER);
+            CREATE TABLE var_assets_investment(asset VARCHAR, milestone_year INTEGER);
+        ");
+        let report: SolutionReport = has_solution_data(db_path).unwrap();
+        assert!(!report.has_solution);
+        assert!(report.columns.iter().all(|c| !c.present));
+    }
+
+    #[test]
+    fn has_solution_data_solved_test() {
+        let db_path: String = setup_test_db("has_solution_solved", "
+            CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, solution DOUBLE);
+            CREATE TABLE var_assets_investment(asset VARCHAR, milestone_year INTEGER, solution DOUBLE);
+            CREATE TABLE cons_balance_storage_rep_period(asset VARCHAR, year INTEGER, dual_balance_storage_rep_period DOUBLE);
+        ");
+        let report: SolutionReport = has_solution_data(db_path).unwrap();
+        assert!(report.has_solution);
+
+        let present: Vec<&str> = report.columns.iter().filter(|c| c.present).map(|c| c.table.as_str()).collect();
+        assert_eq!(present, vec!["var_flow", "var_assets_investment", "cons_balance_storage_rep_period"]);
+    }
+
+    #[test]
+    fn get_carrier_capabilities_test() {
+        // electricity is produced, stored and transported, hydrogen only converted into, gas only produced
+        let db_path: String = setup_test_db("carrier_capabilities", "
+            CREATE TABLE asset(asset VARCHAR, type VARCHAR);
+            INSERT INTO asset VALUES ('wind', 'producer'), ('battery', 'storage'), ('demand_nl', 'consumer'), ('demand_be', 'consumer'),
+                ('electrolyser', 'conversion'), ('h2_demand', 'consumer'), ('gas_well', 'producer');
+            CREATE TABLE flow(from_asset VARCHAR, to_asset VARCHAR, carrier VARCHAR, is_transport BOOLEAN);
+            INSERT INTO flow VALUES
+                ('wind', 'battery', 'electricity', FALSE), ('battery', 'demand_nl', 'electricity', FALSE),
+                ('demand_nl', 'demand_be', 'electricity', TRUE), ('electrolyser', 'h2_demand', 'hydrogen', NULL),
+                ('gas_well', 'electrolyser', 'gas', FALSE);
+        ");
+        let capabilities: Vec<(String, bool, bool, bool)> = get_carrier_capabilities(db_path).unwrap().into_iter()
+            .map(|c| (c.carrier, c.transport, c.production, c.storage))
+            .collect();
+
+        assert_eq!(capabilities, vec![
+            ("electricity".to_string(), true, true, true),
+            ("gas".to_string(), false, true, false),
+            ("hydrogen".to_string(), false, true, false),
+        ]);
+    }
+
+    #[test]
+    fn get_solver_summary_present_test() {
+        let db_path: String = setup_test_db("solver_summary_present", "
+            CREATE TABLE solver_summary(objective_value DOUBLE, termination_status VARCHAR);
+            INSERT INTO solver_summary VALUES (1234.5, 'OPTIMAL');
+        ");
+        let summary: SolverSummary = get_solver_summary(db_path).unwrap();
+
+        assert!(summary.available);
+        assert_eq!(summary.objective_value, Some(1234.5));
+        assert_eq!(summary.termination_status, Some("OPTIMAL".to_string()));
+        assert_eq!(summary.solve_time, None);
+    }
+
+    #[test]
+    fn get_solver_summary_absent_test() {
+        let db_path: String = setup_test_db("solver_summary_absent", YEARS_FIXTURE_SQL);
+        let summary: SolverSummary = get_solver_summary(db_path).unwrap();
+
+        assert!(!summary.available);
+        assert_eq!(summary.objective_value, None);
+        assert_eq!(summary.termination_status, None);
+    }
+
+    #[test]
+    fn get_available_years_for_unknown_analysis_test() {
+        let db_path: String = setup_test_db("available_years_unknown", YEARS_FIXTURE_SQL);
+        let res = available_years(&db_path, "weather");
+        assert!(res.is_err() && res.unwrap_err().contains("Unknown analysis type"));
+    }
+
+    const ASSET_CATEGORY_FIXTURE_SQL: &str = "
+        CREATE TABLE category(id INTEGER, name VARCHAR, parent_id INTEGER, level INTEGER);
+        INSERT INTO category VALUES (1, 'technology', NULL, 1), (2, 'renewable', 1, 0), (3, 'country', NULL, 1), (4, 'NL', 3, 0);
+        CREATE TABLE asset_category(asset VARCHAR, root_id INTEGER, leaf_id INTEGER);
+        INSERT INTO asset_category VALUES ('wind_nl', 1, 2), ('wind_nl', 3, 4), ('gas_be', 1, 1);
+    ";
+
+    #[test]
+    fn get_category_tree_text_three_levels_test() {
+        let db_path: String = setup_test_db("category_tree_text_three_levels", "
+            CREATE TABLE category(id INTEGER, name VARCHAR, parent_id INTEGER, level INTEGER);
+            INSERT INTO category VALUES
+                (1, 'technology', NULL, 2), (2, 'renewable', 1, 1), (3, 'wind', 2, 0), (4, 'solar', 2, 0),
+                (5, 'fossil', 1, 1), (6, 'country', NULL, 1), (7, 'NL', 6, 0);
+        ");
+
+        assert_eq!(
+            get_category_tree_text(db_path).unwrap(),
+            "technology\n  renewable\n    wind\n    solar\n  fossil\ncountry\n  NL",
+        );
+    }
+
+    #[test]
+    fn get_category_tree_text_cycle_test() {
+        let db_path: String = setup_test_db("category_tree_text_cycle", "
+            CREATE TABLE category(id INTEGER, name VARCHAR, parent_id INTEGER, level INTEGER);
+            INSERT INTO category VALUES (1, 'root', NULL, 1), (2, 'a', 3, 0), (3, 'b', 2, 0);
+        ");
+
+        assert_eq!(get_category_tree_text(db_path).unwrap(), "root\na\n  b\n    a (cycle)");
+    }
+
+    #[test]
+    fn get_asset_categories_one_asset_test() {
+        let db_path: String = setup_test_db("asset_categories_one", ASSET_CATEGORY_FIXTURE_SQL);
+        let batches = response_to_batches(get_asset_categories(db_path, Some("wind_nl".to_string())).unwrap());
+
+        assert_eq!(column_string(&batches, "asset"), vec![Some("wind_nl".to_string()), Some("wind_nl".to_string())]);
+        assert_eq!(column_string(&batches, "root_name"), vec![Some("technology".to_string()), Some("country".to_string())]);
+        assert_eq!(column_string(&batches, "leaf_name"), vec![Some("renewable".to_string()), Some("NL".to_string())]);
+    }
+
+    #[test]
+    fn get_asset_categories_all_assets_test() {
+        let db_path: String = setup_test_db("asset_categories_all", ASSET_CATEGORY_FIXTURE_SQL);
+        let batches = response_to_batches(get_asset_categories(db_path, None).unwrap());
+
+        assert_eq!(num_rows(&batches), 3);
+    }
+
+    // 2030 spans a full year of daily periods, 2050 a single day
+    const AUTO_RESOLUTION_FIXTURE_SQL: &str = "
+        CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+        INSERT INTO rep_periods_mapping SELECT 2030, p, 1, 1 FROM range(1, 366) AS t(p);
+        INSERT INTO rep_periods_mapping VALUES (2050, 1, 1, 1);
+        CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+        INSERT INTO rep_periods_data VALUES (2030, 1, 24, 1), (2050, 1, 24, 1);
+    ";
+
+    #[test]
+    fn resolve_resolution_auto_test() {
+        let db_path: String = setup_test_db("resolve_resolution_auto", AUTO_RESOLUTION_FIXTURE_SQL);
+        let long_year: u32 = resolve_resolution(&db_path, 2030, AUTO_RESOLUTION).unwrap();
+        let short_year: u32 = resolve_resolution(&db_path, 2050, AUTO_RESOLUTION).unwrap();
+
+        // 8760 hours / 500 points rounds up to 24 hours, a single day stays hourly
+        assert_eq!(long_year, 24);
+        assert_eq!(short_year, 1);
+        assert!(long_year > short_year);
+    }
+
+    #[test]
+    fn resolve_resolution_explicit_test() {
+        let db_path: String = setup_test_db("resolve_resolution_explicit", AUTO_RESOLUTION_FIXTURE_SQL);
+        assert_eq!(resolve_resolution(&db_path, 2030, 6), Ok(6));
+    }
+
+    #[test]
+    fn get_assets_without_results_tulipa_fixture_test() {
+        let db_path: String = setup_tulipa_db("assets_without_results_tulipa_fixture", "");
+
+        assert_eq!(column_f64(&response_to_batches(get_years(db_path.clone()).unwrap()), "year"), vec![Some(2030.0)]);
+        assert_eq!(get_assets_without_results(db_path, 2030), Ok(vec!["wind".to_string()]));
+    }
+}
+
+// --- QUERIES ---
+
+const ASSET_SQL: &str = "SELECT asset FROM asset ORDER BY asset;";
+const ASSETS_WITHOUT_RESULTS_SQL: &str = "
+    SELECT a.asset
+    FROM asset AS a
+    WHERE NOT EXISTS (
+        SELECT 1
+        FROM var_flow AS vf
+        WHERE (vf.from_asset = a.asset OR vf.to_asset = a.asset)
+          AND vf.year = ?
+          AND vf.solution IS NOT NULL
+    )
+    ORDER BY a.asset;
+";
+const ASSET_COUNT_SQL: &str = "SELECT COUNT(*) FROM asset;";
+const ASSET_TYPES_SQL: &str = "SELECT DISTINCT type FROM asset ORDER BY type;";
+const SEARCH_ASSETS_SQL: &str = "
+    SELECT asset
+    FROM asset
+    WHERE contains(lower(asset), lower($1))
+    ORDER BY NOT starts_with(lower(asset), lower($1)), asset
+    LIMIT $2;
+";
 const TABLES_SQL: &str = "SHOW TABLES";
+const TABLES_OVERVIEW_SQL: &str = "
+    SELECT table_name, column_count
+    FROM duckdb_tables()
+    WHERE NOT internal
+    ORDER BY table_name;
+";
+const COUNT_ROWS_SQL: &str = "SELECT COUNT(*) FROM \"{table}\";";
 const TABLE_INFO_SQL: &str = "PRAGMA table_info({{1}});";
-const CARRIER_SQL: &str = "SELECT DISTINCT carrier FROM flow;";
-const INFER_CARRIER_SQL_FROM_OUTGOING_FLOWS: &str = "
+// (table, column) pairs which are only filled in once the model has been solved
+const SOLUTION_COLUMNS: [(&str, &str); 8] = [
+    ("var_flow", "solution"),
+    ("var_assets_investment", "solution"),
+    ("var_units_on", "solution"),
+    ("cons_capacity_outgoing_simple_method", "dual_max_output_flows_limit_simple_method"),
+    ("cons_capacity_outgoing_compact_method", "dual_max_output_flows_limit_compact_method"),
+    ("cons_balance_storage_rep_period", "dual_balance_storage_rep_period"),
+    ("cons_balance_storage_over_clustered_year", "dual_balance_storage_over_clustered_year"),
+    ("cons_transport_flow_limit_simple_method", "dual_max_transport_flow_limit_simple_method"),
+];
+// (table, columns) which are joined on repeatedly by the resolution and cost queries
+const RECOMMENDED_INDEXES: [(&str, &str); 7] = [
+    ("var_flow", "from_asset, to_asset, year, rep_period"),
+    ("asset_both", "asset, milestone_year"),
+    ("rep_periods_mapping", "year, rep_period"),
+    ("rep_periods_data", "year, rep_period"),
+    ("flow", "from_asset, to_asset"),
+    ("asset_category", "asset"),
+    ("var_units_on", "asset, year, rep_period"),
+];
+// core tables counted by `preload_database`, every analysis reads at least one of them
+const PRELOAD_TABLES: [&str; 5] = ["asset", "flow", "var_flow", "rep_periods_mapping", "rep_periods_data"];
+// the optional solver summary table read by `get_solver_summary`, with its (column, type) pairs
+const SOLVER_TABLE: &str = "solver_summary";
+const SOLVER_COLUMNS: [(&str, &str); 3] = [
+    ("objective_value", "DOUBLE"),
+    ("termination_status", "VARCHAR"),
+    ("solve_time", "DOUBLE"),
+];
+const SOLVER_SUMMARY_SQL: &str = "SELECT {columns} FROM {table} LIMIT 1;";
+// (child table, columns, referenced table, columns) foreign keys which Tulipa exports are expected to satisfy
+const INTEGRITY_CHECKS: [(&str, &[&str], &str, &[&str]); 9] = [
+    ("var_flow", &["from_asset"], "asset", &["asset"]),
+    ("var_flow", &["to_asset"], "asset", &["asset"]),
+    ("flow", &["from_asset"], "asset", &["asset"]),
+    ("flow", &["to_asset"], "asset", &["asset"]),
+    ("asset_both", &["asset"], "asset", &["asset"]),
+    ("asset_category", &["asset"], "asset", &["asset"]),
+    ("asset_category", &["leaf_id"], "category", &["id"]),
+    ("category", &["parent_id"], "category", &["id"]),
+    ("rep_periods_mapping", &["year", "rep_period"], "rep_periods_data", &["year", "rep_period"]),
+];
+const ORPHAN_COUNT_SQL: &str = "
+    SELECT COUNT(*)
+    FROM {child_table} AS c
+    WHERE {not_null}
+      AND NOT EXISTS (SELECT 1 FROM {parent_table} AS p WHERE {join});
+";
+// `previous_end` is the furthest end of the earlier blocks, so a block nested in a longer one is an overlap too
+const TIME_BLOCK_INTEGRITY_SQL: &str = "
+    WITH blocks AS (
+        SELECT
+            {key_cols},
+            year,
+            rep_period,
+            time_block_start,
+            time_block_end,
+            MAX(time_block_end) OVER (
+                PARTITION BY {key_cols}, year, rep_period
+                ORDER BY time_block_start, time_block_end
+                ROWS BETWEEN UNBOUNDED PRECEDING AND 1 PRECEDING
+            ) AS previous_end
+        FROM {table}
+        WHERE year = $1
+    ), issues AS (
+        SELECT {key_cols}, year, rep_period, 'gap' AS issue, COALESCE(previous_end, 0) + 1 AS range_start, time_block_start - 1 AS range_end
+        FROM blocks
+        WHERE time_block_start > COALESCE(previous_end, 0) + 1
+
+        UNION ALL
+
+        SELECT {key_cols}, year, rep_period, 'overlap' AS issue, time_block_start AS range_start, LEAST(previous_end, time_block_end) AS range_end
+        FROM blocks
+        WHERE time_block_start <= previous_end
+
+        UNION ALL
+
+        -- the blocks stop before the end of the rep period
+        SELECT {key_cols}, year, rep_period, 'gap' AS issue, MAX(time_block_end) + 1 AS range_start, num_timesteps AS range_end
+        FROM blocks
+        JOIN rep_periods_data USING (year, rep_period)
+        GROUP BY {key_cols}, year, rep_period, num_timesteps
+        HAVING MAX(time_block_end) < num_timesteps
+    )
+    SELECT *
+    FROM issues
+    ORDER BY ALL;
+";
+const ACCESS_MODE_SQL: &str = "SELECT value FROM duckdb_settings() WHERE name = 'access_mode';";
+const TABLE_EXISTS_SQL: &str = "SELECT table_name FROM information_schema.tables WHERE table_name = ?;";
+const AVAILABLE_YEARS_SQL: &str = "
+    SELECT DISTINCT year FROM (
+    {year_selects}
+    ) AS t
+    WHERE year IS NOT NULL
+    ORDER BY year;
+";
+const NO_YEARS_SQL: &str = "SELECT CAST(NULL AS INTEGER) AS year WHERE FALSE;";
+const MIN_BLOCK_HOURS_SQL: &str = "
+    SELECT MIN((b.time_block_end - b.time_block_start + 1) * rpd.resolution)
+    FROM (
+        {block_selects}
+    ) AS b
+    JOIN rep_periods_data AS rpd ON rpd.year = b.year AND rpd.rep_period = b.rep_period;
+";
+const RESOLUTION_SUMMARY_SQL: &str = "
+    SELECT
+        time_block_end - time_block_start + 1 AS block_length,
+        COUNT(*) AS block_count
+    FROM var_flow
+    WHERE year = ?
+    GROUP BY block_length
+    ORDER BY block_length;
+";
+const REP_PERIOD_INFO_SQL: &str = "
+    SELECT
+        rpm.year,
+        rpm.period,
+        rpm.rep_period,
+        rpm.weight,
+        rpd.resolution,
+        rpd.num_timesteps
+    FROM rep_periods_mapping AS rpm
+    JOIN rep_periods_data AS rpd ON rpd.year = rpm.year AND rpd.rep_period = rpm.rep_period
+    WHERE rpm.year = ?
+    ORDER BY rpm.period, rpm.rep_period;
+";
+const PERIOD_COVERAGE_SQL: &str = "
+    SELECT
+        year,
+        period,
+        rep_period,
+        weight,
+        SUM(weight) OVER (PARTITION BY period) AS period_weight
+    FROM rep_periods_mapping
+    WHERE year = ?
+    ORDER BY period, rep_period;
+";
+const CARRIER_SQL: &str = "SELECT DISTINCT carrier FROM flow ORDER BY carrier;";
+pub const INFER_CARRIER_SQL_FROM_OUTGOING_FLOWS: &str = "
 SELECT DISTINCT
 a.asset,
 f.carrier
@@ -97,13 +1469,88 @@ FROM asset AS a
 JOIN flow AS f
 ON f.from_asset = a.asset
 ";
+const ALL_MILESTONES_SQL: &str = "SELECT *, TRUE AS is_milestone FROM year_data";
 const YEARS_SQL: &str = "
     SELECT DISTINCT year
     FROM year_data AS y
     WHERE y.is_milestone = true
     ORDER BY year;
 ";
+const CARRIER_CAPABILITIES_SQL: &str = "
+    SELECT
+        f.carrier,
+        COALESCE(BOOL_OR(f.is_transport), FALSE) AS transport,
+        COALESCE(BOOL_OR(a_from.type IN ('producer', 'conversion')), FALSE) AS production,
+        COALESCE(BOOL_OR(a_from.type = 'storage' OR a_to.type = 'storage'), FALSE) AS storage
+    FROM flow AS f
+    LEFT JOIN asset AS a_from ON a_from.asset = f.from_asset
+    LEFT JOIN asset AS a_to ON a_to.asset = f.to_asset
+    WHERE f.carrier IS NOT NULL
+    GROUP BY f.carrier
+    ORDER BY f.carrier;
+";
+const CATEGORY_TREE_SQL: &str = "SELECT id, name, parent_id FROM category ORDER BY id;";
 const CATEGORY_SQL: &str = "SELECT id, name, parent_id, level FROM category ORDER BY level;";
+// the same CASE as the breakdown queries, so the bucket matches theirs exactly
+const OTHER_BUCKET_ASSETS_SQL: &str = "
+    SELECT a.asset
+    FROM asset AS a
+    {breakdown_joins}
+    WHERE 1 {filter_conditions}
+      AND CASE
+        {breakdown_case_conditions}
+        ELSE 'Other'
+      END = 'Other'
+    ORDER BY a.asset
+";
+const ASSET_CATEGORIES_SQL: &str = "
+    SELECT
+        ac.asset,
+        ac.root_id,
+        r.name AS root_name,
+        ac.leaf_id,
+        l.name AS leaf_name
+    FROM asset_category AS ac
+    LEFT JOIN category AS r ON r.id = ac.root_id
+    LEFT JOIN category AS l ON l.id = ac.leaf_id
+    WHERE $1 IS NULL OR ac.asset = $1
+    ORDER BY ac.asset, ac.root_id, ac.leaf_id;
+";
+pub const MAX_PIVOT_SERIES: usize = 50;
+const PIVOT_SERIES_SQL: &str = "
+    SELECT DISTINCT CAST({series_col} AS VARCHAR) AS series
+    FROM (
+        {sql}
+    ) AS pivot_source
+    WHERE {series_col} IS NOT NULL
+    ORDER BY series
+    LIMIT {limit};
+";
+
+const SERIES_COUNT_SQL: &str = "
+    SELECT COUNT(DISTINCT {series_col})
+    FROM (
+        {sql}
+    ) AS series_source;
+";
+pub const AUTO_RESOLUTION: u32 = 0;
+const AUTO_RESOLUTION_TARGET_POINTS: f64 = 500.0;
+// hour, day, week, month (730h), quarter (2190h), year
+const COMMON_RESOLUTIONS: [u32; 14] = [1, 2, 3, 4, 6, 8, 12, 24, 48, 72, 168, 730, 2190, 8760];
+const HOURS_PER_YEAR: f64 = 8760.0;
+const ANNUAL_SCALING_TOLERANCE_HOURS: f64 = 1e-6;
+const ANNUAL_WEIGHTED_HOURS_SQL: &str = "
+    SELECT CAST(COALESCE(SUM(rpm.weight * rpd.num_timesteps * rpd.resolution), 0) AS DOUBLE)
+    FROM rep_periods_mapping AS rpm
+    JOIN rep_periods_data AS rpd ON rpd.year = rpm.year AND rpd.rep_period = rpm.rep_period
+    WHERE rpm.year = ?;
+";
+const YEAR_HOUR_SPAN_SQL: &str = "
+    SELECT CAST(COALESCE(SUM(rpd.num_timesteps * rpd.resolution), 0) AS DOUBLE)
+    FROM rep_periods_mapping AS rpm
+    JOIN rep_periods_data AS rpd ON rpd.year = rpm.year AND rpd.rep_period = rpm.rep_period
+    WHERE rpm.year = ?;
+";
 const HAS_META_SQL: &str = "SELECT EXISTS (
     SELECT 1
     FROM information_schema.tables
@@ -1,8 +1,15 @@
+pub mod analysis_bundle;
 pub mod capacity;
+pub mod dashboard;
+pub mod energy_balance;
 pub mod import_export;
 pub mod metadata;
 pub mod production_price;
+pub mod profiles;
 pub mod query;
+pub mod raw_duals;
+pub mod remote;
+pub mod statistics;
 pub mod storage_price;
 pub mod system_cost;
 pub mod transport_price;
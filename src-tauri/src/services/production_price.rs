@@ -2,9 +2,10 @@ use duckdb::arrow::{array::RecordBatch, datatypes::Schema};
 use duckdb::{ types::Value};
 use tauri::ipc::Response;
 use crate::services::query_builder::{build_resolution_query, build_resolution_query_with_filters, build_resolution_query_with_filters_and_breakdown, build_breakdown_columns,
-    build_breakdown_joins, build_breakdown_case_conditions, build_filter_conditions, build_breakdown_selects, build_breakdown_group_by};
-use crate::duckdb_conn::{run_query_rb, serialize_recordbatch};
-use crate::services::metadata::{check_column_in_table, apply_carrier_filter};
+    build_breakdown_joins, build_breakdown_case_conditions, build_filter_conditions, build_breakdown_selects, build_breakdown_group_by,
+    with_category_descendants, with_cross_asset_aggregation, with_resolution_order, with_rounding, with_seasonal, with_clamped_negatives, with_timestamps, with_zeroed_values, build_pivot_query, RESOLUTION_PIVOT_INDEX, ResolutionOptions};
+use crate::duckdb_conn::{debug_sql_metadata, run_query_rb, serialize_recordbatch, serialize_recordbatch_with_metadata, RESOLUTION_METADATA_KEY};
+use crate::services::metadata::{check_table_exists, check_column_in_table, check_series_cap, apply_carrier_filter, ensure_metadata_available, fetch_pivot_series, resolve_resolution, AUTO_RESOLUTION, INFER_CARRIER_SQL_FROM_OUTGOING_FLOWS};
 use std::collections::HashMap;
 
 #[tauri::command]
@@ -15,7 +16,14 @@ pub fn get_production_price_resolution(
     carrier: String,
     filters: HashMap<i32, Vec<i32>>,
     grouper: Vec<i32>,
-    enable_metadata: bool) -> Result<Response, String> {
+    enable_metadata: bool,
+    options: Option<ResolutionOptions>,
+) -> Result<Response, String> {
+    let options: ResolutionOptions = options.unwrap_or_default();
+    if enable_metadata {
+        ensure_metadata_available(&db_path)?;
+    }
+    let resolution: u32 = resolve_resolution(&db_path, year, resolution)?;
     let sql: String;
     let has_breakdown = !grouper.is_empty();
 
@@ -33,8 +41,10 @@ pub fn get_production_price_resolution(
                 &grouper,
                 "ac.asset".to_string(),
                 "ac.asset".to_string(),
-                false
-            ).trim_end_matches(';').trim_end().to_string();
+                false,
+                options.merge_digits,
+                "year"
+            )?.trim_end_matches(';').trim_end().to_string();
         }
         else {
             // With filters without breakdown
@@ -46,8 +56,10 @@ pub fn get_production_price_resolution(
                 &resolution.to_string(),
                 &filters,
                 "ac.asset".to_string(),
-                false
-            ).trim_end_matches(';').trim_end().to_string();
+                false,
+                options.merge_digits,
+                "year"
+            )?.trim_end_matches(';').trim_end().to_string();
         }
     }
      else {
@@ -57,8 +69,10 @@ pub fn get_production_price_resolution(
             &["asset"],
             "avg",
             &resolution.to_string(),
-            false
-        ).trim_end_matches(';').trim_end().to_string();
+            false,
+            options.merge_digits,
+            "year"
+        )?.trim_end_matches(';').trim_end().to_string();
     }
 
     println!("Filtering on nodes: {:#?}", filters);
@@ -71,15 +85,20 @@ pub fn get_production_price_resolution(
                 "
                 WITH production_table AS (
                     {}
-                    UNION ALL
-                    {}
                 )
                 SELECT * FROM (
                     {}
                 ) AS subquery
                 ",
-                apply_carrier_filter(PRODUCTION_DATA_SIMPLE_SQL, &carrier),
-                apply_carrier_filter(PRODUCTION_DATA_COMPACT_SQL, &carrier),
+                with_cross_asset_aggregation(
+                    &format!(
+                        "{}\n                    UNION ALL\n                    {}",
+                        with_zeroed_values(&apply_carrier_filter(PRODUCTION_DATA_SIMPLE_SQL, &carrier), &["dual_value"], options.zero_epsilon)?,
+                        with_zeroed_values(&apply_carrier_filter(PRODUCTION_DATA_COMPACT_SQL, &carrier), &["dual_value"], options.zero_epsilon)?,
+                    ),
+                    options.asset_aggregation.as_deref(),
+                    TIME_BLOCK_COLUMNS,
+                )?,
                 sql
             )
             .replace("{breakdown_selects}", &build_breakdown_selects(&grouper))
@@ -101,7 +120,7 @@ pub fn get_production_price_resolution(
                     {}
                 ) AS subquery
                 ",
-                apply_carrier_filter(PRODUCTION_DATA_COMPACT_SQL, &carrier),
+                with_cross_asset_aggregation(&with_zeroed_values(&apply_carrier_filter(PRODUCTION_DATA_COMPACT_SQL, &carrier), &["dual_value"], options.zero_epsilon)?, options.asset_aggregation.as_deref(), TIME_BLOCK_COLUMNS)?,
                 sql
             )
             .replace("{breakdown_selects}", &build_breakdown_selects(&grouper))
@@ -127,7 +146,7 @@ pub fn get_production_price_resolution(
                     {}
                 ) AS subquery
                 ",
-                apply_carrier_filter(PRODUCTION_DATA_SIMPLE_SQL, &carrier),
+                with_cross_asset_aggregation(&with_zeroed_values(&apply_carrier_filter(PRODUCTION_DATA_SIMPLE_SQL, &carrier), &["dual_value"], options.zero_epsilon)?, options.asset_aggregation.as_deref(), TIME_BLOCK_COLUMNS)?,
                 sql
             )
             .replace("{breakdown_selects}", &build_breakdown_selects(&grouper))
@@ -148,15 +167,473 @@ pub fn get_production_price_resolution(
             );
         }
     }
-    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, query, vec![Value::from(year)])?;
+    let shared_filters: HashMap<i32, Vec<i32>> = if enable_metadata { filters } else { HashMap::new() };
+    let query: String = with_category_descendants(&query, &shared_filters, &grouper);
+    check_series_cap(&db_path, &query, "asset", vec![Value::from(year)], options.max_series)?;
+    let query: String = with_seasonal(&query, "asset", options.seasonal, options.order_by.as_deref(), options.pivot, resolution)?;
+    let query: String = with_clamped_negatives(&query, &["y_axis"], options.clamp_negative)?;
+    let query: String = with_rounding(&query, &["y_axis"], options.round_digits)?;
+    let query: String = with_timestamps(&query, options.start_date.as_deref(), options.seasonal.is_some(), resolution)?;
+    let query: String = if options.seasonal.is_some() {
+        query
+    } else if options.pivot {
+        if options.order_by.is_some() {
+            return Err("Custom ordering is not supported on pivoted results".to_string());
+        }
+        let series: Vec<String> = fetch_pivot_series(&db_path, &query, "asset", vec![Value::from(year)])?;
+        build_pivot_query(&query, "asset", &["y_axis"], &RESOLUTION_PIVOT_INDEX, &series)?
+    } else {
+        with_resolution_order(&query, options.order_by.as_deref())?
+    };
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, query.clone(), vec![Value::from(year)])?;
+
+    let mut metadata: HashMap<String, String> = debug_sql_metadata(&query, options.include_sql);
+    metadata.insert(RESOLUTION_METADATA_KEY.to_string(), resolution.to_string());
+    return serialize_recordbatch_with_metadata(res.0, res.1, metadata);
+
+}
+
+// headline price per carrier over time: in each time step the production price of the assets producing the carrier,
+// weighted by what each of them produced (flat average in steps without production), then aggregated over
+// periods of the automatic resolution; `carrier` "all" returns one series per carrier
+#[tauri::command]
+pub fn get_system_price(db_path: String, year: u32, carrier: String) -> Result<Response, String> {
+    let resolution: u32 = resolve_resolution(&db_path, year, AUTO_RESOLUTION)?;
+    let (query, args): (String, Vec<Value>) = build_system_price_query(&db_path, year, carrier, resolution)?;
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, query, args)?;
+
+    let mut metadata: HashMap<String, String> = HashMap::new();
+    metadata.insert(RESOLUTION_METADATA_KEY.to_string(), resolution.to_string());
+    return serialize_recordbatch_with_metadata(res.0, res.1, metadata);
+}
+
+// the system price query of `get_system_price` with its parameters, shared with the analysis bundle
+pub fn build_system_price_query(db_path: &str, year: u32, carrier: String, resolution: u32) -> Result<(String, Vec<Value>), String> {
+    let price_sources: Vec<String> = production_price_sources(db_path)?;
+
+    let (carrier_filter, mut args): (&str, Vec<Value>) = if carrier == "all" {
+        ("", vec![])
+    } else {
+        ("WHERE f.carrier = ?", vec![Value::from(carrier)])
+    };
+    args.push(Value::from(year));
+
+    let sql: String = build_resolution_query(
+        "system_price_table",
+        "dual_value",
+        &["carrier"],
+        "avg",
+        &resolution.to_string(),
+        false,
+        None,
+        "year",
+    )?.trim_end_matches(';').trim_end().to_string();
+    let query: String = SYSTEM_PRICE_SQL
+        .replace("{price_source}", &price_sources.join("\n        UNION ALL\n        "))
+        .replace("{carrier_filter}", carrier_filter)
+        .replace("{resolution_query}", &sql);
+
+    Ok((query, args))
+}
+
+// the price-setting asset of each resolution period: the asset with the highest production dual in the period,
+// ties going to the first asset by name. Periods are counted in hours from the start of each representative period,
+// `carrier` "all" gives the marginal asset per carrier
+#[tauri::command]
+pub fn get_marginal_asset(db_path: String, year: u32, carrier: String, resolution: u32) -> Result<Response, String> {
+    let resolution: u32 = resolve_resolution(&db_path, year, resolution)?;
+    let price_sources: Vec<String> = production_price_sources(&db_path)?;
+
+    let query: String = MARGINAL_ASSET_SQL.replace("{price_source}", &price_sources.join("\n        UNION ALL\n        "));
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, query, vec![Value::from(year), Value::from(carrier), Value::from(resolution)])?;
+
+    let mut metadata: HashMap<String, String> = HashMap::new();
+    metadata.insert(RESOLUTION_METADATA_KEY.to_string(), resolution.to_string());
+    return serialize_recordbatch_with_metadata(res.0, res.1, metadata);
+}
+
+// the production price duals of every asset, from each method table present in the database
+fn production_price_sources(db_path: &str) -> Result<Vec<String>, String> {
+    let mut price_sources: Vec<String> = Vec::new();
+    for (table, dual_col) in PRODUCTION_DUAL_COLUMNS {
+        if check_table_exists(db_path.to_string(), table)? && check_column_in_table(db_path.to_string(), table, dual_col)? {
+            price_sources.push(format!(
+                "SELECT asset, year, rep_period, time_block_start, time_block_end, {} AS dual_value FROM {}",
+                dual_col, table
+            ));
+        }
+    }
+    if price_sources.is_empty() {
+        return Err("No production price duals in this database".to_string());
+    }
+
+    Ok(price_sources)
+}
+
+// average production price per carrier weighted by the quantity consumed in each hour (consumer perspective),
+// next to the flat time average; both weighted by the representative periods, `carrier` "all" keeps every carrier
+#[tauri::command]
+pub fn get_demand_weighted_price(db_path: String, year: u32, carrier: String) -> Result<Response, String> {
+    let price_sources: Vec<String> = production_price_sources(&db_path)?;
+
+    let query: String = DEMAND_WEIGHTED_PRICE_SQL.replace("{price_source}", &price_sources.join("\n        UNION ALL\n        "));
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, query, vec![Value::from(year), Value::from(carrier)])?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// spread of the production price per carrier over the year: the standard deviation of the hourly prices (averaged
+// over the producers) around their mean, both weighted by the represented hours, and the coefficient of variation
+// (standard deviation over the absolute mean, NULL for a zero mean); `carrier` "all" keeps every carrier
+#[tauri::command]
+pub fn get_price_volatility(db_path: String, year: u32, carrier: String) -> Result<Response, String> {
+    let price_sources: Vec<String> = production_price_sources(&db_path)?;
+
+    let query: String = PRICE_VOLATILITY_SQL.replace("{price_source}", &price_sources.join("\n        UNION ALL\n        "));
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, query, vec![Value::from(year), Value::from(carrier)])?;
 
     return serialize_recordbatch(res.0, res.1);
+}
+
+// why the production price of an asset comes out empty: per asset in the dual tables, its rows for the year, those
+// left by the asset type filter, those also left by the carrier filter and the non-null duals among the latter
+#[tauri::command]
+pub fn diagnose_production_price(db_path: String, year: u32, carrier: String) -> Result<Response, String> {
+    let price_sources: Vec<String> = production_price_sources(&db_path)?;
 
+    let query: String = PRODUCTION_PRICE_DIAGNOSIS_SQL
+        .replace("{price_source}", &price_sources.join("\n        UNION ALL\n        "))
+        .replace("{carrier_assets}", INFER_CARRIER_SQL_FROM_OUTGOING_FLOWS);
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, query, vec![Value::from(year), Value::from(carrier)])?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// how far the compact production price formulation diverges from the simple one: per asset and hour covered by
+// both constraint tables the compact minus the simple dual, averaged over periods of the automatic resolution;
+// `carrier` "all" keeps every asset
+#[tauri::command]
+pub fn get_price_method_diff(db_path: String, year: u32, carrier: String) -> Result<Response, String> {
+    for (table, dual_col) in PRODUCTION_DUAL_COLUMNS {
+        if !check_table_exists(db_path.clone(), table)? {
+            return Err(format!("Comparing the price methods needs table '{}', this database has no such table", table));
+        }
+        if !check_column_in_table(db_path.clone(), table, dual_col)? {
+            return Err(format!("Comparing the price methods needs column '{}' in table '{}'", dual_col, table));
+        }
+    }
+    let resolution: u32 = resolve_resolution(&db_path, year, AUTO_RESOLUTION)?;
+
+    let (carrier_filter, mut args): (&str, Vec<Value>) = if carrier == "all" {
+        ("", vec![])
+    } else {
+        ("WHERE s.asset IN (SELECT from_asset FROM flow WHERE carrier = ?)", vec![Value::from(carrier)])
+    };
+    args.push(Value::from(year));
+
+    let sql: String = build_resolution_query(
+        "price_diff_table",
+        "dual_value",
+        &["asset"],
+        "avg",
+        &resolution.to_string(),
+        false,
+        None,
+        "year",
+    )?.trim_end_matches(';').trim_end().to_string();
+    let query: String = PRICE_METHOD_DIFF_SQL
+        .replace("{carrier_filter}", carrier_filter)
+        .replace("{resolution_query}", &sql);
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, query, args)?;
+
+    let mut metadata: HashMap<String, String> = HashMap::new();
+    metadata.insert(RESOLUTION_METADATA_KEY.to_string(), resolution.to_string());
+    return serialize_recordbatch_with_metadata(res.0, res.1, metadata);
 }
 
 // --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{setup_test_db, setup_tulipa_db, response_to_batches, column_f64, column_string};
+    use std::collections::BTreeSet;
+
+    // the price is high (100) in the hour with low demand (1) and low (10) in the hour with high demand (9)
+    const DEMAND_PRICE_FIXTURE_SQL: &str = "
+        CREATE TABLE asset(asset VARCHAR, type VARCHAR);
+        INSERT INTO asset VALUES ('ccgt', 'producer'), ('demand', 'consumer');
+        CREATE TABLE flow(from_asset VARCHAR, to_asset VARCHAR, carrier VARCHAR);
+        INSERT INTO flow VALUES ('ccgt', 'demand', 'electricity');
+        CREATE TABLE cons_capacity_outgoing_simple_method(asset VARCHAR, year INTEGER, rep_period INTEGER,
+            time_block_start INTEGER, time_block_end INTEGER, dual_max_output_flows_limit_simple_method DOUBLE);
+        INSERT INTO cons_capacity_outgoing_simple_method VALUES ('ccgt', 2030, 1, 1, 1, 100), ('ccgt', 2030, 1, 2, 2, 10);
+        CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, solution DOUBLE);
+        INSERT INTO var_flow VALUES ('ccgt', 'demand', 2030, 1, 1, 1, 1), ('ccgt', 'demand', 2030, 1, 2, 2, 9);
+        CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+        INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 1);
+        CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+        INSERT INTO rep_periods_data VALUES (2030, 1, 2, 1);
+    ";
+
+    #[test]
+    fn get_demand_weighted_price_test() {
+        let db_path: String = setup_test_db("demand_weighted_price", DEMAND_PRICE_FIXTURE_SQL);
+        let batches = response_to_batches(get_demand_weighted_price(db_path, 2030, "all".to_string()).unwrap());
+
+        assert_eq!(column_string(&batches, "carrier"), vec![Some("electricity".to_string())]);
+        // (100 * 1 + 10 * 9) / 10, against (100 + 10) / 2
+        assert_eq!(column_f64(&batches, "demand_weighted_price"), vec![Some(19.0)]);
+        assert_eq!(column_f64(&batches, "flat_average_price"), vec![Some(55.0)]);
+    }
+
+    #[test]
+    fn get_price_volatility_flat_test() {
+        let fixture: String = DEMAND_PRICE_FIXTURE_SQL.to_string() + "UPDATE cons_capacity_outgoing_simple_method SET dual_max_output_flows_limit_simple_method = 50;";
+        let db_path: String = setup_test_db("price_volatility_flat", &fixture);
+        let batches = response_to_batches(get_price_volatility(db_path, 2030, "electricity".to_string()).unwrap());
+
+        assert_eq!(column_f64(&batches, "mean_price"), vec![Some(50.0)]);
+        assert!(column_f64(&batches, "stddev")[0].unwrap().abs() < 1e-9);
+        assert!(column_f64(&batches, "coefficient_of_variation")[0].unwrap().abs() < 1e-9);
+    }
+
+    #[test]
+    fn get_price_volatility_spiky_test() {
+        // one hour at 100 and one at 10
+        let db_path: String = setup_test_db("price_volatility_spiky", DEMAND_PRICE_FIXTURE_SQL);
+        let batches = response_to_batches(get_price_volatility(db_path, 2030, "all".to_string()).unwrap());
+
+        assert_eq!(column_string(&batches, "carrier"), vec![Some("electricity".to_string())]);
+        assert_eq!(column_f64(&batches, "mean_price"), vec![Some(55.0)]);
+        assert_eq!(column_f64(&batches, "stddev"), vec![Some(45.0)]);
+        let cv: f64 = column_f64(&batches, "coefficient_of_variation")[0].unwrap();
+        assert!((cv - 45.0 / 55.0).abs() < 1e-9, "unexpected coefficient of variation: {}", cv);
+    }
+
+    #[test]
+    fn get_system_price_test() {
+        // two producers of one carrier over two hours, 'ccgt' priced 10 then 20 and 'ocgt' 30 then 40
+        let db_path: String = setup_test_db("system_price", "
+            CREATE TABLE flow(from_asset VARCHAR, to_asset VARCHAR, carrier VARCHAR);
+            INSERT INTO flow VALUES ('ccgt', 'demand', 'electricity'), ('ocgt', 'demand', 'electricity');
+            CREATE TABLE cons_capacity_outgoing_simple_method(asset VARCHAR, year INTEGER, rep_period INTEGER,
+                time_block_start INTEGER, time_block_end INTEGER, dual_max_output_flows_limit_simple_method DOUBLE);
+            INSERT INTO cons_capacity_outgoing_simple_method VALUES
+                ('ccgt', 2030, 1, 1, 1, 10), ('ccgt', 2030, 1, 2, 2, 20), ('ocgt', 2030, 1, 1, 1, 30), ('ocgt', 2030, 1, 2, 2, 40);
+            CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, solution DOUBLE);
+            INSERT INTO var_flow VALUES
+                ('ccgt', 'demand', 2030, 1, 1, 1, 1), ('ccgt', 'demand', 2030, 1, 2, 2, 3), ('ocgt', 'demand', 2030, 1, 1, 1, 3), ('ocgt', 'demand', 2030, 1, 2, 2, 2);
+            CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+            INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 1);
+            CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+            INSERT INTO rep_periods_data VALUES (2030, 1, 2, 1);
+        ");
+        let batches = response_to_batches(get_system_price(db_path, 2030, "electricity".to_string()).unwrap());
+
+        assert_eq!(column_string(&batches, "carrier"), vec![Some("electricity".to_string()), Some("electricity".to_string())]);
+        // (10 * 1 + 30 * 3) / 4 and (20 * 3 + 40 * 2) / 5
+        assert_eq!(column_f64(&batches, "y_axis"), vec![Some(25.0), Some(28.0)]);
+    }
+
+    #[test]
+    fn get_marginal_asset_test() {
+        // 'ccgt' sets the price in the first two hours, 'ocgt' (40) in the last two; 'peaker' ties 'ocgt' in hour 4
+        let db_path: String = setup_test_db("marginal_asset", "
+            CREATE TABLE flow(from_asset VARCHAR, to_asset VARCHAR, carrier VARCHAR);
+            INSERT INTO flow VALUES ('ccgt', 'demand', 'electricity'), ('ocgt', 'demand', 'electricity'), ('peaker', 'demand', 'electricity');
+            CREATE TABLE cons_capacity_outgoing_simple_method(asset VARCHAR, year INTEGER, rep_period INTEGER,
+                time_block_start INTEGER, time_block_end INTEGER, dual_max_output_flows_limit_simple_method DOUBLE);
+            INSERT INTO cons_capacity_outgoing_simple_method VALUES
+                ('ccgt', 2030, 1, 1, 2, 30), ('ccgt', 2030, 1, 3, 4, 10),
+                ('ocgt', 2030, 1, 1, 2, 20), ('ocgt', 2030, 1, 3, 4, 40),
+                ('peaker', 2030, 1, 4, 4, 40);
+            CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+            INSERT INTO rep_periods_data VALUES (2030, 1, 4, 1);
+        ");
+        let batches = response_to_batches(get_marginal_asset(db_path.clone(), 2030, "electricity".to_string(), 2).unwrap());
+
+        assert_eq!(column_string(&batches, "asset"), vec![Some("ccgt".to_string()), Some("ocgt".to_string())]);
+        assert_eq!(column_f64(&batches, "dual_value"), vec![Some(30.0), Some(40.0)]);
+        assert_eq!(column_f64(&batches, "period_start"), vec![Some(0.0), Some(2.0)]);
+
+        // hourly, 'ocgt' and 'peaker' tie at 40 in hour 4 and the name decides
+        let hourly = response_to_batches(get_marginal_asset(db_path, 2030, "all".to_string(), 1).unwrap());
+        assert_eq!(column_string(&hourly, "asset"), vec![
+            Some("ccgt".to_string()), Some("ccgt".to_string()), Some("ocgt".to_string()), Some("ocgt".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn get_production_price_resolution_asset_aggregation_test() {
+        // three gas assets grouped together, 'peaker' has an extreme price
+        let db_path: String = setup_test_db("production_price_asset_aggregation", "
+            CREATE TABLE asset(asset VARCHAR, type VARCHAR);
+            INSERT INTO asset VALUES ('ccgt', 'producer'), ('ocgt', 'producer'), ('peaker', 'producer');
+            CREATE TABLE category(id INTEGER, name VARCHAR, parent_id INTEGER, level INTEGER);
+            INSERT INTO category VALUES (1, 'technology', NULL, 1), (2, 'gas', 1, 0);
+            CREATE TABLE asset_category(asset VARCHAR, root_id INTEGER, leaf_id INTEGER);
+            INSERT INTO asset_category VALUES ('ccgt', 1, 2), ('ocgt', 1, 2), ('peaker', 1, 2);
+            CREATE TABLE cons_capacity_outgoing_simple_method(asset VARCHAR, year INTEGER, rep_period INTEGER,
+                time_block_start INTEGER, time_block_end INTEGER, dual_max_output_flows_limit_simple_method DOUBLE);
+            INSERT INTO cons_capacity_outgoing_simple_method VALUES
+                ('ccgt', 2030, 1, 1, 2, 10), ('ocgt', 2030, 1, 1, 1, 12), ('ocgt', 2030, 1, 2, 2, 14), ('peaker', 2030, 1, 1, 2, 1000);
+            CREATE TABLE cons_capacity_outgoing_compact_method(asset VARCHAR, year INTEGER, rep_period INTEGER,
+                time_block_start INTEGER, time_block_end INTEGER, dual_max_output_flows_limit_compact_method DOUBLE);
+            CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+            INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 1);
+            CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+            INSERT INTO rep_periods_data VALUES (2030, 1, 2, 1);
+        ");
+        let prices = |asset_aggregation: &str| -> Vec<f64> {
+            let batches = response_to_batches(get_production_price_resolution(
+                db_path.clone(), 2030, 1, "all".to_string(), HashMap::new(), vec![2], true,
+                Some(ResolutionOptions { asset_aggregation: Some(asset_aggregation.to_string()), ..Default::default() }),
+            ).unwrap());
+            assert!(column_string(&batches, "asset").iter().all(|a| a.as_deref() == Some("gas")));
+            column_f64(&batches, "y_axis").into_iter().flatten().collect()
+        };
+
+        // hour 1: 10, 12, 1000 and hour 2: 10, 14, 1000
+        assert_eq!(prices("median"), vec![12.0, 14.0]);
+        let mean: Vec<f64> = prices("mean");
+        assert!((mean[0] - 1022.0 / 3.0).abs() < 1e-9 && (mean[1] - 1024.0 / 3.0).abs() < 1e-9, "{:?}", mean);
+
+        let res = get_production_price_resolution(
+            db_path, 2030, 1, "all".to_string(), HashMap::new(), vec![2], true,
+            Some(ResolutionOptions { asset_aggregation: Some("mode".to_string()), ..Default::default() }),
+        );
+        assert!(res.err().unwrap().contains("Invalid cross-asset aggregation 'mode'"));
+    }
+
+    #[test]
+    fn get_production_price_resolution_max_series_test() {
+        let db_path: String = setup_tulipa_db("production_price_max_series", "");
+        let price = |max_series: Option<u32>| -> Result<Response, String> {
+            get_production_price_resolution(
+                db_path.clone(), 2030, 1, "all".to_string(), HashMap::new(), vec![], false,
+                Some(ResolutionOptions { max_series, ..Default::default() }),
+            )
+        };
+        let series: usize = column_string(&response_to_batches(price(None).unwrap()), "asset").into_iter()
+            .collect::<BTreeSet<Option<String>>>()
+            .len();
+
+        assert!(series > 1);
+        assert!(price(Some(series as u32)).is_ok());
+        let err: String = price(Some(1)).err().unwrap();
+        assert_eq!(err, format!("Breakdown produced {} series, exceeds cap 1", series));
+    }
+
+    #[test]
+    fn get_production_price_resolution_zero_epsilon_test() {
+        // solver noise on the first hour of 'solar'
+        let db_path: String = setup_tulipa_db("production_price_zero_epsilon", "
+            UPDATE cons_capacity_outgoing_simple_method SET dual_max_output_flows_limit_simple_method = 1e-14
+            WHERE asset = 'solar' AND time_block_start = 1;
+        ");
+        let solar_prices = |zero_epsilon: Option<f64>| -> Vec<Option<f64>> {
+            let batches = response_to_batches(get_production_price_resolution(
+                db_path.clone(), 2030, 1, "all".to_string(), HashMap::new(), vec![], false,
+                Some(ResolutionOptions { zero_epsilon, ..Default::default() }),
+            ).unwrap());
+            column_string(&batches, "asset").into_iter()
+                .zip(column_f64(&batches, "y_axis"))
+                .filter(|(asset, _)| asset.as_deref() == Some("solar"))
+                .map(|(_, price)| price)
+                .collect()
+        };
+
+        assert_eq!(solar_prices(None), vec![Some(1e-14), Some(10.0)]);
+        assert_eq!(solar_prices(Some(1e-9)), vec![Some(0.0), Some(10.0)]);
+    }
+
+    #[test]
+    fn diagnose_production_price_carrier_test() {
+        // 'hub' is dropped by the type filter, 'ccgt' only produces electricity and has a missing dual
+        let db_path: String = setup_test_db("diagnose_production_price", "
+            CREATE TABLE asset(asset VARCHAR, type VARCHAR);
+            INSERT INTO asset VALUES ('ccgt', 'producer'), ('hub', 'hub');
+            CREATE TABLE flow(from_asset VARCHAR, to_asset VARCHAR, carrier VARCHAR);
+            INSERT INTO flow VALUES ('ccgt', 'hub', 'electricity');
+            CREATE TABLE cons_capacity_outgoing_simple_method(asset VARCHAR, year INTEGER, rep_period INTEGER,
+                time_block_start INTEGER, time_block_end INTEGER, dual_max_output_flows_limit_simple_method DOUBLE);
+            INSERT INTO cons_capacity_outgoing_simple_method VALUES
+                ('ccgt', 2030, 1, 1, 1, 10), ('ccgt', 2030, 1, 2, 2, NULL), ('hub', 2030, 1, 1, 2, 5), ('ccgt', 2050, 1, 1, 2, 10);
+        ");
+        let batches = response_to_batches(diagnose_production_price(db_path.clone(), 2030, "gas".to_string()).unwrap());
+
+        assert_eq!(column_string(&batches, "asset"), vec![Some("ccgt".to_string()), Some("hub".to_string())]);
+        assert_eq!(column_f64(&batches, "constraint_rows"), vec![Some(2.0), Some(1.0)]);
+        assert_eq!(column_f64(&batches, "type_filtered_rows"), vec![Some(2.0), Some(0.0)]);
+        assert_eq!(column_f64(&batches, "carrier_filtered_rows"), vec![Some(0.0), Some(0.0)]);
+        assert_eq!(column_f64(&batches, "non_null_duals"), vec![Some(0.0), Some(0.0)]);
+
+        let electricity = response_to_batches(diagnose_production_price(db_path, 2030, "electricity".to_string()).unwrap());
+        assert_eq!(column_f64(&electricity, "carrier_filtered_rows"), vec![Some(2.0), Some(0.0)]);
+        assert_eq!(column_f64(&electricity, "non_null_duals"), vec![Some(1.0), Some(0.0)]);
+    }
+
+    #[test]
+    fn get_demand_weighted_price_no_demand_test() {
+        let db_path: String = setup_test_db("demand_weighted_price_no_demand", &(DEMAND_PRICE_FIXTURE_SQL.to_string() + "DELETE FROM var_flow;"));
+        let batches = response_to_batches(get_demand_weighted_price(db_path, 2030, "electricity".to_string()).unwrap());
+
+        assert_eq!(column_f64(&batches, "demand_weighted_price"), vec![None]);
+        assert_eq!(column_f64(&batches, "flat_average_price"), vec![Some(55.0)]);
+    }
+
+    #[test]
+    fn get_price_method_diff_test() {
+        // the compact method prices 'ccgt' 16 over both hours where the simple one has 10 then 20,
+        // 'ocgt' only has a simple dual
+        let db_path: String = setup_test_db("price_method_diff", "
+            CREATE TABLE flow(from_asset VARCHAR, to_asset VARCHAR, carrier VARCHAR);
+            INSERT INTO flow VALUES ('ccgt', 'demand', 'electricity'), ('ocgt', 'demand', 'electricity');
+            CREATE TABLE cons_capacity_outgoing_simple_method(asset VARCHAR, year INTEGER, rep_period INTEGER,
+                time_block_start INTEGER, time_block_end INTEGER, dual_max_output_flows_limit_simple_method DOUBLE);
+            INSERT INTO cons_capacity_outgoing_simple_method VALUES
+                ('ccgt', 2030, 1, 1, 1, 10), ('ccgt', 2030, 1, 2, 2, 20), ('ocgt', 2030, 1, 1, 2, 30);
+            CREATE TABLE cons_capacity_outgoing_compact_method(asset VARCHAR, year INTEGER, rep_period INTEGER,
+                time_block_start INTEGER, time_block_end INTEGER, dual_max_output_flows_limit_compact_method DOUBLE);
+            INSERT INTO cons_capacity_outgoing_compact_method VALUES ('ccgt', 2030, 1, 1, 2, 16);
+            CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+            INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 1);
+            CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+            INSERT INTO rep_periods_data VALUES (2030, 1, 2, 1);
+        ");
+        let batches = response_to_batches(get_price_method_diff(db_path.clone(), 2030, "electricity".to_string()).unwrap());
+
+        assert_eq!(column_string(&batches, "asset"), vec![Some("ccgt".to_string()), Some("ccgt".to_string())]);
+        assert_eq!(column_f64(&batches, "y_axis"), vec![Some(6.0), Some(-4.0)]);
+
+        let gas = response_to_batches(get_price_method_diff(db_path, 2030, "gas".to_string()).unwrap());
+        assert!(column_f64(&gas, "y_axis").is_empty());
+    }
+
+    #[test]
+    fn get_price_method_diff_missing_table_test() {
+        let db_path: String = setup_test_db("price_method_diff_missing_table", "
+            CREATE TABLE cons_capacity_outgoing_simple_method(asset VARCHAR, year INTEGER, rep_period INTEGER,
+                time_block_start INTEGER, time_block_end INTEGER, dual_max_output_flows_limit_simple_method DOUBLE);
+        ");
+        let res = get_price_method_diff(db_path, 2030, "all".to_string());
+
+        assert_eq!(res.err(), Some("Comparing the price methods needs table 'cons_capacity_outgoing_compact_method', this database has no such table".to_string()));
+    }
+
+    #[test]
+    fn get_system_price_tulipa_fixture_test() {
+        let db_path: String = setup_tulipa_db("system_price_tulipa_fixture", "");
+        let batches = response_to_batches(get_system_price(db_path, 2030, "electricity".to_string()).unwrap());
+
+        // (10 * 4 + 50 * 1 + 30 * 0) / 5 and (10 * 2 + 65 * 2 + 30 * 2) / 6, 'gas_well' only sets the gas price
+        assert_eq!(column_f64(&batches, "y_axis"), vec![Some(18.0), Some(35.0)]);
+    }
+}
 
 // --- QUERIES ---
+const TIME_BLOCK_COLUMNS: (&str, &str) = ("time_block_start", "time_block_end");
+
     const PRODUCTION_DATA_SIMPLE_SQL: &str = "
         SELECT
             CASE 
@@ -178,7 +655,7 @@ pub fn get_production_price_resolution(
             simple.rep_period,
             simple.time_block_start,
             simple.time_block_end,
-            dual_value
+            dual_value{per_asset_group_by}
         {breakdown_group_by},
         CASE 
         {breakdown_case_conditions}
@@ -207,7 +684,7 @@ pub fn get_production_price_resolution(
             compact.rep_period,
             compact.time_block_start,
             compact.time_block_end,
-            dual_value
+            dual_value{per_asset_group_by}
         {breakdown_group_by},
         CASE 
         {breakdown_case_conditions}
@@ -215,6 +692,232 @@ pub fn get_production_price_resolution(
         END
     ";
 
+// (table, dual column) of the production price duals
+const PRODUCTION_DUAL_COLUMNS: [(&str, &str); 2] = [
+    ("cons_capacity_outgoing_simple_method", "dual_max_output_flows_limit_simple_method"),
+    ("cons_capacity_outgoing_compact_method", "dual_max_output_flows_limit_compact_method"),
+];
+
+// prices and consumed quantities are expanded to single time steps to match differing time blocks,
+// time steps are weighted by the rep period weight and resolution
+const DEMAND_WEIGHTED_PRICE_SQL: &str = "
+    WITH price_source AS (
+        {price_source}
+    ),
+    hourly_price AS ( -- average price over the producers of a carrier
+        SELECT f.carrier, p.year, p.rep_period, h.step, AVG(p.dual_value) AS price
+        FROM price_source AS p
+        JOIN (SELECT DISTINCT from_asset, carrier FROM flow) AS f ON f.from_asset = p.asset
+        CROSS JOIN LATERAL generate_series(p.time_block_start, p.time_block_end) AS h(step)
+        WHERE p.year = $1 AND ($2 = 'all' OR f.carrier = $2)
+        GROUP BY f.carrier, p.year, p.rep_period, h.step
+    ),
+    hourly_demand AS ( -- quantity flowing into consumers
+        SELECT f.carrier, vf.year, vf.rep_period, h.step, SUM(vf.solution) AS quantity
+        FROM var_flow AS vf
+        JOIN flow AS f ON f.from_asset = vf.from_asset AND f.to_asset = vf.to_asset
+        JOIN asset AS a ON a.asset = vf.to_asset
+        CROSS JOIN LATERAL generate_series(vf.time_block_start, vf.time_block_end) AS h(step)
+        WHERE a.type = 'consumer' AND vf.year = $1 AND ($2 = 'all' OR f.carrier = $2)
+        GROUP BY f.carrier, vf.year, vf.rep_period, h.step
+    ),
+    step_weights AS (
+        SELECT rpm.year, rpm.rep_period, SUM(rpm.weight) * ANY_VALUE(rpd.resolution) AS weight
+        FROM rep_periods_mapping AS rpm
+        JOIN rep_periods_data AS rpd ON rpd.year = rpm.year AND rpd.rep_period = rpm.rep_period
+        WHERE rpm.year = $1
+        GROUP BY rpm.year, rpm.rep_period
+    )
+    SELECT
+        p.carrier,
+        -- no demand at all gives NULL instead of a division by zero
+        SUM(p.price * COALESCE(d.quantity, 0) * w.weight) / NULLIF(SUM(COALESCE(d.quantity, 0) * w.weight), 0) AS demand_weighted_price,
+        SUM(p.price * w.weight) / NULLIF(SUM(w.weight), 0) AS flat_average_price,
+        SUM(COALESCE(d.quantity, 0) * w.weight) AS total_demand
+    FROM hourly_price AS p
+    LEFT JOIN hourly_demand AS d ON d.carrier = p.carrier AND d.year = p.year AND d.rep_period = p.rep_period AND d.step = p.step
+    JOIN step_weights AS w ON w.year = p.year AND w.rep_period = p.rep_period
+    GROUP BY p.carrier
+    ORDER BY p.carrier;
+";
+
+// hourly prices as in the demand weighted price, the deviations are taken from the weighted mean in a second pass
+const PRICE_VOLATILITY_SQL: &str = "
+    WITH price_source AS (
+        {price_source}
+    ),
+    hourly_price AS ( -- average price over the producers of a carrier
+        SELECT f.carrier, p.year, p.rep_period, h.step, AVG(p.dual_value) AS price
+        FROM price_source AS p
+        JOIN (SELECT DISTINCT from_asset, carrier FROM flow) AS f ON f.from_asset = p.asset
+        CROSS JOIN LATERAL generate_series(p.time_block_start, p.time_block_end) AS h(step)
+        WHERE p.year = $1 AND ($2 = 'all' OR f.carrier = $2)
+        GROUP BY f.carrier, p.year, p.rep_period, h.step
+    ),
+    step_weights AS (
+        SELECT rpm.year, rpm.rep_period, SUM(rpm.weight) * ANY_VALUE(rpd.resolution) AS weight
+        FROM rep_periods_mapping AS rpm
+        JOIN rep_periods_data AS rpd ON rpd.year = rpm.year AND rpd.rep_period = rpm.rep_period
+        WHERE rpm.year = $1
+        GROUP BY rpm.year, rpm.rep_period
+    ),
+    weighted_price AS (
+        SELECT p.carrier, p.price, w.weight
+        FROM hourly_price AS p
+        JOIN step_weights AS w ON w.year = p.year AND w.rep_period = p.rep_period
+    ),
+    mean_price AS (
+        SELECT carrier, SUM(price * weight) / NULLIF(SUM(weight), 0) AS mean_price
+        FROM weighted_price
+        GROUP BY carrier
+    ),
+    volatility AS (
+        SELECT
+            wp.carrier,
+            m.mean_price,
+            SQRT(SUM(wp.weight * POWER(wp.price - m.mean_price, 2)) / NULLIF(SUM(wp.weight), 0)) AS stddev
+        FROM weighted_price AS wp
+        JOIN mean_price AS m ON m.carrier = wp.carrier
+        GROUP BY wp.carrier, m.mean_price
+    )
+    SELECT
+        carrier,
+        mean_price,
+        stddev,
+        stddev / NULLIF(ABS(mean_price), 0) AS coefficient_of_variation
+    FROM volatility
+    ORDER BY carrier;
+";
+
+// the stages follow the production price query: the producing asset types, then the carrier inferred from the
+// outgoing flows ("all" keeps every asset); assets missing from the asset table get a NULL type
+const PRODUCTION_PRICE_DIAGNOSIS_SQL: &str = "
+    WITH price_source AS (
+        {price_source}
+    ),
+    stages AS (
+        SELECT
+            p.asset,
+            a.type,
+            a.type IN ('producer', 'storage', 'conversion') AS passes_type,
+            ($2 = 'all' OR p.asset IN (SELECT asset FROM ({carrier_assets}) WHERE carrier = $2)) AS passes_carrier,
+            p.dual_value
+        FROM price_source AS p
+        LEFT JOIN asset AS a ON a.asset = p.asset
+        WHERE p.year = $1
+    )
+    SELECT
+        asset,
+        ANY_VALUE(type) AS type,
+        COUNT(*) AS constraint_rows,
+        COUNT(*) FILTER (WHERE passes_type) AS type_filtered_rows,
+        COUNT(*) FILTER (WHERE passes_type AND passes_carrier) AS carrier_filtered_rows,
+        COUNT(dual_value) FILTER (WHERE passes_type AND passes_carrier) AS non_null_duals
+    FROM stages
+    GROUP BY asset
+    ORDER BY asset;
+";
+
+// duals expanded to time steps, bucketed into periods of $3 hours within their rep period
+const MARGINAL_ASSET_SQL: &str = "
+    WITH price_source AS (
+        {price_source}
+    ),
+    asset_period AS ( -- highest dual of each asset in each period
+        SELECT
+            f.carrier,
+            p.rep_period,
+            FLOOR((h.step - 1) * rpd.resolution / $3) AS period,
+            p.asset,
+            MAX(p.dual_value) AS dual_value
+        FROM price_source AS p
+        JOIN (SELECT DISTINCT from_asset, carrier FROM flow) AS f ON f.from_asset = p.asset
+        JOIN rep_periods_data AS rpd ON rpd.year = p.year AND rpd.rep_period = p.rep_period
+        CROSS JOIN LATERAL generate_series(p.time_block_start, p.time_block_end) AS h(step)
+        WHERE p.year = $1 AND ($2 = 'all' OR f.carrier = $2)
+        GROUP BY f.carrier, p.rep_period, FLOOR((h.step - 1) * rpd.resolution / $3), p.asset
+    ),
+    ranked AS (
+        SELECT *, ROW_NUMBER() OVER (PARTITION BY carrier, rep_period, period ORDER BY dual_value DESC, asset) AS price_rank
+        FROM asset_period
+    )
+    SELECT
+        carrier,
+        rep_period,
+        CAST(period AS INTEGER) AS period,
+        CAST(period * $3 AS INTEGER) AS period_start,
+        CAST((period + 1) * $3 AS INTEGER) AS period_end,
+        asset,
+        dual_value
+    FROM ranked
+    WHERE price_rank = 1
+    ORDER BY carrier, rep_period, period;
+";
+
+// per-step production price of each carrier weighted by the produced quantity, as the source of the resolution query
+const SYSTEM_PRICE_SQL: &str = "
+    WITH price_source AS (
+        {price_source}
+    ),
+    asset_production AS ( -- quantity each asset produces of a carrier per time step
+        SELECT vf.from_asset AS asset, f.carrier, vf.year, vf.rep_period, h.step, SUM(vf.solution) AS production
+        FROM var_flow AS vf
+        JOIN flow AS f ON f.from_asset = vf.from_asset AND f.to_asset = vf.to_asset
+        CROSS JOIN LATERAL generate_series(vf.time_block_start, vf.time_block_end) AS h(step)
+        GROUP BY vf.from_asset, f.carrier, vf.year, vf.rep_period, h.step
+    ),
+    system_price_table AS (
+        SELECT
+            f.carrier,
+            p.year,
+            p.rep_period,
+            h.step AS time_block_start,
+            h.step AS time_block_end,
+            COALESCE(
+                SUM(p.dual_value * COALESCE(ap.production, 0)) / NULLIF(SUM(COALESCE(ap.production, 0)), 0),
+                AVG(p.dual_value)
+            ) AS dual_value
+        FROM price_source AS p
+        JOIN (SELECT DISTINCT from_asset, carrier FROM flow) AS f ON f.from_asset = p.asset
+        CROSS JOIN LATERAL generate_series(p.time_block_start, p.time_block_end) AS h(step)
+        LEFT JOIN asset_production AS ap ON ap.asset = p.asset AND ap.carrier = f.carrier
+            AND ap.year = p.year AND ap.rep_period = p.rep_period AND ap.step = h.step
+        {carrier_filter}
+        GROUP BY f.carrier, p.year, p.rep_period, h.step
+    )
+    SELECT * FROM (
+        {resolution_query}
+    ) AS subquery
+";
+
+const PRICE_METHOD_DIFF_SQL: &str = "
+    WITH simple_duals AS ( -- hourly duals of each method
+        SELECT s.asset, s.year, s.rep_period, h.step, s.dual_max_output_flows_limit_simple_method AS dual_value
+        FROM cons_capacity_outgoing_simple_method AS s
+        CROSS JOIN LATERAL generate_series(s.time_block_start, s.time_block_end) AS h(step)
+    ),
+    compact_duals AS (
+        SELECT c.asset, c.year, c.rep_period, h.step, c.dual_max_output_flows_limit_compact_method AS dual_value
+        FROM cons_capacity_outgoing_compact_method AS c
+        CROSS JOIN LATERAL generate_series(c.time_block_start, c.time_block_end) AS h(step)
+    ),
+    price_diff_table AS (
+        SELECT
+            s.asset,
+            s.year,
+            s.rep_period,
+            s.step AS time_block_start,
+            s.step AS time_block_end,
+            c.dual_value - s.dual_value AS dual_value
+        FROM simple_duals AS s
+        JOIN compact_duals AS c ON c.asset = s.asset AND c.year = s.year AND c.rep_period = s.rep_period AND c.step = s.step
+        {carrier_filter}
+    )
+    SELECT * FROM (
+        {resolution_query}
+    ) AS subquery
+";
+
 const EMPTY_SQL: &str = "
                 SELECT 
                 CASE 
@@ -0,0 +1,73 @@
+use duckdb::arrow::{array::RecordBatch, datatypes::Schema};
+use duckdb::types::Value;
+use tauri::ipc::Response;
+use crate::duckdb_conn::{run_query_rb, serialize_recordbatch};
+use crate::services::metadata::check_table_exists;
+
+// the input profiles of one asset in one representative period, one row per profile type and timestep exactly as
+// stored (no weighting or resolution), to validate the profiles fed to the model; only the profiles of the assets
+// commissioned in `year` are used in that year
+#[tauri::command]
+pub fn get_rep_period_profile(db_path: String, year: u32, rep_period: u32, asset: String) -> Result<Response, String> {
+    for table in PROFILE_TABLES {
+        if !check_table_exists(db_path.clone(), table)? {
+            return Err(format!("Profiles not available in this database (missing table '{}')", table));
+        }
+    }
+
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(
+        db_path,
+        REP_PERIOD_PROFILE_SQL.to_string(),
+        vec![Value::from(asset), Value::from(year), Value::from(rep_period)],
+    )?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{setup_test_db, response_to_batches, column_f64, column_string};
+
+    // 'wind' has an availability profile in both rep periods of 2030, 'demand' a demand profile in rep period 1;
+    // the 2050 'wind' vintage reuses the profile, and has its own one which also covers 2030
+    const PROFILES_FIXTURE_SQL: &str = "
+        CREATE TABLE assets_profiles(asset VARCHAR, commission_year INTEGER, profile_type VARCHAR, profile_name VARCHAR);
+        INSERT INTO assets_profiles VALUES
+            ('wind', 2030, 'availability', 'wind_nl'), ('demand', 2030, 'demand', 'demand_nl'),
+            ('wind', 2050, 'availability', 'wind_nl'), ('wind', 2050, 'availability', 'wind_nl_2050');
+        CREATE TABLE profiles_rep_periods(profile_name VARCHAR, year INTEGER, rep_period INTEGER, timestep INTEGER, value DOUBLE);
+        INSERT INTO profiles_rep_periods VALUES
+            ('wind_nl', 2030, 2, 1, 0.9), ('wind_nl', 2030, 1, 3, 0.2), ('wind_nl', 2030, 1, 1, 0.5), ('wind_nl', 2030, 1, 2, 0.7),
+            ('wind_nl', 2050, 1, 1, 0.1), ('demand_nl', 2030, 1, 1, 1.0), ('wind_nl_2050', 2030, 1, 1, 0.3);
+    ";
+
+    #[test]
+    fn get_rep_period_profile_test() {
+        let db_path: String = setup_test_db("rep_period_profile", PROFILES_FIXTURE_SQL);
+        let batches = response_to_batches(get_rep_period_profile(db_path, 2030, 1, "wind".to_string()).unwrap());
+
+        assert_eq!(column_string(&batches, "profile_name"), vec![Some("wind_nl".to_string()); 3]);
+        assert_eq!(column_f64(&batches, "timestep"), vec![Some(1.0), Some(2.0), Some(3.0)]);
+        assert_eq!(column_f64(&batches, "value"), vec![Some(0.5), Some(0.7), Some(0.2)]);
+    }
+
+    #[test]
+    fn get_rep_period_profile_missing_tables_test() {
+        let db_path: String = setup_test_db("rep_period_profile_missing_tables", "CREATE TABLE asset(asset VARCHAR);");
+        let res = get_rep_period_profile(db_path, 2030, 1, "wind".to_string());
+        assert_eq!(res.err().unwrap(), "Profiles not available in this database (missing table 'assets_profiles')");
+    }
+}
+
+// --- QUERIES ---
+const PROFILE_TABLES: [&str; 2] = ["assets_profiles", "profiles_rep_periods"];
+
+const REP_PERIOD_PROFILE_SQL: &str = "
+    SELECT ap.asset, ap.profile_type, ap.profile_name, p.year, p.rep_period, p.timestep, p.value
+    FROM assets_profiles AS ap
+    JOIN profiles_rep_periods AS p ON p.profile_name = ap.profile_name AND p.year = ap.commission_year
+    WHERE ap.asset = ? AND p.year = ? AND p.rep_period = ?
+    ORDER BY ap.profile_type, ap.profile_name, p.timestep;
+";
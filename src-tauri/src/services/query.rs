@@ -1,5 +1,7 @@
 use tauri::ipc::Response;
-use super::super::duckdb_conn::{ serialize_recordbatch, run_query_rb };
+use serde::Serialize;
+use duckdb::Row;
+use super::super::duckdb_conn::{ serialize_recordbatch, run_query_rb, run_query_row, describe_query, cancel_queries as cancel_running_queries, QueryColumn };
 use duckdb::arrow::{array::RecordBatch, datatypes::Schema};
 
 // runs arbitrary SQL query
@@ -9,4 +11,192 @@ pub fn run_serialize_query_on_db(db_path: String, q: String) -> Result<Response,
     
     let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, q, Vec::new())?;
     return serialize_recordbatch(res.0, res.1);
-}
\ No newline at end of file
+}
+
+// dry-run of a custom query: returns its output columns, or the parse error, without fetching data
+#[tauri::command]
+pub fn validate_query(db_path: String, q: String) -> Result<Vec<QueryColumn>, String> {
+    println!("Validating custom: {}", q);
+
+    return describe_query(db_path, q);
+}
+
+// output schema of a custom query as JSON (name, data_type, nullable), for typing table components up front;
+// only a single statement is accepted, so probing the schema never runs anything
+#[tauri::command]
+pub fn get_query_schema(db_path: String, q: String) -> Result<Vec<QueryColumn>, String> {
+    return describe_query(db_path, q);
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct QueryEstimate {
+    row_count: i64,
+    column_count: usize,
+}
+
+// size of a custom query's result, counted by the database without transferring the rows,
+// so the UI can warn before fetching a large result
+#[tauri::command]
+pub fn estimate_query_rows(db_path: String, q: String) -> Result<QueryEstimate, String> {
+    let q: &str = q.trim().trim_end_matches(';');
+    let column_count: usize = describe_query(db_path.clone(), q.to_string())?.len();
+    let row_count: i64 = run_query_row(db_path, COUNT_ROWS_SQL.replace("{query}", q), Vec::new(), |row: &Row<'_>| Ok(row.get::<usize, i64>(0)?))?
+        .pop()
+        .unwrap_or(0);
+
+    Ok(QueryEstimate { row_count, column_count })
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct QueryDifference {
+    // 1 or 2, the query whose result has the row and the other one lacks
+    query: i32,
+    // the values of the row as text
+    row: Vec<Option<String>>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct QueryComparison {
+    equal: bool,
+    first_difference: Option<QueryDifference>,
+}
+
+// whether two queries return the same rows, ignoring their order but counting duplicates, e.g. to check a
+// rewritten query against the original; the first differing row is the smallest one only in the first result,
+// otherwise the smallest one only in the second
+#[tauri::command]
+pub fn compare_queries(db_path: String, q1: String, q2: String) -> Result<QueryComparison, String> {
+    let q1: &str = q1.trim().trim_end_matches(';');
+    let q2: &str = q2.trim().trim_end_matches(';');
+    let column_count: usize = describe_query(db_path.clone(), q1.to_string())?.len();
+    let other_column_count: usize = describe_query(db_path.clone(), q2.to_string())?.len();
+    if column_count != other_column_count {
+        return Err(format!("The queries return a different number of columns ({} and {})", column_count, other_column_count));
+    }
+
+    let sql: String = COMPARE_QUERIES_SQL.replace("{q1}", q1).replace("{q2}", q2);
+    let first_difference: Option<QueryDifference> = run_query_row(db_path, sql, Vec::new(), |row: &Row<'_>| {
+        let values: Vec<Option<String>> = (1..=column_count)
+            .map(|i| row.get::<usize, Option<String>>(i))
+            .collect::<Result<_, _>>()?;
+        Ok(QueryDifference { query: row.get::<usize, i32>(0)?, row: values })
+    })?.pop();
+
+    Ok(QueryComparison { equal: first_difference.is_none(), first_difference })
+}
+
+// aborts the statement currently running on a database (e.g. when its chart is closed),
+// a no-op returning false when nothing is running
+#[tauri::command]
+pub fn cancel_queries(db_path: String) -> Result<bool, String> {
+    return cancel_running_queries(&db_path);
+}
+
+// --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::test_utils::setup_test_db;
+
+    const DEMO_FIXTURE_SQL: &str = "
+        CREATE TABLE demoTable(id INTEGER, name VARCHAR);
+        INSERT INTO demoTable VALUES (1, 'Belgium'), (2, 'Netherlands'), (3, 'France'), (4, 'Germany');
+    ";
+
+    #[test]
+    fn get_query_schema_json_test() {
+        let db_path: String = setup_test_db("query_schema_json", DEMO_FIXTURE_SQL);
+        let schema: Vec<QueryColumn> = get_query_schema(db_path, "SELECT id, name, CAST(id AS DOUBLE) AS scaled FROM demoTable;".to_string()).unwrap();
+
+        assert_eq!(serde_json::to_value(schema).unwrap(), json!([
+            { "name": "id", "data_type": "INTEGER", "nullable": true },
+            { "name": "name", "data_type": "VARCHAR", "nullable": true },
+            { "name": "scaled", "data_type": "DOUBLE", "nullable": true },
+        ]));
+    }
+
+    #[test]
+    fn get_query_schema_syntax_err_test() {
+        let db_path: String = setup_test_db("query_schema_syntax_err", DEMO_FIXTURE_SQL);
+        let res = get_query_schema(db_path, "SELCT name FROM demoTable".to_string());
+        assert!(res.is_err() && res.err().unwrap().contains("syntax"));
+    }
+
+    #[test]
+    fn get_query_schema_multiple_statements_test() {
+        let db_path: String = setup_test_db("query_schema_multiple_statements", DEMO_FIXTURE_SQL);
+        let res = get_query_schema(db_path.clone(), "DELETE FROM demoTable; SELECT id FROM demoTable".to_string());
+        assert_eq!(res, Err("Expected a single statement to describe, got 2".to_string()));
+
+        let estimate: QueryEstimate = estimate_query_rows(db_path, "SELECT id FROM demoTable".to_string()).unwrap();
+        assert_eq!(estimate.row_count, 4);
+    }
+
+    #[test]
+    fn estimate_query_rows_test() {
+        let db_path: String = setup_test_db("estimate_query_rows", DEMO_FIXTURE_SQL);
+        let estimate: QueryEstimate = estimate_query_rows(db_path, "SELECT id, name FROM demoTable WHERE id > 1;".to_string()).unwrap();
+
+        assert_eq!(estimate, QueryEstimate { row_count: 3, column_count: 2 });
+    }
+
+    #[test]
+    fn compare_queries_equal_test() {
+        let db_path: String = setup_test_db("compare_queries_equal", DEMO_FIXTURE_SQL);
+        let comparison: QueryComparison = compare_queries(
+            db_path,
+            "SELECT id, name FROM demoTable ORDER BY id;".to_string(),
+            "SELECT id, name FROM demoTable ORDER BY name DESC".to_string(),
+        ).unwrap();
+
+        assert_eq!(comparison, QueryComparison { equal: true, first_difference: None });
+    }
+
+    #[test]
+    fn compare_queries_difference_test() {
+        let db_path: String = setup_test_db("compare_queries_difference", DEMO_FIXTURE_SQL);
+        let comparison: QueryComparison = compare_queries(
+            db_path.clone(),
+            "SELECT id, name FROM demoTable".to_string(),
+            "SELECT id, name FROM demoTable WHERE id <> 3 UNION ALL SELECT 5, 'Spain'".to_string(),
+        ).unwrap();
+
+        assert_eq!(comparison, QueryComparison {
+            equal: false,
+            first_difference: Some(QueryDifference { query: 1, row: vec![Some("3".to_string()), Some("France".to_string())] }),
+        });
+
+        // a duplicated row is a difference as well
+        let duplicated: QueryComparison = compare_queries(
+            db_path.clone(),
+            "SELECT name FROM demoTable".to_string(),
+            "SELECT name FROM demoTable UNION ALL SELECT 'Belgium'".to_string(),
+        ).unwrap();
+        assert_eq!(duplicated.first_difference, Some(QueryDifference { query: 2, row: vec![Some("Belgium".to_string())] }));
+
+        let res = compare_queries(db_path, "SELECT id FROM demoTable".to_string(), "SELECT id, name FROM demoTable".to_string());
+        assert!(res.unwrap_err().contains("different number of columns (1 and 2)"));
+    }
+}
+
+// --- QUERIES ---
+const COUNT_ROWS_SQL: &str = "SELECT COUNT(*) FROM ({query})";
+// EXCEPT ALL keeps the surplus duplicates of a side, the values are cast to text to compare any column types
+const COMPARE_QUERIES_SQL: &str = "
+WITH q1 AS (
+{q1}
+), q2 AS (
+{q2}
+), only_q1 AS (
+  SELECT * FROM q1 EXCEPT ALL SELECT * FROM q2
+), only_q2 AS (
+  SELECT * FROM q2 EXCEPT ALL SELECT * FROM q1
+)
+SELECT * FROM (
+  SELECT 1 AS query, CAST(COLUMNS(*) AS VARCHAR) FROM (SELECT * FROM only_q1 ORDER BY ALL LIMIT 1)
+  UNION ALL
+  SELECT 2 AS query, CAST(COLUMNS(*) AS VARCHAR) FROM (SELECT * FROM only_q2 ORDER BY ALL LIMIT 1)
+) AS differences
+ORDER BY 1
+LIMIT 1";
@@ -1,4 +1,63 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use serde::Deserialize;
+use crate::services::metadata::INFER_CARRIER_SQL_FROM_OUTGOING_FLOWS;
+
+/// Calendar buckets a resolution timeline can be averaged over, see `with_seasonal`.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SeasonMode {
+    /// One row per calendar month (`month` 1-12).
+    Month,
+    /// One row per meteorological season (`season` winter, spring, summer, autumn).
+    Season,
+}
+
+/// How an asset under more than one grouper node is labeled in a breakdown, which happens when the grouper mixes
+/// nodes of different category roots (e.g. a carrier and a region) or a node with one of its ancestors.
+///
+/// Splitting such an asset's values proportionally over its nodes is not supported: the breakdown assigns a single
+/// label per row, so the values would have to be duplicated and weighted in every analysis query.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BreakdownOverlap {
+    /// The first matching node in grouper order, the asset is counted once.
+    #[default]
+    First,
+    /// A separate `AMBIGUOUS_BREAKDOWN_GROUP` group instead of any of the nodes.
+    Ambiguous,
+}
+
+/// Optional knobs of the price resolution commands (production, storage and transportation prices), applied to the
+/// built resolution query. Every knob is off when missing, so the frontend only sends the ones it uses.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ResolutionOptions {
+    /// Decimals to round `y_axis` to, see `with_rounding`.
+    pub round_digits: Option<u32>,
+    /// One of `RESOLUTION_ORDER_BY`, see `with_resolution_order`; not supported together with `pivot`.
+    pub order_by: Option<String>,
+    /// Attaches the generated SQL as response metadata, see `debug_sql_metadata`.
+    pub include_sql: bool,
+    /// One column per series instead of one row per series and period, see `build_pivot_query`.
+    pub pivot: bool,
+    /// Averages the timeline per calendar month or season, see `with_seasonal`.
+    pub seasonal: Option<SeasonMode>,
+    /// Clamps negative prices to zero, see `with_clamped_negatives`.
+    pub clamp_negative: bool,
+    /// Decimals up to which consecutive values count as equal when merging them into one block.
+    pub merge_digits: Option<u32>,
+    /// "mean" or "median" over the assets of a group, see `with_cross_asset_aggregation` (production and storage
+    /// prices only).
+    pub asset_aggregation: Option<String>,
+    /// One of `BOTH_COMBINATIONS`, see `build_both_resolutions_sql` (storage prices of both types only).
+    pub combine: Option<String>,
+    /// The date of hour 0 to return the timeline as timestamps, see `with_timestamps`.
+    pub start_date: Option<String>,
+    /// Errors instead of returning more series, see `check_series_cap` (production and storage prices only).
+    pub max_series: Option<u32>,
+    /// Duals with a smaller magnitude are zeroed, see `with_zeroed_values`.
+    pub zero_epsilon: Option<f64>,
+}
 
 /// Builds a SQL query to calculate resolution-based values for a given source table.
 /// This version supports two resolution methods: representative periods and clustered periods.
@@ -11,10 +70,14 @@ use std::collections::HashMap;
 /// * `agg` - Aggregation method (e.g., "avg", "sum").
 /// * `resolution` - Resolution period length (e.g., 24 for daily).
 /// * `clustered` - Whether to use the clustered resolution strategy.
+/// * `merge_digits` - Decimal digits consecutive blocks are compared at when merging them, exact by default.
+/// * `year_col` - The year column of the source table, filtered on the bound year and reported as `milestone_year`.
+///   All current sources (`cons_capacity_outgoing_*`, `cons_balance_storage_*`, `cons_transport_flow_limit_simple_method`
+///   and `var_flow`) store the milestone year in `year`; tables keyed by e.g. `commission_year` pass that column instead.
 ///
 /// # Returns
 ///
-/// A `String` containing the generated SQL query.
+/// A `String` containing the generated SQL query, or an error for a table or column name failing `validate_ident`.
 pub fn build_resolution_query(
     source_table: &str,
     value_col: &str,
@@ -22,15 +85,20 @@ pub fn build_resolution_query(
     agg: &str,
     resolution: &str,
     clustered: bool,
-) -> String {
+    merge_digits: Option<u32>,
+    year_col: &str,
+) -> Result<String, String> {
+    validate_idents(&[source_table, value_col, year_col])?;
+    validate_idents(group_cols)?;
+
     let combine_sql: String;
     if clustered {
         combine_sql = "WITH ".to_string()
             + CLUSTURED_YEAR_RESOLUTIONS_SQL
-            + &LAST_PART_SQL.replace("{final}", "final_clustered");
+            + &build_last_part_sql("final_clustered", merge_digits);
     } else {
-        combine_sql = REP_PERIOD_RESOLUTION_SQL.to_string()
-            + &LAST_PART_SQL.replace("{final}", "final_rep_periods");
+        combine_sql = build_rep_period_resolution_sql(merge_digits)
+            + &build_last_part_sql("final_rep_periods", merge_digits);
     }
     let group_cols_sql = group_cols.join(", ");
     let group_cols_comparisons = group_cols
@@ -39,13 +107,14 @@ pub fn build_resolution_query(
         .collect::<Vec<_>>()
         .join(" AND ");
 
-        combine_sql
-            .replace("{group_cols}", &group_cols_sql)
-            .replace("{group_cols_comparisons}", &group_cols_comparisons)
-            .replace("{value_col}", value_col)
-            .replace("{source_table}", source_table)
-            .replace("{agg}", agg)
-            .replace("{period_length}", resolution)
+    Ok(combine_sql
+        .replace("{group_cols}", &group_cols_sql)
+        .replace("{group_cols_comparisons}", &group_cols_comparisons)
+        .replace("{value_col}", value_col)
+        .replace("{source_table}", source_table)
+        .replace("{agg}", agg)
+        .replace("{period_length}", resolution)
+        .replace("{year_col}", year_col))
 }
 
 /// Builds a SQL query that combines both clustered and representative period resolutions.
@@ -58,10 +127,13 @@ pub fn build_resolution_query(
 /// * `group_cols` - A list of column names to group by.
 /// * `agg` - Aggregation method (e.g., "avg", "sum").
 /// * `resolution` - Resolution period length (e.g., 24 for daily).
+/// * `merge_digits` - Decimal digits consecutive blocks are compared at when merging them, exact by default.
+/// * `year_col` - The year column of both source tables, see `build_resolution_query`.
+/// * `combine` - How the values of both tables in the same period are combined, see `build_both_resolutions_sql`.
 /// 
 /// # Returns
 ///
-/// A `String` containing the generated SQL query.
+/// A `String` containing the generated SQL query, or an error for a table or column name failing `validate_ident`.
 pub fn build_resolution_query_both(
     source_table: &str,
     source_table_1: &str,
@@ -69,12 +141,17 @@ pub fn build_resolution_query_both(
     group_cols: &[&str],
     agg: &str,
     resolution: &str,
-    ) -> String {
+    merge_digits: Option<u32>,
+    year_col: &str,
+    combine: Option<&str>,
+    ) -> Result<String, String> {
+  validate_idents(&[source_table, source_table_1, value_col, year_col])?;
+  validate_idents(group_cols)?;
 
-  let combine_sql = REP_PERIOD_RESOLUTION_SQL.to_string()
+  let combine_sql = build_rep_period_resolution_sql(merge_digits)
       + &CLUSTURED_YEAR_RESOLUTIONS_SQL.replace("{source_table}", "{source_table_1}")
-      + BOTH_RESOLUTIONS_SQL
-      + &LAST_PART_SQL.replace("{final}", "final");
+      + &build_both_resolutions_sql(combine)
+      + &build_last_part_sql("final", merge_digits);
   let group_cols_sql = group_cols.join(", ");
   let group_cols_comparisons = group_cols
         .iter()
@@ -82,7 +159,7 @@ pub fn build_resolution_query_both(
         .collect::<Vec<_>>()
         .join(" AND ");
 
-    combine_sql
+    Ok(combine_sql
         .replace("{group_cols}", &group_cols_sql)
         .replace("{group_cols_comparisons}", &group_cols_comparisons)
         .replace("{value_col}", value_col)
@@ -90,6 +167,7 @@ pub fn build_resolution_query_both(
         .replace("{source_table_1}", source_table_1)
         .replace("{agg}", agg)
         .replace("{period_length}", resolution)
+        .replace("{year_col}", year_col))
 }
 
 /// Builds a SQL query for resolution-based aggregation with category-based filters.
@@ -106,7 +184,7 @@ pub fn build_resolution_query_both(
 ///
 /// # Returns
 ///
-/// A `String` representing the SQL query with category-based filters.
+/// A `String` representing the SQL query with category-based filters, or an error for an invalid table or column name.
 pub fn build_resolution_query_with_filters(
     source_table: &str,
     value_col: &str,
@@ -115,8 +193,13 @@ pub fn build_resolution_query_with_filters(
     resolution: &str,
     filters_by_category: &HashMap<i32, Vec<i32>>,
     asset_identifier_column_filtering: String,
-    clustered: bool
-) -> String {
+    clustered: bool,
+    merge_digits: Option<u32>,
+    year_col: &str,
+) -> Result<String, String> {
+    validate_idents(&[source_table, value_col, year_col])?;
+    validate_idents(group_cols)?;
+
     let group_cols_sql = group_cols.join(", ");
     let filter_conditions = build_filter_conditions(filters_by_category, asset_identifier_column_filtering);
 
@@ -131,20 +214,21 @@ pub fn build_resolution_query_with_filters(
     if clustered {
         combine_sql = "WITH ".to_string()
             + CLUSTURED_YEAR_RESOLUTIONS_SQL
-            + &LAST_PART_SQL.replace("{final}", "final_clustered");
+            + &build_last_part_sql("final_clustered", merge_digits);
     } else {
-        combine_sql = REP_PERIOD_RESOLUTION_SQL.to_string()
-            + &LAST_PART_SQL.replace("{final}", "final_rep_periods");
+        combine_sql = build_rep_period_resolution_sql(merge_digits)
+            + &build_last_part_sql("final_rep_periods", merge_digits);
     }
 
-    combine_sql
+    Ok(combine_sql
         .replace("{group_cols}", &group_cols_sql)
         .replace("{group_cols_comparisons}", &group_cols_comparisons)
         .replace("{value_col}", value_col)
         .replace("{source_table}", source_table)
         .replace("{agg}", agg)
         .replace("{period_length}", resolution)
-        .replace("{filter_conditions}", &filter_conditions)
+        .replace("{year_col}", year_col)
+        .replace("{filter_conditions}", &filter_conditions))
 }
 
 pub fn build_resolution_query_with_filters_both(
@@ -156,7 +240,13 @@ pub fn build_resolution_query_with_filters_both(
     resolution: &str,
     filters_by_category: &HashMap<i32, Vec<i32>>,
     asset_identifier_column_filtering: String,
-) -> String {
+    merge_digits: Option<u32>,
+    year_col: &str,
+    combine: Option<&str>,
+) -> Result<String, String> {
+    validate_idents(&[source_table, source_table_1, value_col, year_col])?;
+    validate_idents(group_cols)?;
+
     let group_cols_sql = group_cols.join(", ");
     let filter_conditions = build_filter_conditions(filters_by_category, asset_identifier_column_filtering);
 
@@ -167,12 +257,12 @@ pub fn build_resolution_query_with_filters_both(
         .collect::<Vec<_>>()
         .join(" AND ");
     
-    let combine_sql = REP_PERIOD_RESOLUTION_SQL.to_string()
+    let combine_sql = build_rep_period_resolution_sql(merge_digits)
         + &CLUSTURED_YEAR_RESOLUTIONS_SQL.replace("{source_table}", "{source_table_1}")
-        + BOTH_RESOLUTIONS_SQL
-        + &LAST_PART_SQL.replace("{final}", "final");
+        + &build_both_resolutions_sql(combine)
+        + &build_last_part_sql("final", merge_digits);
 
-    combine_sql
+    Ok(combine_sql
         .replace("{group_cols}", &group_cols_sql)
         .replace("{group_cols_comparisons}", &group_cols_comparisons)
         .replace("{value_col}", value_col)
@@ -180,10 +270,14 @@ pub fn build_resolution_query_with_filters_both(
         .replace("{source_table_1}", source_table_1)
         .replace("{agg}", agg)
         .replace("{period_length}", resolution)
-        .replace("{filter_conditions}", &filter_conditions)
+        .replace("{year_col}", year_col)
+        .replace("{filter_conditions}", &filter_conditions))
 }
 
 /// Builds SQL filter conditions for a map of category filters.
+///
+/// The conditions reference the shared `descendants_{id}` CTEs, so the final query has to be wrapped
+/// with `with_category_descendants` using the same filters.
 /// 
 /// # Arguments
 ///
@@ -204,31 +298,23 @@ pub fn build_filter_conditions(filters_by_category: &HashMap<i32, Vec<i32>>, ass
         if leaf_ids.is_empty() {
             continue;
         }
-        let ids_list: Vec<String> = leaf_ids.iter().map(ToString::to_string).collect();
-        let ids_csv = ids_list.join(", ");
-
-        // Build a recursive CTE to find all descendants of the filtered nodes, including the filtered nodes themselves.
-        let desc_cte = format!(
-            "WITH RECURSIVE sub{0} AS (  
-                SELECT id FROM category WHERE id IN ({1})  
-              UNION ALL  
-                SELECT c.id FROM category c JOIN sub{0} s ON c.parent_id = s.id
-            )",
-            root_id, ids_csv
-        );
+        // All descendants of the filtered nodes, including the filtered nodes themselves.
+        let descendants = leaf_ids.iter()
+            .map(|id| format!("SELECT id FROM descendants_{}", id))
+            .collect::<Vec<_>>()
+            .join(" UNION ALL ");
 
-        // Simply check if the asset's category (ac.leaf_id) is a included in the filtered nodes (sub{0}).
+        // Simply check if the asset's category (ac.leaf_id) is a included in the filtered nodes.
         let condition = format!(
             "EXISTS (
-                {desc_cte}
                 SELECT 1
                 FROM asset_category ac{0}
                 WHERE ac{0}.asset = {asset_column}
                   AND ac{0}.root_id = {0}
-                  AND ac{0}.leaf_id IN (SELECT id FROM sub{0})
+                  AND ac{0}.leaf_id IN ({descendants})
             )",
             root_id,
-            desc_cte = desc_cte.replace("\n", " "),
+            descendants = descendants,
             asset_column = asset_identifier_column
         );
 
@@ -242,6 +328,59 @@ pub fn build_filter_conditions(filters_by_category: &HashMap<i32, Vec<i32>>, ass
     }
 }
 
+/// Builds one recursive `descendants_{id}` CTE per category node, listing the node and all its descendants.
+///
+/// # Arguments
+///
+/// * `node_ids` - The category nodes to compute the descendants of.
+///
+/// # Returns
+///
+/// The comma-separated CTE definitions, without the `WITH RECURSIVE` keyword.
+pub fn build_category_descendants_ctes(node_ids: &BTreeSet<i32>) -> String {
+    node_ids.iter()
+        .map(|node_id| format!(
+            "descendants_{0} AS (
+    SELECT id FROM category WHERE id = {0}
+    UNION ALL
+    SELECT c.id FROM category c
+    JOIN descendants_{0} d ON c.parent_id = d.id
+)",
+            node_id
+        ))
+        .collect::<Vec<_>>()
+        .join(",\n")
+}
+
+/// Wraps a final query in the shared descendant CTEs of all filtered and breakdown nodes,
+/// so every filter and breakdown condition reuses a single recursive descent per node.
+///
+/// # Arguments
+///
+/// * `sql` - The final query, which may reference `descendants_{id}` anywhere (also in nested subqueries).
+/// * `filters_by_category` - The filters used to build the query's filter conditions.
+/// * `grouper` - The breakdown nodes used to build the query's breakdown conditions.
+///
+/// # Returns
+///
+/// The query unchanged when no nodes are involved, otherwise the query selected from below the CTEs.
+pub fn with_category_descendants(sql: &str, filters_by_category: &HashMap<i32, Vec<i32>>, grouper: &[i32]) -> String {
+    let node_ids: BTreeSet<i32> = filters_by_category.values()
+        .flatten()
+        .chain(grouper.iter())
+        .copied()
+        .collect();
+
+    if node_ids.is_empty() {
+        return sql.to_string();
+    }
+
+    format!(
+        "WITH RECURSIVE {}\nSELECT * FROM (\n{}\n) AS shared_descendants_query",
+        build_category_descendants_ctes(&node_ids),
+        sql.trim_end().trim_end_matches(';')
+    )
+}
 
 /// Builds a SQL query for resolution-based aggregation with both category-based filters and breakdown support.
 ///
@@ -259,7 +398,7 @@ pub fn build_filter_conditions(filters_by_category: &HashMap<i32, Vec<i32>>, ass
 ///
 /// # Returns
 ///
-/// A `String` representing the SQL query with both filters and breakdown support.
+/// A `String` representing the SQL query with both filters and breakdown support, or an error for an invalid table or column name.
 pub fn build_resolution_query_with_filters_and_breakdown(
     source_table: &str,
     value_col: &str,
@@ -270,10 +409,14 @@ pub fn build_resolution_query_with_filters_and_breakdown(
     grouper: &[i32],
     asset_identifier_column_filtering: String,
     asset_identifier_column_breakdown: String,
-    clustered: bool
-) -> String {
+    clustered: bool,
+    merge_digits: Option<u32>,
+    year_col: &str,
+) -> Result<String, String> {
     // For breakdown, we group by the breakdown categories, not individual assets
     let breakdown_refs: Vec<&str> = breakdown_cols.iter().map(String::as_str).collect();
+    validate_idents(&[source_table, value_col, year_col])?;
+    validate_idents(&breakdown_refs)?;
     let group_cols_sql = breakdown_refs.join(", ");
 
     // Build individual column comparisons for WHERE clauses
@@ -293,24 +436,25 @@ pub fn build_resolution_query_with_filters_and_breakdown(
      if clustered {
         combine_sql = "WITH ".to_string()
             + CLUSTURED_YEAR_RESOLUTIONS_SQL
-            + &LAST_PART_SQL.replace("{final}", "final_clustered");
+            + &build_last_part_sql("final_clustered", merge_digits);
     } else {
-        combine_sql = REP_PERIOD_RESOLUTION_SQL.to_string()
-            + &LAST_PART_SQL.replace("{final}", "final_rep_periods");
+        combine_sql = build_rep_period_resolution_sql(merge_digits)
+            + &build_last_part_sql("final_rep_periods", merge_digits);
     }
 
-    combine_sql
+    Ok(combine_sql
         .replace("{group_cols}", &group_cols_sql)
         .replace("{group_cols_comparisons}", &group_cols_comparisons)
         .replace("{value_col}", value_col)
         .replace("{source_table}", source_table)
         .replace("{agg}", agg)
         .replace("{period_length}", resolution)
+        .replace("{year_col}", year_col)
         .replace("{filter_conditions}", &filter_conditions)
         .replace("{breakdown_joins}", &breakdown_joins)
         .replace("{breakdown_selects}", &breakdown_selects)
         .replace("{breakdown_case_conditions}", &breakdown_case_conditions)
-        .replace("{breakdown_group_by}", &breakdown_group_by)
+        .replace("{breakdown_group_by}", &breakdown_group_by))
 }
 
 
@@ -325,9 +469,14 @@ pub fn build_resolution_query_with_filters_and_breakdown_both(
     grouper: &[i32],
     asset_identifier_column_filtering: String,
     asset_identifier_column_breakdown: String,
-) -> String {
+    merge_digits: Option<u32>,
+    year_col: &str,
+    combine: Option<&str>,
+) -> Result<String, String> {
     // For breakdown, we group by the breakdown categories, not individual assets
     let breakdown_refs: Vec<&str> = breakdown_cols.iter().map(String::as_str).collect();
+    validate_idents(&[source_table, source_table_1, value_col, year_col])?;
+    validate_idents(&breakdown_refs)?;
     let group_cols_sql = breakdown_refs.join(", ");
 
     // Build individual column comparisons for WHERE clauses
@@ -343,12 +492,12 @@ pub fn build_resolution_query_with_filters_and_breakdown_both(
     let breakdown_case_conditions = build_breakdown_case_conditions(grouper, asset_identifier_column_breakdown);
     let breakdown_group_by = build_breakdown_group_by(grouper);
     
-    let combine_sql = REP_PERIOD_RESOLUTION_SQL.to_string()
+    let combine_sql = build_rep_period_resolution_sql(merge_digits)
         + &CLUSTURED_YEAR_RESOLUTIONS_SQL.replace("{source_table}", "{source_table_1}")
-        + BOTH_RESOLUTIONS_SQL
-        + &LAST_PART_SQL.replace("{final}", "final");
+        + &build_both_resolutions_sql(combine)
+        + &build_last_part_sql("final", merge_digits);
 
-    combine_sql
+    Ok(combine_sql
         .replace("{group_cols}", &group_cols_sql)
         .replace("{group_cols_comparisons}", &group_cols_comparisons)
         .replace("{value_col}", value_col)
@@ -356,11 +505,12 @@ pub fn build_resolution_query_with_filters_and_breakdown_both(
         .replace("{source_table_1}", source_table_1)
         .replace("{agg}", agg)
         .replace("{period_length}", resolution)
+        .replace("{year_col}", year_col)
         .replace("{filter_conditions}", &filter_conditions)
         .replace("{breakdown_joins}", &breakdown_joins)
         .replace("{breakdown_selects}", &breakdown_selects)
         .replace("{breakdown_case_conditions}", &breakdown_case_conditions)
-        .replace("{breakdown_group_by}", &breakdown_group_by)
+        .replace("{breakdown_group_by}", &breakdown_group_by))
 }
 
 /// Builds breakdown column names for SQL GROUP BY clause
@@ -375,6 +525,8 @@ pub fn build_breakdown_columns(grouper: &[i32]) -> Vec<String> {
 
 /// Builds CASE conditions for breakdown categorization
 /// This creates the logic to categorize assets into breakdown groups or 'Other'
+///
+/// Like the filter conditions, these reference the shared `descendants_{id}` CTEs (see `with_category_descendants`).
 /// 
 /// # Arguments
 ///
@@ -384,7 +536,36 @@ pub fn build_breakdown_columns(grouper: &[i32]) -> Vec<String> {
 /// # Returns
 ///
 /// A `String` representing the SQL query the condition to match an asset to the furthest ancestor, if possible
+///
+/// An asset under several grouper nodes goes to the first one in grouper order, see `BreakdownOverlap`.
 pub fn build_breakdown_case_conditions(grouper: &[i32], asset_identifier_column: String) -> String {
+    build_breakdown_case_conditions_with_overlap(grouper, asset_identifier_column, BreakdownOverlap::First)
+}
+
+/// Same as `build_breakdown_case_conditions`, with the policy for assets under several grouper nodes.
+pub fn build_breakdown_case_conditions_with_overlap(grouper: &[i32], asset_identifier_column: String, overlap: BreakdownOverlap) -> String {
+    let conditions: String = breakdown_case_conditions(grouper, &asset_identifier_column, "");
+    let nodes: BTreeSet<i32> = grouper.iter().copied().collect();
+    if overlap == BreakdownOverlap::First || nodes.len() < 2 {
+        return conditions;
+    }
+
+    // counts the grouper nodes the asset is under, before any of the single node conditions
+    let matches: Vec<String> = nodes.iter()
+        .map(|&node_id| format!(
+            "CAST(EXISTS (
+                SELECT 1 FROM asset_category ac_overlap_{0}
+                WHERE ac_overlap_{0}.asset = {1}
+                  AND ac_overlap_{0}.leaf_id IN (SELECT id FROM descendants_{0})
+            ) AS INTEGER)",
+            node_id,
+            asset_identifier_column
+        ))
+        .collect();
+    format!("WHEN {} > 1 THEN '{}'\n        {}", matches.join(" + "), AMBIGUOUS_BREAKDOWN_GROUP, conditions)
+}
+
+fn breakdown_case_conditions(grouper: &[i32], asset_identifier_column: &str, alias_prefix: &str) -> String {
     if grouper.is_empty() {
         return format!("WHEN 1 THEN {asset_identifier_column} ");
     }
@@ -392,21 +573,17 @@ pub fn build_breakdown_case_conditions(grouper: &[i32], asset_identifier_column:
     let mut conditions = Vec::new();
     
     for &node_id in grouper {
+        // descendants_{0} is shared with the filters, see `with_category_descendants`
         let condition = format!(
             "WHEN EXISTS (
-                WITH RECURSIVE descendants_{0} AS (
-                    SELECT id FROM category WHERE id = {0}
-                    UNION ALL
-                    SELECT c.id FROM category c 
-                    JOIN descendants_{0} d ON c.parent_id = d.id
-                )
-                SELECT 1 FROM asset_category ac_{0}
-                WHERE ac_{0}.asset = {1}
-                  AND ac_{0}.leaf_id IN (SELECT id FROM descendants_{0})
+                SELECT 1 FROM asset_category ac_{2}{0}
+                WHERE ac_{2}{0}.asset = {1}
+                  AND ac_{2}{0}.leaf_id IN (SELECT id FROM descendants_{0})
                 LIMIT 1
-            ) THEN c{0}.name",
+            ) THEN c{2}{0}.name",
             node_id,
-            asset_identifier_column
+            asset_identifier_column,
+            alias_prefix
         );
         conditions.push(condition);
     }
@@ -416,12 +593,17 @@ pub fn build_breakdown_case_conditions(grouper: &[i32], asset_identifier_column:
 
 /// Builds JOIN clauses for breakdown nodes with proper category hierarchy handling
 pub fn build_breakdown_joins(grouper: &[i32]) -> String {
+    breakdown_joins(grouper, "")
+}
+
+fn breakdown_joins(grouper: &[i32], alias_prefix: &str) -> String {
    let mut joins = Vec::new();
     
     for &node_id in grouper {
         let join = format!(
-            "LEFT JOIN category c{0} ON c{0}.id = {0}",
-            node_id
+            "LEFT JOIN category c{1}{0} ON c{1}{0}.id = {0}",
+            node_id,
+            alias_prefix
         );
         joins.push(join);
     }
@@ -431,13 +613,17 @@ pub fn build_breakdown_joins(grouper: &[i32]) -> String {
 
 /// Builds SELECT clauses for breakdown columns with proper aggregation
 pub fn build_breakdown_selects(grouper: &[i32]) -> String {
+    breakdown_selects(grouper, "", "breakdown")
+}
+
+fn breakdown_selects(grouper: &[i32], alias_prefix: &str, column_prefix: &str) -> String {
     if grouper.is_empty() {
         return String::new();
     }
     
     let selects: Vec<String> = grouper.iter()
         .map(|&node_id| {
-            format!("c{}.name AS breakdown_{}", node_id, node_id)
+            format!("c{}{}.name AS {}_{}", alias_prefix, node_id, column_prefix, node_id)
         })
         .collect();
     
@@ -446,46 +632,1049 @@ pub fn build_breakdown_selects(grouper: &[i32]) -> String {
 
 /// Builds GROUP BY clauses for breakdown columns
 pub fn build_breakdown_group_by(grouper: &[i32]) -> String {
+    breakdown_group_by(grouper, "")
+}
+
+fn breakdown_group_by(grouper: &[i32], alias_prefix: &str) -> String {
     if grouper.is_empty() {
         return String::new();
     }
     
     let group_bys: Vec<String> = grouper.iter()
         .map(|&node_id| {
-            format!("c{}.name", node_id)
+            format!("c{}{}.name", alias_prefix, node_id)
         })
         .collect();
     
     format!(",\n      {}", group_bys.join(",\n      "))
 }
 
+/// Builds the column names of a query broken down along two dimensions (see `with_secondary_breakdown`): those of
+/// `build_breakdown_columns` followed by `asset_group` and a `group_breakdown_{id}` column per secondary node.
+pub fn build_two_dimensional_breakdown_columns(grouper: &[i32], secondary_grouper: &[i32]) -> Vec<String> {
+    let mut columns = build_breakdown_columns(grouper);
+    columns.push(SECONDARY_BREAKDOWN_COLUMN.to_string());
+    columns.extend(
+        secondary_grouper.iter()
+            .map(|&node_id| format!("{}_{}", SECONDARY_BREAKDOWN_COLUMN_PREFIX, node_id))
+    );
+    columns
+}
+
+/// Adds a second, independent breakdown dimension to a query already broken down by `grouper`, e.g. stacked by
+/// carrier and grouped by region: each asset is put in a primary group (the `asset` column) and a secondary group
+/// (the `asset_group` column), the query groups by both.
+///
+/// The secondary builders use their own table aliases, so a node can be in both groupers. Both groupers must be
+/// passed to `with_category_descendants`.
+///
+/// # Arguments
+///
+/// * `sql` - A query with the `{secondary_breakdown_case_conditions}` (inside `CASE ... ELSE 'Other' END AS asset_group`),
+///   `{secondary_breakdown_joins}`, `{secondary_breakdown_selects}` and `{secondary_breakdown_group_by}` placeholders,
+///   used like their primary counterparts.
+/// * `secondary_grouper` - List of the secondary breakdown node IDs.
+/// * `asset_identifier_column` - String representing the table.column to match the assets on.
+///
+/// # Returns
+///
+/// The query with the secondary placeholders replaced.
+pub fn with_secondary_breakdown(sql: &str, secondary_grouper: &[i32], asset_identifier_column: String) -> String {
+    sql
+        .replace("{secondary_breakdown_case_conditions}", &breakdown_case_conditions(secondary_grouper, &asset_identifier_column, SECONDARY_BREAKDOWN_ALIAS_PREFIX))
+        .replace("{secondary_breakdown_joins}", &breakdown_joins(secondary_grouper, SECONDARY_BREAKDOWN_ALIAS_PREFIX))
+        .replace("{secondary_breakdown_selects}", &breakdown_selects(secondary_grouper, SECONDARY_BREAKDOWN_ALIAS_PREFIX, SECONDARY_BREAKDOWN_COLUMN_PREFIX))
+        .replace("{secondary_breakdown_group_by}", &breakdown_group_by(secondary_grouper, SECONDARY_BREAKDOWN_ALIAS_PREFIX))
+}
+
+/// Orders the final output of a resolution query by one of the whitelisted `RESOLUTION_ORDER_BY` expressions,
+/// e.g. `y_axis DESC` for a ranking view.
+///
+/// # Arguments
+///
+/// * `sql` - The final resolution query (possibly wrapped by the service).
+/// * `order_by` - The ordering expression, `None` keeps the default ordering of the query.
+///
+/// # Returns
+///
+/// The query unchanged when no ordering is requested, otherwise the query selected from in the requested order,
+/// or an error when the expression is not whitelisted.
+pub fn with_resolution_order(sql: &str, order_by: Option<&str>) -> Result<String, String> {
+    let order_by: &str = match order_by {
+        Some(order_by) => order_by.trim(),
+        None => return Ok(sql.to_string()),
+    };
+
+    if !RESOLUTION_ORDER_BY.contains(&order_by) {
+        return Err(format!(
+            "Unsupported ordering '{}', expected one of: {}",
+            order_by,
+            RESOLUTION_ORDER_BY.join(" | ")
+        ));
+    }
+
+    Ok(format!(
+        "SELECT *\nFROM (\n{}\n) AS ordered_query\nORDER BY {}",
+        sql.trim().trim_end_matches(';'),
+        order_by,
+    ))
+}
+
+/// Pivots a long-format query (one row per series and period) with DuckDB's `PIVOT` into one column per series.
+///
+/// # Arguments
+///
+/// * `sql` - The long-format query.
+/// * `series_col` - The column whose values become the output columns (e.g. the asset or breakdown group).
+/// * `value_cols` - The value columns, with several of them the output columns are named `{series}_{value_col}`.
+/// * `index_cols` - The columns identifying an output row (e.g. `RESOLUTION_PIVOT_INDEX`).
+/// * `series` - The series to pivot on, as fetched by `fetch_pivot_series`.
+///
+/// # Returns
+///
+/// A `String` containing the pivot query, ordered by the index columns, or an error for an invalid column name.
+pub fn build_pivot_query(sql: &str, series_col: &str, value_cols: &[&str], index_cols: &[&str], series: &[String]) -> Result<String, String> {
+    validate_ident(series_col)?;
+    validate_idents(value_cols)?;
+    validate_idents(index_cols)?;
+
+    let sql: &str = sql.trim().trim_end_matches(';');
+    let index: String = index_cols.join(", ");
+    if series.is_empty() {
+        return Ok(format!("SELECT DISTINCT {index}\nFROM (\n{sql}\n) AS pivot_source\nWHERE FALSE"));
+    }
+
+    let series_list: Vec<String> = series.iter()
+        .map(|s| format!("'{}'", s.replace('\'', "''")))
+        .collect();
+    let values: Vec<String> = value_cols.iter()
+        .map(|col| if value_cols.len() > 1 { format!("ANY_VALUE({col}) AS {col}") } else { format!("ANY_VALUE({col})") })
+        .collect();
+
+    Ok(format!(
+        "SELECT *\nFROM (\nPIVOT (\n{sql}\n)\nON {series_col} IN ({})\nUSING {}\nGROUP BY {index}\n) AS pivoted_query\nORDER BY {index}",
+        series_list.join(", "),
+        values.join(", "),
+    ))
+}
+
+/// Builds a LEFT JOIN attaching the carrier inferred from each asset's outgoing flows as `inferred_carrier.carrier`.
+///
+/// Assets with several outgoing carriers are attributed to the first one alphabetically so their values
+/// are not counted twice, assets without outgoing flows keep a NULL carrier and end up in their own bucket.
+///
+/// # Arguments
+///
+/// * `asset_identifier_column` - String representing the table.column to use for matching asset by their ids
+///
+/// # Returns
+///
+/// A `String` containing the JOIN clause.
+pub fn build_carrier_join(asset_identifier_column: String) -> String {
+    format!(
+        "LEFT JOIN (
+            SELECT asset, MIN(carrier) AS carrier
+            FROM ({INFER_CARRIER_SQL_FROM_OUTGOING_FLOWS}) AS outgoing_carriers
+            GROUP BY asset
+        ) AS inferred_carrier ON inferred_carrier.asset = {asset_identifier_column}"
+    )
+}
+
+/// Builds the GROUP BY clause grouping by the carrier attached by `build_carrier_join`.
+///
+/// # Arguments
+///
+/// * `group_cols` - Additional columns to group by after the carrier.
+///
+/// # Returns
+///
+/// A `String` containing the GROUP BY clause, the NULL carrier forming its own group.
+pub fn build_carrier_group(group_cols: &[&str]) -> String {
+    let mut cols: Vec<&str> = vec!["inferred_carrier.carrier"];
+    cols.extend_from_slice(group_cols);
+
+    format!("GROUP BY {}", cols.join(", "))
+}
+
+/// Validates that a user-provided name is a plain SQL identifier, so it can safely be substituted into a query.
+///
+/// # Arguments
+///
+/// * `name` - The table or column name to validate.
+///
+/// # Returns
+///
+/// The name itself if it only consists of letters, digits and underscores (not starting with a digit), an error otherwise.
+pub fn validate_ident(name: &str) -> Result<&str, String> {
+    let mut chars = name.chars();
+    let valid_start = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    if valid_start && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Ok(name)
+    } else {
+        Err(format!("Invalid identifier: '{}'", name))
+    }
+}
+
+// `validate_ident` for every table or column name a builder substitutes
+fn validate_idents(names: &[&str]) -> Result<(), String> {
+    names.iter().try_for_each(|name| validate_ident(name).map(|_| ()))
+}
+
+/// Builds the two CTEs merging runs of consecutive rows with the same value into one block: the first numbers
+/// the runs by the difference of two `ROW_NUMBER`s (over the partition, and over the partition and value),
+/// the second groups each run into its first start and last end.
+///
+/// # Arguments
+///
+/// * `numbered_cte` - Name of the CTE assigning the run number `grp`.
+/// * `merged_cte` - Name of the CTE with one row per run.
+/// * `source` - The table or CTE to merge the rows of.
+/// * `partition_cols` - The columns a run can't cross (e.g. `{group_cols}`, `year`, `rep_period`).
+/// * `value_col` - The column whose value has to stay the same within a run.
+/// * `start_col` - The column ordering the rows, its minimum is the start of a run.
+/// * `end_col` - The column whose maximum is the end of a run.
+/// * `merged_start` - Name of the start column of a run.
+/// * `merged_end` - Name of the end column of a run.
+/// * `filter` - Optional `WHERE` clause applied after numbering the runs, or an empty string.
+/// * `merge_digits` - When set, values equal after rounding to this many decimals form one run whose value is their average,
+///   otherwise runs need exactly equal values.
+///
+/// # Returns
+///
+/// The comma-separated CTE definitions, the merged CTE has the partition columns, the start, the end and the value.
+pub fn build_run_merge_ctes(
+    numbered_cte: &str,
+    merged_cte: &str,
+    source: &str,
+    partition_cols: &[&str],
+    value_col: &str,
+    start_col: &str,
+    end_col: &str,
+    merged_start: &str,
+    merged_end: &str,
+    filter: &str,
+    merge_digits: Option<u32>,
+) -> String {
+    let partition: String = partition_cols.join(", ");
+    let filter: String = if filter.is_empty() { String::new() } else { format!("\n  {}", filter) };
+    let (run_key, run_value): (String, String) = match merge_digits {
+        Some(digits) => (format!("ROUND({value_col}, {digits})"), format!("AVG({value_col}) AS {value_col}")),
+        None => (value_col.to_string(), value_col.to_string()),
+    };
+
+    format!(
+        "{numbered_cte} AS (
+  SELECT *,
+    ROW_NUMBER() OVER (PARTITION BY {partition} ORDER BY {start_col}) -
+    ROW_NUMBER() OVER (PARTITION BY {partition}, {run_key} ORDER BY {start_col}) AS grp
+  FROM {source}
+),
+{merged_cte} AS (
+  SELECT
+    {partition},
+    MIN({start_col}) AS {merged_start},
+    MAX({end_col}) AS {merged_end},
+    {run_value}
+  FROM {numbered_cte}{filter}
+  GROUP BY {partition}, {run_key}, grp
+)"
+    )
+}
+
+// the representative period CTEs of a resolution query, up to `final_rep_periods`
+fn build_rep_period_resolution_sql(merge_digits: Option<u32>) -> String {
+    REP_PERIOD_RESOLUTION_SQL
+        .replace("{grouped_runs}", &build_run_merge_ctes(
+            "numbered", "grouped", "{source_table}", &["{group_cols}", "{year_col}", "rep_period"], "{value_col}",
+            "time_block_start", "time_block_end", "time_block_start", "time_block_end", "WHERE {year_col} = ?", merge_digits,
+        ))
+        .replace("{final_rep_periods_runs}", &build_run_merge_ctes(
+            "numbered_blocks", "final_rep_periods", "raw", &["{group_cols}", "milestone_year", "period"], "y_axis",
+            "start_hour", "end_hour", "start_hour", "end_hour", "", merge_digits,
+        ))
+}
+
+/// Builds the `final` CTE of the queries over both resolutions, combining the representative period and clustered
+/// year values of the same group and period.
+///
+/// # Arguments
+///
+/// * `combine` - One of `BOTH_COMBINATIONS`: "sum", "avg" or "max" of both values in every representative period
+///   block, a period with values in only one table keeps that value. By default both are kept as separate blocks,
+///   which the resolution periods then average by duration.
+///
+/// # Returns
+///
+/// The CTE, with the `{group_cols_comparisons}` placeholder still to fill in.
+fn build_both_resolutions_sql(combine: Option<&str>) -> String {
+    let combination: &str = match combine {
+        Some("sum") => "f.y_axis + COALESCE(d.y_axis, 0)",
+        Some("avg") => "COALESCE((f.y_axis + d.y_axis) / 2, f.y_axis)",
+        Some("max") => "GREATEST(f.y_axis, COALESCE(d.y_axis, f.y_axis))",
+        _ => return BOTH_RESOLUTIONS_SQL.to_string(),
+    };
+
+    COMBINED_RESOLUTIONS_SQL.replace("{combination}", combination)
+}
+
+// the CTEs mapping `final_table` onto the global timeline and into resolution periods, and the final select
+fn build_last_part_sql(final_table: &str, merge_digits: Option<u32>) -> String {
+    LAST_PART_SQL
+        .replace("{final}", final_table)
+        .replace("{merged_runs}", &build_run_merge_ctes(
+            "numbered_1", "merged", "period_avg", &["{group_cols}", "milestone_year"], "y_axis",
+            "global_start_val", "global_end_val", "global_start", "global_end", "", merge_digits,
+        ))
+}
+
+/// Clamps negative values of output columns of a final query to zero, e.g. shadow prices during curtailment.
+///
+/// # Arguments
+///
+/// * `sql` - The final query.
+/// * `columns` - The numeric output columns to clamp, all other columns are kept as is.
+/// * `clamp_negative` - Whether to clamp, negative values are kept by default.
+///
+/// # Returns
+///
+/// The query unchanged when not clamping, otherwise the query selected from with the columns clamped at zero,
+/// or an error for an invalid column name.
+pub fn with_clamped_negatives(sql: &str, columns: &[&str], clamp_negative: bool) -> Result<String, String> {
+    if !clamp_negative || columns.is_empty() {
+        return Ok(sql.to_string());
+    }
+    validate_idents(columns)?;
+
+    let replacements: Vec<String> = columns.iter()
+        .map(|col| format!("GREATEST({col}, 0) AS {col}"))
+        .collect();
+
+    Ok(format!(
+        "SELECT * REPLACE ({})\nFROM (\n{}\n) AS clamped_query",
+        replacements.join(", "),
+        sql.trim().trim_end_matches(';'),
+    ))
+}
+
+/// Sets values of a source query's columns which are numerically zero (e.g. a solver's `1e-14` duals) to exactly
+/// zero, so they neither show up as noise in price charts nor keep equal blocks from merging.
+///
+/// # Arguments
+///
+/// * `sql` - The source query, before any aggregation.
+/// * `columns` - The numeric columns to zero, all other columns are kept as is.
+/// * `zero_epsilon` - Values with a magnitude below it become zero, `None` keeps the values as is.
+///
+/// # Returns
+///
+/// The query unchanged without an epsilon, otherwise the query selected from with the columns zeroed, or an error
+/// for an invalid column name or an epsilon which is not a positive number.
+pub fn with_zeroed_values(sql: &str, columns: &[&str], zero_epsilon: Option<f64>) -> Result<String, String> {
+    let epsilon: f64 = match zero_epsilon {
+        Some(epsilon) if !columns.is_empty() => epsilon,
+        _ => return Ok(sql.to_string()),
+    };
+    if !epsilon.is_finite() || epsilon <= 0.0 {
+        return Err(format!("Invalid zero epsilon '{}', expected a positive number", epsilon));
+    }
+    validate_idents(columns)?;
+
+    let replacements: Vec<String> = columns.iter()
+        .map(|col| format!("CASE WHEN ABS({col}) < {epsilon:e} THEN 0 ELSE {col} END AS {col}"))
+        .collect();
+
+    Ok(format!(
+        "SELECT * REPLACE ({})\nFROM (\n{}\n) AS zeroed_query",
+        replacements.join(", "),
+        sql.trim().trim_end_matches(';'),
+    ))
+}
+
+/// Aggregates the dual values of the assets in each group (the `asset` column, a breakdown group name when
+/// grouping) per single time step, so a group's price is e.g. the median over its assets instead of the sum
+/// of its distinct prices. Assets with differing time blocks are compared step by step.
+///
+/// # Arguments
+///
+/// * `source_sql` - The price source query with one row per (group, time block, dual value), keeping the
+///   `{per_asset_group_by}` placeholder after `dual_value` in its GROUP BY, and the assets joined as `a`.
+/// * `aggregation` - "mean" or "median", `None` keeps the source as is.
+/// * `block_cols` - The start and end columns of the source's blocks (time blocks or period blocks).
+///
+/// # Returns
+///
+/// The source query (placeholder removed) when not aggregating, otherwise the source kept per asset and
+/// aggregated per group and step, or an error for an unknown aggregation.
+pub fn with_cross_asset_aggregation(source_sql: &str, aggregation: Option<&str>, block_cols: (&str, &str)) -> Result<String, String> {
+    let aggregation: &str = match aggregation {
+        Some(aggregation) => aggregation,
+        None => return Ok(source_sql.replace("{per_asset_group_by}", "")),
+    };
+    let agg_fn: &str = CROSS_ASSET_AGGREGATIONS.iter()
+        .find(|(name, _)| *name == aggregation)
+        .map(|(_, agg_fn)| *agg_fn)
+        .ok_or_else(|| format!(
+            "Invalid cross-asset aggregation '{}', expected one of: {}",
+            aggregation,
+            CROSS_ASSET_AGGREGATIONS.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", "),
+        ))?;
+    validate_idents(&[block_cols.0, block_cols.1])?;
+
+    Ok(CROSS_ASSET_AGGREGATION_SQL
+        .replace("{source_sql}", &source_sql.replace("{per_asset_group_by}", ",\n        a.asset"))
+        .replace("{agg_fn}", agg_fn)
+        .replace("{block_start}", block_cols.0)
+        .replace("{block_end}", block_cols.1))
+}
+
+/// Rounds numeric output columns of a final query in SQL, which keeps the serialized payload small.
+///
+/// # Arguments
+///
+/// * `sql` - The final query.
+/// * `columns` - The numeric output columns to round, all other columns are kept as is.
+/// * `round_digits` - Number of decimal digits to round to, `None` disables rounding.
+///
+/// # Returns
+///
+/// The query unchanged when no rounding is requested, otherwise the query selected from with the columns rounded,
+/// or an error for an invalid column name.
+pub fn with_rounding(sql: &str, columns: &[&str], round_digits: Option<u32>) -> Result<String, String> {
+    let digits: u32 = match round_digits {
+        Some(digits) if !columns.is_empty() => digits,
+        _ => return Ok(sql.to_string()),
+    };
+    validate_idents(columns)?;
+
+    let replacements: Vec<String> = columns.iter()
+        .map(|col| format!("ROUND({col}, {digits}) AS {col}"))
+        .collect();
+
+    Ok(format!(
+        "SELECT * REPLACE ({})\nFROM (\n{}\n) AS rounded_query",
+        replacements.join(", "),
+        sql.trim().trim_end_matches(';'),
+    ))
+}
+
+/// Averages the output of a resolution query per calendar month or meteorological season instead of
+/// returning a continuous timeline. Periods are mapped onto a non-leap calendar year by the hour they start at,
+/// `global_start` (a period index) times the resolution.
+///
+/// # Arguments
+///
+/// * `sql` - The resolution query, producing `milestone_year`, `global_start` and `y_axis` per series.
+/// * `series_col` - The column identifying a series (e.g. the asset or carrier).
+/// * `seasonal` - The calendar buckets, `None` keeps the timeline.
+/// * `order_by` - The custom ordering requested alongside, which only applies to a timeline.
+/// * `pivot` - Whether a pivoted output is requested alongside, which only applies to a timeline.
+/// * `resolution` - The length of the query's periods in hours.
+///
+/// # Returns
+///
+/// The query unchanged without `seasonal`, otherwise the query grouped into one row per series, milestone year and bucket,
+/// or an error when combined with a custom ordering or pivot, or for an invalid series column.
+pub fn with_seasonal(sql: &str, series_col: &str, seasonal: Option<SeasonMode>, order_by: Option<&str>, pivot: bool, resolution: u32) -> Result<String, String> {
+    let template: &str = match seasonal {
+        Some(SeasonMode::Month) => SEASONAL_MONTH_SQL,
+        Some(SeasonMode::Season) => SEASONAL_SEASON_SQL,
+        None => return Ok(sql.to_string()),
+    };
+    if order_by.is_some() || pivot {
+        return Err("Seasonal aggregation cannot be combined with custom ordering or pivoting".to_string());
+    }
+    validate_ident(series_col)?;
+
+    Ok(template
+        .replace("{series_col}", series_col)
+        .replace("{resolution}", &resolution.to_string())
+        .replace("{sql}", sql.trim().trim_end_matches(';')))
+}
+
+/// Turns the `global_start` and `global_end` period indices of a resolution query into timestamps, counting
+/// `resolution` hours per period from midnight of `start_date`.
+///
+/// # Arguments
+///
+/// * `sql` - The resolution query, producing `global_start` and `global_end`.
+/// * `start_date` - The ISO date (YYYY-MM-DD) of hour 0, `None` keeps the period indices.
+/// * `seasonal` - Whether the query was aggregated by `with_seasonal`, which leaves no timeline to convert.
+/// * `resolution` - The length of the query's periods in hours.
+///
+/// # Returns
+///
+/// The query unchanged without `start_date`, otherwise the query with timestamp columns,
+/// or an error for a malformed date or a seasonal query.
+pub fn with_timestamps(sql: &str, start_date: Option<&str>, seasonal: bool, resolution: u32) -> Result<String, String> {
+    let start_date: &str = match start_date {
+        Some(start_date) => start_date,
+        None => return Ok(sql.to_string()),
+    };
+    if seasonal {
+        return Err("Timestamps cannot be combined with seasonal aggregation".to_string());
+    }
+    // checked by shape only, it ends up as a literal in the query
+    let is_iso_date: bool = start_date.len() == 10 && start_date.char_indices().all(|(i, c)| match i {
+        4 | 7 => c == '-',
+        _ => c.is_ascii_digit(),
+    });
+    if !is_iso_date {
+        return Err(format!("Invalid start date '{}', expected an ISO date (YYYY-MM-DD)", start_date));
+    }
+
+    Ok(TIMESTAMPS_SQL
+        .replace("{start_date}", start_date)
+        .replace("{resolution}", &resolution.to_string())
+        .replace("{sql}", sql.trim().trim_end_matches(';')))
+}
+
+// --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use duckdb::types::Value;
+    use crate::duckdb_conn::run_query_row;
+    use crate::test_utils::setup_test_db;
+
+    #[test]
+    fn build_filter_conditions_snapshot_test() {
+        let filters: HashMap<i32, Vec<i32>> = HashMap::from([(1, vec![4, 5])]);
+        let conditions: String = build_filter_conditions(&filters, "a.asset".to_string());
+
+        assert!(conditions.starts_with("AND EXISTS ("));
+        assert!(conditions.contains("WHERE ac1.asset = a.asset"));
+        assert!(conditions.contains("AND ac1.root_id = 1"));
+        assert!(conditions.contains("AND ac1.leaf_id IN (SELECT id FROM descendants_4 UNION ALL SELECT id FROM descendants_5)"));
+        assert!(!conditions.contains("RECURSIVE"), "descendants must come from the shared CTEs");
+    }
+
+    #[test]
+    fn build_breakdown_case_conditions_snapshot_test() {
+        let conditions: String = build_breakdown_case_conditions(&[7], "a.asset".to_string());
+
+        assert!(conditions.starts_with("WHEN EXISTS ("));
+        assert!(conditions.contains("AND ac_7.leaf_id IN (SELECT id FROM descendants_7)"));
+        assert!(conditions.ends_with(") THEN c7.name"));
+        assert!(!conditions.contains("RECURSIVE"), "descendants must come from the shared CTEs");
+    }
+
+    #[test]
+    fn with_category_descendants_shares_nodes_test() {
+        // node 4 is both filtered on and broken down by, it must only be computed once
+        let filters: HashMap<i32, Vec<i32>> = HashMap::from([(1, vec![4]), (2, vec![])]);
+        let sql: String = with_category_descendants("SELECT 1;", &filters, &[4, 3]);
+
+        assert!(sql.starts_with("WITH RECURSIVE descendants_3 AS ("));
+        assert_eq!(sql.matches("descendants_4 AS (").count(), 1);
+        assert!(sql.ends_with("SELECT * FROM (\nSELECT 1\n) AS shared_descendants_query"));
+    }
+
+    #[test]
+    fn with_category_descendants_no_nodes_test() {
+        assert_eq!(with_category_descendants("SELECT 1;", &HashMap::new(), &[]), "SELECT 1;");
+    }
+
+    // chain of 300 categories (1 is the root, 300 the deepest), asset_i is attached to category i
+    const DEEP_TREE_FIXTURE_SQL: &str = "
+        CREATE TABLE category(id INTEGER, name VARCHAR, parent_id INTEGER, level INTEGER);
+        INSERT INTO category SELECT i, 'cat_' || i, CASE WHEN i = 1 THEN NULL ELSE i - 1 END, 300 - i FROM range(1, 301) AS t(i);
+        CREATE TABLE asset(asset VARCHAR);
+        INSERT INTO asset SELECT 'asset_' || i FROM range(1, 301) AS t(i);
+        CREATE TABLE asset_category(asset VARCHAR, root_id INTEGER, leaf_id INTEGER);
+        INSERT INTO asset_category SELECT 'asset_' || i, 1, i FROM range(1, 301) AS t(i);
+    ";
+
+    #[test]
+    fn shared_descendants_deep_tree_test() {
+        let db_path: String = setup_test_db("shared_descendants_deep_tree", DEEP_TREE_FIXTURE_SQL);
+        let filters: HashMap<i32, Vec<i32>> = HashMap::from([(1, vec![100])]);
+        let grouper: [i32; 2] = [200, 100];
+
+        let sql: String = with_category_descendants(
+            &format!(
+                "SELECT CASE {} ELSE 'Other' END AS grp, COUNT(*) AS n FROM asset a {} WHERE 1 {} GROUP BY grp ORDER BY grp",
+                build_breakdown_case_conditions(&grouper, "a.asset".to_string()),
+                build_breakdown_joins(&grouper),
+                build_filter_conditions(&filters, "a.asset".to_string()),
+            ),
+            &filters,
+            &grouper,
+        );
+
+        let start: Instant = Instant::now();
+        let groups: Vec<(String, i64)> = run_query_row(db_path, sql, vec![], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        let elapsed = start.elapsed();
+
+        // assets 100..=300 pass the filter, 200..=300 land in the first matching breakdown node
+        assert_eq!(groups, vec![("cat_100".to_string(), 100), ("cat_200".to_string(), 101)]);
+        assert!(elapsed.as_secs() < 10, "deep category tree took {:?}", elapsed);
+    }
+
+    // 'carrier' (1) has 'electricity' (2) and 'gas' (3), 'region' (4) has 'NL' (5) and 'BE' (6)
+    const TWO_DIMENSIONS_FIXTURE_SQL: &str = "
+        CREATE TABLE category(id INTEGER, name VARCHAR, parent_id INTEGER, level INTEGER);
+        INSERT INTO category VALUES (1, 'carrier', NULL, 1), (2, 'electricity', 1, 0), (3, 'gas', 1, 0),
+            (4, 'region', NULL, 1), (5, 'NL', 4, 0), (6, 'BE', 4, 0);
+        CREATE TABLE asset(asset VARCHAR, capacity DOUBLE);
+        INSERT INTO asset VALUES ('wind_nl', 10), ('solar_nl', 2), ('wind_be', 5), ('ccgt_nl', 8);
+        CREATE TABLE asset_category(asset VARCHAR, root_id INTEGER, leaf_id INTEGER);
+        INSERT INTO asset_category VALUES ('wind_nl', 1, 2), ('wind_nl', 4, 5), ('solar_nl', 1, 2), ('solar_nl', 4, 5),
+            ('wind_be', 1, 2), ('wind_be', 4, 6), ('ccgt_nl', 1, 3), ('ccgt_nl', 4, 5);
+    ";
+
+    #[test]
+    fn with_secondary_breakdown_test() {
+        let db_path: String = setup_test_db("secondary_breakdown", TWO_DIMENSIONS_FIXTURE_SQL);
+        let (grouper, secondary_grouper): ([i32; 2], [i32; 2]) = ([2, 3], [5, 6]);
+
+        let sql: String = "
+            SELECT
+                CASE {breakdown_case_conditions} ELSE 'Other' END AS asset,
+                CASE {secondary_breakdown_case_conditions} ELSE 'Other' END AS asset_group,
+                SUM(a.capacity) AS capacity{breakdown_selects}{secondary_breakdown_selects}
+            FROM asset AS a
+            {breakdown_joins}
+            {secondary_breakdown_joins}
+            GROUP BY 1, 2{breakdown_group_by}{secondary_breakdown_group_by}
+            ORDER BY 1, 2"
+            .replace("{breakdown_case_conditions}", &build_breakdown_case_conditions(&grouper, "a.asset".to_string()))
+            .replace("{breakdown_selects}", &build_breakdown_selects(&grouper))
+            .replace("{breakdown_joins}", &build_breakdown_joins(&grouper))
+            .replace("{breakdown_group_by}", &build_breakdown_group_by(&grouper));
+        let sql: String = with_secondary_breakdown(&sql, &secondary_grouper, "a.asset".to_string());
+        let sql: String = with_category_descendants(&sql, &HashMap::new(), &[grouper, secondary_grouper].concat());
+
+        let groups: Vec<(String, String, f64)> = run_query_row(db_path, sql, vec![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap();
+        assert_eq!(groups, vec![
+            ("electricity".to_string(), "BE".to_string(), 5.0),
+            ("electricity".to_string(), "NL".to_string(), 12.0),
+            ("gas".to_string(), "NL".to_string(), 8.0),
+        ]);
+    }
+
+    #[test]
+    fn build_breakdown_case_conditions_overlap_test() {
+        // every asset is under both a carrier and a region node
+        let db_path: String = setup_test_db("breakdown_overlap", TWO_DIMENSIONS_FIXTURE_SQL);
+        let grouper: [i32; 2] = [2, 5];
+
+        let groups = |overlap: BreakdownOverlap| -> Vec<(String, f64)> {
+            let sql: String = "
+                SELECT CASE {breakdown_case_conditions} ELSE 'Other' END AS asset, SUM(a.capacity) AS capacity
+                FROM asset AS a
+                {breakdown_joins}
+                GROUP BY 1
+                ORDER BY 1"
+                .replace("{breakdown_case_conditions}", &build_breakdown_case_conditions_with_overlap(&grouper, "a.asset".to_string(), overlap))
+                .replace("{breakdown_joins}", &build_breakdown_joins(&grouper));
+            let sql: String = with_category_descendants(&sql, &HashMap::new(), &grouper);
+            run_query_row(db_path.clone(), sql, vec![], |row| Ok((row.get(0)?, row.get(1)?))).unwrap()
+        };
+
+        // wind_nl and solar_nl are electricity and NL, counted once under the first node
+        assert_eq!(groups(BreakdownOverlap::First), vec![
+            ("NL".to_string(), 8.0),
+            ("electricity".to_string(), 17.0),
+        ]);
+        assert_eq!(groups(BreakdownOverlap::Ambiguous), vec![
+            ("Ambiguous".to_string(), 12.0),
+            ("NL".to_string(), 8.0),
+            ("electricity".to_string(), 5.0),
+        ]);
+    }
+
+    #[test]
+    fn with_secondary_breakdown_same_node_test() {
+        // node 2 is in both groupers, the secondary dimension must not reuse the primary aliases
+        let db_path: String = setup_test_db("secondary_breakdown_same_node", TWO_DIMENSIONS_FIXTURE_SQL);
+        let sql: String = format!(
+            "SELECT CASE {} ELSE 'Other' END AS asset, CASE {{secondary_breakdown_case_conditions}} ELSE 'Other' END AS asset_group, COUNT(*)
+            FROM asset AS a {} {{secondary_breakdown_joins}} GROUP BY ALL ORDER BY ALL",
+            build_breakdown_case_conditions(&[2], "a.asset".to_string()),
+            build_breakdown_joins(&[2]),
+        );
+        let sql: String = with_category_descendants(&with_secondary_breakdown(&sql, &[2], "a.asset".to_string()), &HashMap::new(), &[2, 2]);
+
+        let groups: Vec<(String, String, i64)> = run_query_row(db_path, sql, vec![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap();
+        assert_eq!(groups, vec![("Other".to_string(), "Other".to_string(), 1), ("electricity".to_string(), "electricity".to_string(), 3)]);
+    }
+
+    #[test]
+    fn build_two_dimensional_breakdown_columns_test() {
+        assert_eq!(
+            build_two_dimensional_breakdown_columns(&[2, 3], &[5]),
+            vec!["asset", "breakdown_2", "breakdown_3", "asset_group", "group_breakdown_5"],
+        );
+    }
+
+    #[test]
+    fn build_run_merge_ctes_snapshot_test() {
+        let sql: String = build_run_merge_ctes("numbered", "merged", "src", &["asset", "year"], "price", "t_start", "t_end", "s", "e", "WHERE year = ?", None);
+
+        assert!(sql.starts_with("numbered AS (\n  SELECT *,\n    ROW_NUMBER() OVER (PARTITION BY asset, year ORDER BY t_start) -\n"));
+        assert!(sql.contains("ROW_NUMBER() OVER (PARTITION BY asset, year, price ORDER BY t_start) AS grp\n  FROM src\n),"));
+        assert!(sql.contains("merged AS (\n  SELECT\n    asset, year,\n    MIN(t_start) AS s,\n    MAX(t_end) AS e,\n    price\n"));
+        assert!(sql.ends_with("FROM numbered\n  WHERE year = ?\n  GROUP BY asset, year, price, grp\n)"));
+    }
+
+    #[test]
+    fn build_resolution_query_entry_points_merge_runs_test() {
+        let rep_periods: String = build_resolution_query("t", "v", &["asset"], "avg", "1", false, None, "year").unwrap();
+        let clustered: String = build_resolution_query("t", "v", &["asset"], "avg", "1", true, None, "year").unwrap();
+        let both: String = build_resolution_query_both("t", "t1", "v", &["asset"], "avg", "1", None, "year", None).unwrap();
+
+        // every run merge comes from the shared generator, with all placeholders filled in
+        assert_eq!(rep_periods.matches(" AS grp").count(), 3);
+        assert_eq!(clustered.matches(" AS grp").count(), 1);
+        assert_eq!(both.matches(" AS grp").count(), 3);
+        assert!(rep_periods.contains("ROW_NUMBER() OVER (PARTITION BY asset, year, rep_period, v ORDER BY time_block_start) AS grp\n  FROM t\n"));
+        assert!(rep_periods.contains("FROM numbered\n  WHERE year = ?\n  GROUP BY asset, year, rep_period, v, grp"));
+        assert!(both.contains("final_rep_periods AS (\n  SELECT\n    asset, milestone_year, period,\n    MIN(start_hour) AS start_hour,\n    MAX(end_hour) AS end_hour,\n    y_axis\n"));
+        for sql in [&rep_periods, &clustered, &both] {
+            assert!(!sql.contains('{'), "unfilled placeholder in: {}", sql);
+            assert!(sql.ends_with("FROM merged\nORDER BY asset, milestone_year, global_start;"));
+        }
+    }
+
+    #[test]
+    fn build_resolution_query_merged_output_test() {
+        // hourly values 1, 1, 2, 2, 1 merge into three blocks, also at a two hour resolution
+        let db_path: String = setup_test_db("resolution_query_merged_output", "
+            CREATE TABLE t(asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, v DOUBLE);
+            INSERT INTO t VALUES ('a', 2030, 1, 1, 1, 1), ('a', 2030, 1, 2, 2, 1), ('a', 2030, 1, 3, 3, 2), ('a', 2030, 1, 4, 4, 2), ('a', 2030, 1, 5, 5, 1);
+            CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+            INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 1);
+            CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+            INSERT INTO rep_periods_data VALUES (2030, 1, 5, 1);
+        ");
+        let blocks = |resolution: &str| -> Vec<(i64, i64, f64)> {
+            let sql: String = build_resolution_query("t", "v", &["asset"], "avg", resolution, false, None, "year").unwrap();
+            run_query_row(db_path.clone(), sql, vec![Value::from(2030)], |row| {
+                Ok((row.get::<&str, i64>("global_start")?, row.get::<&str, i64>("global_end")?, row.get::<&str, f64>("y_axis")?))
+            }).unwrap()
+        };
+
+        assert_eq!(blocks("1"), vec![(0, 2, 1.0), (2, 4, 2.0), (4, 5, 1.0)]);
+        assert_eq!(blocks("2"), vec![(0, 1, 1.0), (1, 2, 2.0), (2, 3, 1.0)]);
+    }
+
+    #[test]
+    fn build_resolution_query_merge_tolerance_test() {
+        // the first two hours differ only past the third decimal
+        let db_path: String = setup_test_db("resolution_query_merge_tolerance", "
+            CREATE TABLE t(asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, v DOUBLE);
+            INSERT INTO t VALUES ('a', 2030, 1, 1, 1, 1.0), ('a', 2030, 1, 2, 2, 1.0000001), ('a', 2030, 1, 3, 3, 2), ('a', 2030, 1, 4, 4, 2);
+            CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+            INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 1);
+            CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+            INSERT INTO rep_periods_data VALUES (2030, 1, 4, 1);
+        ");
+        let blocks = |merge_digits: Option<u32>| -> Vec<(i64, i64, f64)> {
+            let sql: String = build_resolution_query("t", "v", &["asset"], "avg", "1", false, merge_digits, "year").unwrap();
+            run_query_row(db_path.clone(), sql, vec![Value::from(2030)], |row| {
+                Ok((row.get::<&str, i64>("global_start")?, row.get::<&str, i64>("global_end")?, row.get::<&str, f64>("y_axis")?))
+            }).unwrap()
+        };
+
+        let exact = blocks(None);
+        assert_eq!(exact.iter().map(|b| (b.0, b.1)).collect::<Vec<_>>(), vec![(0, 1), (1, 2), (2, 4)]);
+
+        let tolerant = blocks(Some(3));
+        assert_eq!(tolerant.iter().map(|b| (b.0, b.1)).collect::<Vec<_>>(), vec![(0, 2), (2, 4)]);
+        assert!((tolerant[0].2 - 1.0).abs() < 1e-6);
+        assert_eq!(tolerant[1].2, 2.0);
+        assert!(build_resolution_query("t", "v", &["asset"], "avg", "1", false, Some(3), "year").unwrap().contains("PARTITION BY asset, year, rep_period, ROUND(v, 3)"));
+    }
+
+    #[test]
+    fn build_resolution_query_year_col_test() {
+        // the source table is keyed by commission_year, the 2040 rows must not be picked up
+        let db_path: String = setup_test_db("resolution_query_year_col", "
+            CREATE TABLE t(asset VARCHAR, commission_year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, v DOUBLE);
+            INSERT INTO t VALUES ('a', 2030, 1, 1, 2, 3), ('a', 2030, 1, 3, 3, 5), ('a', 2040, 1, 1, 3, 7);
+            CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+            INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 1), (2040, 1, 1, 1);
+            CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+            INSERT INTO rep_periods_data VALUES (2030, 1, 3, 1), (2040, 1, 3, 1);
+        ");
+        let sql: String = build_resolution_query("t", "v", &["asset"], "avg", "1", false, None, "commission_year").unwrap();
+        let blocks: Vec<(i64, i64, i64, f64)> = run_query_row(db_path, sql, vec![Value::from(2030)], |row| {
+            Ok((
+                row.get::<&str, i64>("milestone_year")?,
+                row.get::<&str, i64>("global_start")?,
+                row.get::<&str, i64>("global_end")?,
+                row.get::<&str, f64>("y_axis")?,
+            ))
+        }).unwrap();
+
+        assert_eq!(blocks, vec![(2030, 0, 2, 3.0), (2030, 2, 3, 5.0)]);
+    }
+
+    #[test]
+    fn validate_ident_ok_test() {
+        assert_eq!(validate_ident("var_flow"), Ok("var_flow"));
+        assert_eq!(validate_ident("_col1"), Ok("_col1"));
+    }
+
+    #[test]
+    fn validate_ident_invalid_test() {
+        for name in ["", "1col", "asset; DROP TABLE asset", "a.b", "na\"me", "col name"] {
+            assert!(validate_ident(name).is_err(), "'{}' should be rejected", name);
+        }
+    }
+
+    #[test]
+    fn build_resolution_query_invalid_ident_test() {
+        let bad: &str = "t; DROP TABLE asset";
+        assert!(build_resolution_query(bad, "v", &["asset"], "avg", "1", false, None, "year").is_err());
+        assert!(build_resolution_query("t", "v", &["asset", bad], "avg", "1", false, None, "year").is_err());
+        assert!(build_resolution_query_both("t", bad, "v", &["asset"], "avg", "1", None, "year", None).is_err());
+
+        let breakdown_cols: Vec<String> = vec!["breakdown_2".to_string(), bad.to_string()];
+        let res = build_resolution_query_with_filters_and_breakdown(
+            "t", "v", &breakdown_cols, "avg", "1", &HashMap::new(), &[2], "a.asset".to_string(), "a.asset".to_string(), false, None, "year",
+        );
+        assert_eq!(res, Err(format!("Invalid identifier: '{}'", bad)));
+    }
+
+    #[test]
+    fn with_rounding_invalid_column_test() {
+        assert!(with_rounding("SELECT 1 AS y_axis", &["y_axis) FROM asset; --"], Some(2)).is_err());
+        assert!(with_clamped_negatives("SELECT 1 AS y_axis", &["y_axis, 0)"], true).is_err());
+        assert!(build_pivot_query("SELECT 1", "asset; --", &["y_axis"], &RESOLUTION_PIVOT_INDEX, &[]).is_err());
+        assert!(with_seasonal("SELECT 1", "asset; --", Some(SeasonMode::Month), None, false, 1).is_err());
+    }
+
+    #[test]
+    fn build_carrier_join_snapshot_test() {
+        let join: String = build_carrier_join("cap.asset".to_string());
+
+        assert!(join.starts_with("LEFT JOIN ("));
+        assert!(join.contains("SELECT asset, MIN(carrier) AS carrier"));
+        assert!(join.contains("JOIN flow AS f"));
+        assert!(join.ends_with(") AS inferred_carrier ON inferred_carrier.asset = cap.asset"));
+    }
+
+    #[test]
+    fn build_carrier_group_snapshot_test() {
+        assert_eq!(build_carrier_group(&[]), "GROUP BY inferred_carrier.carrier");
+        assert_eq!(build_carrier_group(&["cap.year"]), "GROUP BY inferred_carrier.carrier, cap.year");
+    }
+
+    #[test]
+    fn with_resolution_order_default_test() {
+        assert_eq!(with_resolution_order("SELECT 1 AS y_axis;", None), Ok("SELECT 1 AS y_axis;".to_string()));
+    }
+
+    #[test]
+    fn with_resolution_order_invalid_test() {
+        for order_by in ["y_axis; DROP TABLE asset", "asset", "random()"] {
+            assert!(with_resolution_order("SELECT 1 AS y_axis", Some(order_by)).is_err(), "'{}' should be rejected", order_by);
+        }
+    }
+
+    #[test]
+    fn build_pivot_query_snapshot_test() {
+        let sql: String = build_pivot_query("SELECT 1;", "asset", &["y_axis"], &RESOLUTION_PIVOT_INDEX, &["wind".to_string(), "o'hare".to_string()]).unwrap();
+
+        assert!(sql.contains("ON asset IN ('wind', 'o''hare')"));
+        assert!(sql.contains("USING ANY_VALUE(y_axis)\n"));
+        assert!(sql.ends_with("GROUP BY milestone_year, global_start, global_end\n) AS pivoted_query\nORDER BY milestone_year, global_start, global_end"));
+    }
+
+    #[test]
+    fn with_clamped_negatives_disabled_test() {
+        assert_eq!(with_clamped_negatives("SELECT 1;", &["y_axis"], false), Ok("SELECT 1;".to_string()));
+    }
+
+    #[test]
+    fn with_zeroed_values_test() {
+        assert_eq!(with_zeroed_values("SELECT 1;", &["dual_value"], None), Ok("SELECT 1;".to_string()));
+        assert!(with_zeroed_values("SELECT 1", &["dual_value"], Some(0.0)).is_err());
+        assert!(with_zeroed_values("SELECT 1", &["dual_value"], Some(f64::NAN)).is_err());
+        assert!(with_zeroed_values("SELECT 1", &["dual_value) FROM x --"], Some(1e-9)).is_err());
+        assert!(with_zeroed_values("SELECT 1", &["dual_value"], Some(1e-9)).unwrap()
+            .contains("CASE WHEN ABS(dual_value) < 1e-9 THEN 0 ELSE dual_value END AS dual_value"));
+    }
+
+    #[test]
+    fn with_rounding_none_test() {
+        assert_eq!(with_rounding("SELECT 1.2345 AS y_axis;", &["y_axis"], None), Ok("SELECT 1.2345 AS y_axis;".to_string()));
+    }
+
+    #[test]
+    fn with_rounding_matches_unrounded_test() {
+        let db_path: String = setup_test_db("with_rounding", "
+            CREATE TABLE prices(asset VARCHAR, y_axis DOUBLE);
+            INSERT INTO prices VALUES ('a', 1.23456789), ('b', -2.71828), ('c', 1000.0049), ('d', NULL);
+        ");
+        let sql: &str = "SELECT asset, y_axis FROM prices ORDER BY asset;";
+        let read = |q: String| -> Vec<(String, Option<f64>)> {
+            run_query_row(db_path.clone(), q, vec![], |row| Ok((row.get(0)?, row.get(1)?))).unwrap()
+        };
+
+        let unrounded = read(sql.to_string());
+        let rounded = read(with_rounding(sql, &["y_axis"], Some(2)).unwrap());
+
+        assert_eq!(rounded.len(), unrounded.len());
+        for ((asset, value), (rounded_asset, rounded_value)) in unrounded.iter().zip(rounded.iter()) {
+            assert_eq!(asset, rounded_asset);
+            match (value, rounded_value) {
+                (Some(v), Some(r)) => assert!(((v * 100.0).round() / 100.0 - r).abs() < 1e-9, "{} rounded to {}", v, r),
+                (None, None) => {},
+                _ => panic!("null mismatch for asset {}", asset),
+            }
+        }
+    }
+
+    #[test]
+    fn with_timestamps_second_day_test() {
+        let db_path: String = setup_test_db("with_timestamps_second_day", "");
+        let sql: String = with_timestamps("SELECT 24 AS global_start, 48 AS global_end, 1.5 AS y_axis;", Some("2030-01-01"), false, 1).unwrap();
+        let timeline: Vec<(String, String)> = run_query_row(
+            db_path,
+            format!("SELECT CAST(global_start AS VARCHAR), CAST(global_end AS VARCHAR) FROM ({})", sql),
+            vec![],
+            |row| Ok((row.get::<usize, String>(0)?, row.get::<usize, String>(1)?)),
+        ).unwrap();
+
+        assert_eq!(timeline, vec![("2030-01-02 00:00:00".to_string(), "2030-01-03 00:00:00".to_string())]);
+        assert_eq!(with_timestamps("SELECT 1", None, true, 1), Ok("SELECT 1".to_string()));
+    }
+
+    #[test]
+    fn with_timestamps_daily_resolution_test() {
+        // two days of hourly blocks, averaged per day
+        let db_path: String = setup_test_db("with_timestamps_daily_resolution", "
+            CREATE TABLE t(asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, v DOUBLE);
+            INSERT INTO t SELECT 'a', 2030, 1, h, h, CASE WHEN h <= 24 THEN 1 ELSE 2 END FROM range(1, 49) AS hours(h);
+            CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+            INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 1);
+            CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+            INSERT INTO rep_periods_data VALUES (2030, 1, 48, 1);
+        ");
+        let sql: String = build_resolution_query("t", "v", &["asset"], "avg", "24", false, None, "year").unwrap();
+        let periods: Vec<(i64, i64, f64)> = run_query_row(db_path.clone(), sql.clone(), vec![Value::from(2030)], |row| {
+            Ok((row.get::<&str, i64>("global_start")?, row.get::<&str, i64>("global_end")?, row.get::<&str, f64>("y_axis")?))
+        }).unwrap();
+        assert_eq!(periods, vec![(0, 1, 1.0), (1, 2, 2.0)]);
+
+        let sql: String = with_timestamps(&sql, Some("2030-01-01"), false, 24).unwrap();
+        let timeline: Vec<(String, String, f64)> = run_query_row(
+            db_path,
+            format!("SELECT CAST(global_start AS VARCHAR), CAST(global_end AS VARCHAR), y_axis FROM ({}) ORDER BY global_start", sql),
+            vec![Value::from(2030)],
+            |row| Ok((row.get::<usize, String>(0)?, row.get::<usize, String>(1)?, row.get::<usize, f64>(2)?)),
+        ).unwrap();
+
+        assert_eq!(timeline, vec![
+            ("2030-01-01 00:00:00".to_string(), "2030-01-02 00:00:00".to_string(), 1.0),
+            ("2030-01-02 00:00:00".to_string(), "2030-01-03 00:00:00".to_string(), 2.0),
+        ]);
+    }
+
+    #[test]
+    fn with_timestamps_invalid_test() {
+        let invalid = with_timestamps("SELECT 1", Some("2030-01-01' OR 1=1"), false, 1);
+        assert!(invalid.unwrap_err().contains("Invalid start date"));
+        assert!(with_timestamps("SELECT 1", Some("2030-01-01"), true, 1).is_err());
+    }
+}
+
 // --- QUERIES ---
 
+// (name, DuckDB aggregate) of the supported `with_cross_asset_aggregation` aggregations
+const CROSS_ASSET_AGGREGATIONS: [(&str, &str); 2] = [("mean", "AVG"), ("median", "MEDIAN")];
+
+const CROSS_ASSET_AGGREGATION_SQL: &str = "
+    SELECT
+        per_asset.* EXCLUDE (dual_value, {block_start}, {block_end}),
+        h.step AS {block_start},
+        h.step AS {block_end},
+        {agg_fn}(per_asset.dual_value) AS dual_value
+    FROM (
+        {source_sql}
+    ) AS per_asset
+    CROSS JOIN LATERAL generate_series(per_asset.{block_start}, per_asset.{block_end}) AS h(step)
+    GROUP BY ALL";
+
+// the combinations of `build_both_resolutions_sql`
+pub const BOTH_COMBINATIONS: [&str; 3] = ["sum", "avg", "max"];
+
+// the secondary breakdown dimension of `with_secondary_breakdown`: its table aliases (`ac_2_{id}`, `c2_{id}`),
+// its group column and the prefix of its per node columns
+const SECONDARY_BREAKDOWN_ALIAS_PREFIX: &str = "2_";
+const SECONDARY_BREAKDOWN_COLUMN: &str = "asset_group";
+const SECONDARY_BREAKDOWN_COLUMN_PREFIX: &str = "group_breakdown";
+
+// the group of an asset under several grouper nodes with `BreakdownOverlap::Ambiguous`
+pub const AMBIGUOUS_BREAKDOWN_GROUP: &str = "Ambiguous";
+
+// the columns identifying a period of a resolution query, the rows of its pivoted output
+pub const RESOLUTION_PIVOT_INDEX: [&str; 3] = ["milestone_year", "global_start", "global_end"];
+
+// the ordering expressions accepted by `with_resolution_order`, every resolution query returns these columns
+const RESOLUTION_ORDER_BY: [&str; 8] = [
+    "y_axis",
+    "y_axis DESC",
+    "milestone_year, global_start",
+    "milestone_year DESC, global_start",
+    "global_start",
+    "global_start DESC",
+    "milestone_year, y_axis DESC",
+    "milestone_year, y_axis",
+];
+
+const TIMESTAMPS_SQL: &str = "
+SELECT * REPLACE (
+  TIMESTAMP '{start_date}' + to_hours(CAST(global_start * {resolution} AS BIGINT)) AS global_start,
+  TIMESTAMP '{start_date}' + to_hours(CAST(global_end * {resolution} AS BIGINT)) AS global_end
+)
+FROM (
+{sql}
+) AS timestamped_query";
+
+// the periods' start hours are added to a non-leap year, so `global_start` 0 is January 1st
+const SEASONAL_MONTH_SQL: &str = "
+SELECT
+  {series_col},
+  milestone_year,
+  month,
+  AVG(y_axis) AS y_axis
+FROM (
+  SELECT *, month(TIMESTAMP '2001-01-01' + to_hours(CAST(global_start * {resolution} AS BIGINT))) AS month
+  FROM (
+{sql}
+  ) AS seasonal_source
+) AS seasonal_query
+GROUP BY {series_col}, milestone_year, month
+ORDER BY {series_col}, milestone_year, month";
+
+// December, January and February form winter: (month % 12) // 3 numbers the seasons from winter (0) to autumn (3)
+const SEASONAL_SEASON_SQL: &str = "
+SELECT
+  {series_col},
+  milestone_year,
+  CASE season_index WHEN 0 THEN 'winter' WHEN 1 THEN 'spring' WHEN 2 THEN 'summer' ELSE 'autumn' END AS season,
+  AVG(y_axis) AS y_axis
+FROM (
+  SELECT *, (month(TIMESTAMP '2001-01-01' + to_hours(CAST(global_start * {resolution} AS BIGINT))) % 12) // 3 AS season_index
+  FROM (
+{sql}
+  ) AS seasonal_source
+) AS seasonal_query
+GROUP BY {series_col}, milestone_year, season_index
+ORDER BY {series_col}, milestone_year, season_index";
+
 const REP_PERIOD_RESOLUTION_SQL: &str = "
 /* Assigns a group number (grp) to consecutive blocks that have the same {value_col} values
-   within the same {group_cols}, year, and rep_period, ordered by time_block_start (chronologically).
+   within the same {group_cols}, {year_col}, and rep_period, ordered by time_block_start (chronologically).
 */
-WITH numbered AS (
-  SELECT *,
-         ROW_NUMBER() OVER (PARTITION BY {group_cols}, year, rep_period ORDER BY time_block_start) 
-       - ROW_NUMBER() OVER (PARTITION BY {group_cols}, year, rep_period, {value_col} ORDER BY time_block_start) AS grp
-  FROM {source_table}
-),
 /* Aggregates consecutive rows (based on the grp from numbered) into a single time block
    by computing the MIN(time_block_start) and MAX(time_block_end) for each distinct {value_col} group.
 */
-grouped AS (
-  SELECT
-    {group_cols},
-    year,
-    rep_period,
-    {value_col},
-    MIN(time_block_start) AS time_block_start,
-    MAX(time_block_end) AS time_block_end
-  FROM numbered
-  WHERE year = ?
-  GROUP BY {group_cols}, year, rep_period, {value_col}, grp
-),
+WITH {grouped_runs},
 /* Generates all block end times per period by:
     - Including each time_block_end from grouped
     - Adding an extra starting point (0) to aid with boundary calculations
@@ -493,15 +1682,15 @@ grouped AS (
 
 */
 all_blocks AS (
-  SELECT g.year, m.period, g.time_block_end
+  SELECT g.{year_col} AS year, m.period, g.time_block_end
   FROM grouped AS g
   JOIN rep_periods_mapping AS m 
-    ON g.year = m.year AND g.rep_period = m.rep_period
+    ON g.{year_col} = m.year AND g.rep_period = m.rep_period
   UNION
-  SELECT g.year, m.period, 0 AS time_block_end
+  SELECT g.{year_col} AS year, m.period, 0 AS time_block_end
   FROM grouped AS g
   JOIN rep_periods_mapping AS m 
-    ON g.year = m.year AND g.rep_period = m.rep_period
+    ON g.{year_col} = m.year AND g.rep_period = m.rep_period
 ),
 /* Assigns a row number (rn) to each time_block_end value within a given year and period,
    so that we can later pair consecutive time blocks together.
@@ -536,14 +1725,14 @@ periods AS (
 raw AS (
   SELECT
       {group_cols},
-      g.year AS milestone_year,
+      g.{year_col} AS milestone_year,
       m.period,
       (p.start_hour * d.resolution) AS start_hour,
       (p.end_hour * d.resolution) AS end_hour,
       SUM(g.{value_col} * m.weight / d.resolution ) AS y_axis
   FROM grouped AS g
   JOIN rep_periods_mapping AS m
-      ON g.year = m.year AND g.rep_period = m.rep_period
+      ON g.{year_col} = m.year AND g.rep_period = m.rep_period
   JOIN periods AS p
       ON p.year = m.year
       AND p.period = m.period
@@ -553,36 +1742,14 @@ raw AS (
       ON d.year = m.year AND d.rep_period = m.rep_period
   GROUP BY
       {group_cols},
-      g.year,
+      g.{year_col},
       m.period,
       d.resolution,
       p.start_hour,
       p.end_hour
 ),
-/* Again groups consecutive data with the same y_axis values. */
-numbered_blocks AS (
-  SELECT *,
-         ROW_NUMBER() OVER (PARTITION BY {group_cols}, milestone_year, period ORDER BY start_hour) -
-         ROW_NUMBER() OVER (PARTITION BY {group_cols}, milestone_year, period, y_axis ORDER BY start_hour) AS grp
-  FROM raw
-),
-/* Combines the consecutive blocks with the same y_axis values. */
-final_rep_periods AS (
-  SELECT
-    {group_cols},
-    milestone_year,
-    period,
-    MIN(start_hour) AS start_hour,
-    MAX(end_hour) AS end_hour,
-    y_axis
-  FROM numbered_blocks
-  GROUP BY
-    {group_cols},
-    milestone_year,
-    period,
-    y_axis,
-    grp
-),
+/* Again groups consecutive data with the same y_axis values and combines them. */
+{final_rep_periods_runs},
 ";
 
 static CLUSTURED_YEAR_RESOLUTIONS_SQL: &str = "
@@ -591,12 +1758,12 @@ static CLUSTURED_YEAR_RESOLUTIONS_SQL: &str = "
   pre_processed AS (
     SELECT
       {group_cols},
-      year AS milestone_year,
+      {year_col} AS milestone_year,
       period,
       SUM({value_col}) AS y_axis
     FROM {source_table},
     LATERAL UNNEST(GENERATE_SERIES(period_block_start, period_block_end)) AS period(period)
-    WHERE year = ?
+    WHERE {year_col} = ?
     GROUP BY
       {group_cols},
       milestone_year,
@@ -626,6 +1793,24 @@ final AS (
   ),
 ";
 
+// the representative period blocks with the clustered value of their period, then the periods only clustered
+const COMBINED_RESOLUTIONS_SQL: &str = "
+/* Combines the clustered and non-clustered data of the same period into single values.*/
+final AS (
+  SELECT f.* REPLACE ({combination} AS y_axis)
+  FROM final_rep_periods AS f
+  LEFT JOIN final_clustered AS d ON {group_cols_comparisons} AND d.milestone_year = f.milestone_year AND d.period = f.period
+  UNION ALL
+  SELECT d.*
+  FROM final_clustered AS d
+  WHERE NOT EXISTS (
+    SELECT 1
+    FROM final_rep_periods AS f
+    WHERE {group_cols_comparisons} AND f.milestone_year = d.milestone_year AND f.period = d.period
+  )
+  ),
+";
+
 static LAST_PART_SQL: &str = "
   /* Calculates the total duration (in hours) of each period per {group_cols} and milestone_year. */
 period_durations AS (
@@ -684,15 +1869,16 @@ exploded AS (
   FROM s_table
 ),
 /* Generates all resolution periods. For each row in exploded, it creates a series of periods.
-   Each period is defined by its start and end times, which are adjusted to fit within the global_start and global_end.
+   Each period is defined by its start and end hours (period index times the period length), which are adjusted to fit
+   within the global_start and global_end.
 */
 resolution_periods AS (
   SELECT 
     e.{group_cols},
     e.milestone_year,
     p.period,
-    GREATEST(e.global_start, p.period) AS period_start,
-    LEAST(e.global_end, p.period + 1) AS period_end,
+    GREATEST(e.global_start, p.period * {period_length}) AS period_start,
+    LEAST(e.global_end, (p.period + 1) * {period_length}) AS period_end,
     e.y_axis
   FROM exploded e
   JOIN LATERAL (
@@ -730,24 +1916,8 @@ period_avg AS (
     weighted_price / NULLIF(duration, 0) AS y_axis
   FROM weighted
 ),
-/* Again it groups consecutive data with the same y_axis values. */
-numbered_1 AS (
-  SELECT *,
-    ROW_NUMBER() OVER (PARTITION BY {group_cols}, milestone_year ORDER BY global_start_val) -
-    ROW_NUMBER() OVER (PARTITION BY {group_cols}, milestone_year, y_axis ORDER BY global_start_val) AS grp
-  FROM period_avg
-),
-/* Merges the consecutive blocks with the same y_axis values. */
-merged AS (
-  SELECT
-    {group_cols},
-    milestone_year,
-    MIN(global_start_val) AS global_start,
-    MAX(global_end_val) AS global_end,
-    y_axis
-  FROM numbered_1
-  GROUP BY {group_cols}, milestone_year, y_axis, grp
-)
+/* Again it groups consecutive data with the same y_axis values and merges them. */
+{merged_runs}
 /* Returns the final dataset. */
 SELECT *
 FROM merged
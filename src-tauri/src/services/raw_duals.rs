@@ -0,0 +1,77 @@
+use duckdb::arrow::{array::RecordBatch, datatypes::Schema};
+use duckdb::types::Value;
+use tauri::ipc::Response;
+use crate::duckdb_conn::{run_query_rb, serialize_recordbatch};
+
+// the dual values of one asset and year exactly as stored by the solver (no weighting or merging of blocks),
+// to validate the price pipeline; only the constraint tables of `RAW_DUAL_TABLES` can be queried
+#[tauri::command]
+pub fn get_raw_duals(db_path: String, constraint_table: String, asset: String, year: u32) -> Result<Response, String> {
+    let (table, dual_col, time_cols) = RAW_DUAL_TABLES.iter()
+        .find(|(table, _, _)| *table == constraint_table)
+        .ok_or_else(|| format!(
+            "Unsupported constraint table '{}', expected one of: {}",
+            constraint_table,
+            RAW_DUAL_TABLES.iter().map(|(table, _, _)| *table).collect::<Vec<_>>().join(", ")
+        ))?;
+
+    let sql: String = RAW_DUALS_SQL
+        .replace("{table}", table)
+        .replace("{dual_col}", dual_col)
+        .replace("{time_cols}", time_cols);
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, sql, vec![Value::from(asset), Value::from(year)])?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{setup_test_db, response_to_batches, column_f64};
+
+    // equal consecutive duals of 'ccgt' are kept as separate blocks, 'wind' and 2050 are left out
+    const DUALS_FIXTURE_SQL: &str = "
+        CREATE TABLE cons_capacity_outgoing_simple_method(asset VARCHAR, year INTEGER, rep_period INTEGER,
+            time_block_start INTEGER, time_block_end INTEGER, dual_max_output_flows_limit_simple_method DOUBLE);
+        INSERT INTO cons_capacity_outgoing_simple_method VALUES
+            ('ccgt', 2030, 2, 1, 3, 0.5), ('ccgt', 2030, 1, 4, 4, 2.25), ('ccgt', 2030, 1, 1, 3, 2.25),
+            ('wind', 2030, 1, 1, 4, 9), ('ccgt', 2050, 1, 1, 4, 7);
+    ";
+
+    #[test]
+    fn get_raw_duals_verbatim_test() {
+        let db_path: String = setup_test_db("raw_duals_verbatim", DUALS_FIXTURE_SQL);
+        let batches = response_to_batches(get_raw_duals(
+            db_path, "cons_capacity_outgoing_simple_method".to_string(), "ccgt".to_string(), 2030,
+        ).unwrap());
+
+        assert_eq!(column_f64(&batches, "rep_period"), vec![Some(1.0), Some(1.0), Some(2.0)]);
+        assert_eq!(column_f64(&batches, "time_block_start"), vec![Some(1.0), Some(4.0), Some(1.0)]);
+        assert_eq!(column_f64(&batches, "dual_max_output_flows_limit_simple_method"), vec![Some(2.25), Some(2.25), Some(0.5)]);
+    }
+
+    #[test]
+    fn get_raw_duals_unsupported_table_test() {
+        let db_path: String = setup_test_db("raw_duals_unsupported_table", DUALS_FIXTURE_SQL);
+        let res = get_raw_duals(db_path, "asset; DROP TABLE asset".to_string(), "ccgt".to_string(), 2030);
+        assert!(res.err().unwrap().starts_with("Unsupported constraint table"));
+    }
+}
+
+// --- QUERIES ---
+
+// (constraint table, dual column, time block columns) which can be queried raw
+const RAW_DUAL_TABLES: [(&str, &str, &str); 4] = [
+    ("cons_capacity_outgoing_simple_method", "dual_max_output_flows_limit_simple_method", "rep_period, time_block_start, time_block_end"),
+    ("cons_capacity_outgoing_compact_method", "dual_max_output_flows_limit_compact_method", "rep_period, time_block_start, time_block_end"),
+    ("cons_balance_storage_rep_period", "dual_balance_storage_rep_period", "rep_period, time_block_start, time_block_end"),
+    ("cons_balance_storage_over_clustered_year", "dual_balance_storage_over_clustered_year", "period_block_start, period_block_end"),
+];
+
+const RAW_DUALS_SQL: &str = "
+    SELECT asset, year, {time_cols}, {dual_col}
+    FROM {table}
+    WHERE asset = ? AND year = ?
+    ORDER BY {time_cols};
+";
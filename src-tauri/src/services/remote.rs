@@ -0,0 +1,81 @@
+use serde::Deserialize;
+use crate::duckdb_conn::{execute_batch, REMOTE_DB_PATH};
+
+/// Optional S3 credentials, every field left out keeps DuckDB's default.
+#[derive(Deserialize, Default)]
+pub struct S3Credentials {
+    region: Option<String>,
+    access_key_id: Option<String>,
+    secret_access_key: Option<String>,
+    session_token: Option<String>,
+    endpoint: Option<String>,
+}
+
+/// Loads the `httpfs` extension on the remote scratch connection and optionally sets S3 credentials.
+///
+/// Afterwards `run_serialize_query_on_db` can be called with `REMOTE_DB_PATH` (`":remote:"`) as the
+/// database path to query remote sources, e.g. `SELECT * FROM read_parquet('s3://bucket/file.parquet')`.
+///
+/// # Security
+///
+/// * `INSTALL httpfs` downloads the (signed) extension from the DuckDB extension repository on first use.
+/// * Credentials are kept in memory by the scratch connection only, they are never written to disk,
+///   but any query on that connection can use them (including those typed in the query console).
+/// * Any query on the scratch connection can reach arbitrary HTTP(S)/S3 URLs, so only use credentials
+///   scoped to the data that should be visible in the application.
+#[tauri::command]
+pub fn enable_remote_sources(credentials: Option<S3Credentials>) -> Result<(), String> {
+    let settings_sql: String = build_s3_settings_sql(&credentials.unwrap_or_default());
+    execute_batch(REMOTE_DB_PATH.to_string(), format!("{}{}", LOAD_HTTPFS_SQL, settings_sql))
+}
+
+// builds one SET statement per provided credential, escaping quotes in the values
+fn build_s3_settings_sql(credentials: &S3Credentials) -> String {
+    [
+        ("s3_region", &credentials.region),
+        ("s3_access_key_id", &credentials.access_key_id),
+        ("s3_secret_access_key", &credentials.secret_access_key),
+        ("s3_session_token", &credentials.session_token),
+        ("s3_endpoint", &credentials.endpoint),
+    ]
+    .into_iter()
+    .filter_map(|(setting, value)| {
+        value.as_ref().map(|v| format!("SET {} = '{}';\n", setting, v.replace('\'', "''")))
+    })
+    .collect()
+}
+
+// --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // loading httpfs requires network access, so only the generated credential statements are tested
+    #[test]
+    fn build_s3_settings_sql_test() {
+        let credentials = S3Credentials {
+            region: Some("eu-west-1".to_string()),
+            access_key_id: Some("KEY".to_string()),
+            secret_access_key: Some("se'cret".to_string()),
+            ..Default::default()
+        };
+        let sql: String = build_s3_settings_sql(&credentials);
+
+        assert!(sql.contains("SET s3_region = 'eu-west-1';"));
+        assert!(sql.contains("SET s3_access_key_id = 'KEY';"));
+        assert!(sql.contains("SET s3_secret_access_key = 'se''cret';"), "quote not escaped: {}", sql);
+        assert!(!sql.contains("s3_session_token"));
+        assert!(!sql.contains("s3_endpoint"));
+    }
+
+    #[test]
+    fn build_s3_settings_sql_empty_test() {
+        assert_eq!(build_s3_settings_sql(&S3Credentials::default()), "");
+    }
+}
+
+// --- QUERIES ---
+const LOAD_HTTPFS_SQL: &str = "
+INSTALL httpfs;
+LOAD httpfs;
+";
@@ -3,11 +3,19 @@ use duckdb::types::Value;
 use tauri::ipc::Response;
 use std::collections::HashMap;
 
-use crate::services::query_builder::{build_resolution_query_with_filters, 
-  build_resolution_query_with_filters_and_breakdown, 
+use crate::services::query_builder::{build_breakdown_case_conditions,
   build_breakdown_columns,
-  build_resolution_query};
-use crate::duckdb_conn::{run_query_rb, serialize_recordbatch};
+  build_breakdown_group_by,
+  build_breakdown_joins,
+  build_breakdown_selects,
+  build_filter_conditions,
+  build_resolution_query,
+  with_category_descendants,
+  with_seasonal,
+  with_timestamps,
+  SeasonMode};
+use crate::duckdb_conn::{run_query_rb, serialize_recordbatch_with_metadata, RESOLUTION_METADATA_KEY};
+use crate::services::metadata::{check_series_cap, ensure_metadata_available, resolve_resolution};
 
 #[tauri::command]
 pub fn get_supply(
@@ -16,63 +24,211 @@ pub fn get_supply(
     resolution: u32,
     filters: HashMap<i32, Vec<i32>>,
     grouper: Vec<i32>,
-    enable_metadata: bool
+    enable_metadata: bool,
+    seasonal: Option<SeasonMode>,
+    exclude_conversion: Option<bool>,
+    subtract_storage: Option<bool>,
+    start_date: Option<String>,
+    max_series: Option<u32>
 ) -> Result<Response, String> {
-    
-    let sql;
-    let has_breakdown = !grouper.is_empty();
-
-    if enable_metadata {
-      if has_breakdown {
-        // With filters and breakdown
-        let breakdown_cols = build_breakdown_columns(&grouper);
-        sql = build_resolution_query_with_filters_and_breakdown(
-            SUPPLY_SQL_WITH_FILTERS_AND_BREAKDOWN,
-            "solution",
-            &breakdown_cols,
-            "sum",
-            &resolution.to_string(),
-            &filters,
-            &grouper,
-            "ac.asset".to_string(),
-            "bf.from_asset".to_string(),
-            false
-        )
-      } else {
-        // With filters without breakdown
-        sql = build_resolution_query_with_filters(
-            SUPPLY_SQL_WITH_FILTERS,
-            "solution",
-            &["asset"],
-            "sum",
-            &resolution.to_string(),
-            &filters,
-            "ac.asset".to_string(),
-            false
-        );
-    } 
+  if enable_metadata {
+    ensure_metadata_available(&db_path)?;
+  }
+  let resolution: u32 = resolve_resolution(&db_path, year, resolution)?;
+
+  let sql: String = build_supply_sql(resolution, filters, &grouper, enable_metadata, exclude_conversion, subtract_storage)?;
+  check_series_cap(&db_path, &sql, "asset", vec![Value::from(year)], max_series)?;
+  let sql: String = with_seasonal(&sql, "asset", seasonal, None, false, resolution)?;
+  let sql: String = with_timestamps(&sql, start_date.as_deref(), seasonal.is_some(), resolution)?;
+  let res: (Vec<RecordBatch>, Schema) =
+      run_query_rb(db_path, sql, vec![Value::from(year)])?;
+  let metadata: HashMap<String, String> = HashMap::from([(RESOLUTION_METADATA_KEY.to_string(), resolution.to_string())]);
+  serialize_recordbatch_with_metadata(res.0, res.1, metadata)
+}
+
+// the supply resolution query shared by `get_supply` and `get_supply_duration_curve`
+fn build_supply_sql(
+    resolution: u32,
+    filters: HashMap<i32, Vec<i32>>,
+    grouper: &[i32],
+    enable_metadata: bool,
+    exclude_conversion: Option<bool>,
+    subtract_storage: Option<bool>,
+) -> Result<String, String> {
+  let (supply_sql, group_cols): (String, Vec<String>) = if enable_metadata && !grouper.is_empty() {
+    // With filters and breakdown
+    let supply_sql: String = SUPPLY_SQL_WITH_FILTERS_AND_BREAKDOWN
+        .replace("{filter_conditions}", &build_filter_conditions(&filters, "ac.asset".to_string()))
+        .replace("{breakdown_case_conditions}", &build_breakdown_case_conditions(grouper, "bf.from_asset".to_string()))
+        .replace("{breakdown_selects}", &build_breakdown_selects(grouper))
+        .replace("{breakdown_joins}", &build_breakdown_joins(grouper))
+        .replace("{breakdown_group_by}", &build_breakdown_group_by(grouper));
+    (supply_sql, build_breakdown_columns(grouper))
+  } else if enable_metadata {
+    // With filters without breakdown
+    let supply_sql: String = SUPPLY_SQL_WITH_FILTERS
+        .replace("{filter_conditions}", &build_filter_conditions(&filters, "ac.asset".to_string()));
+    (supply_sql, vec!["asset".to_string()])
   } else {
     // Without categories
-    sql = build_resolution_query(
-        SUPPLY_SQL_WITHOUT_FILTERS,
-        "solution",
-        &["asset"],
-        "sum",
-        &resolution.to_string(),
-        false
-    );
+    (SUPPLY_SQL_WITHOUT_FILTERS.to_string(), vec!["asset".to_string()])
+  };
+  let group_cols: Vec<&str> = group_cols.iter().map(String::as_str).collect();
+  let resolution_sql: String = build_resolution_query(
+      "supply_table",
+      "solution",
+      &group_cols,
+      "sum",
+      &resolution.to_string(),
+      false,
+      None,
+      "year"
+  )?;
+
+  // supply only counts flows into consumers, so excluding conversion drops what the conversion assets deliver
+  // from the supply altogether (it is not counted as supply of their inputs either), leaving the direct supply of
+  // the other assets; storage discharge only shifts energy that was supplied before
+  let mut excluded_types: Vec<&str> = Vec::new();
+  if exclude_conversion.unwrap_or(false) {
+    excluded_types.push("'conversion'");
   }
+  if subtract_storage.unwrap_or(false) {
+    excluded_types.push("'storage'");
+  }
+  let source_type_filter: String = if excluded_types.is_empty() {
+    String::new()
+  } else {
+    EXCLUDE_SOURCE_TYPES_SQL.replace("{source_types}", &excluded_types.join(", "))
+  };
+  let sql: String = SUPPLY_RESOLUTION_SQL
+      .replace("{supply_sql}", &supply_sql.replace("{source_type_filter}", &source_type_filter))
+      .replace("{resolution_query}", resolution_sql.trim_end().trim_end_matches(';'));
+  let shared_filters: HashMap<i32, Vec<i32>> = if enable_metadata { filters } else { HashMap::new() };
+  Ok(with_category_descendants(&sql, &shared_filters, grouper))
+}
+
+// load duration curve of the total supply: the supply of every resolution period sorted descending, against the
+// cumulative duration (in hours) of the periods supplying at least that level
+#[tauri::command]
+pub fn get_supply_duration_curve(
+    db_path: String,
+    year: u32,
+    resolution: u32,
+    filters: HashMap<i32, Vec<i32>>,
+    enable_metadata: bool,
+    exclude_conversion: Option<bool>,
+    subtract_storage: Option<bool>
+) -> Result<Response, String> {
+  if enable_metadata {
+    ensure_metadata_available(&db_path)?;
+  }
+  let resolution: u32 = resolve_resolution(&db_path, year, resolution)?;
+
+  let supply_sql: String = build_supply_sql(resolution, filters, &[], enable_metadata, exclude_conversion, subtract_storage)?;
+  let sql: String = SUPPLY_DURATION_CURVE_SQL
+      .replace("{supply_sql}", supply_sql.trim().trim_end_matches(';'))
+      .replace("{resolution}", &resolution.to_string());
   let res: (Vec<RecordBatch>, Schema) =
       run_query_rb(db_path, sql, vec![Value::from(year)])?;
-  serialize_recordbatch(res.0, res.1)
+  let metadata: HashMap<String, String> = HashMap::from([(RESOLUTION_METADATA_KEY.to_string(), resolution.to_string())]);
+  serialize_recordbatch_with_metadata(res.0, res.1, metadata)
 }
 
 // --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{setup_test_db, setup_tulipa_db, response_to_batches, column_f64, column_string};
+
+    // 'wind', the gas fired 'ccgt' and the discharging 'battery' supply 'demand', 'gas_well' only feeds the 'ccgt'
+    const SUPPLY_FIXTURE_SQL: &str = "
+        CREATE TABLE asset(asset VARCHAR, type VARCHAR);
+        INSERT INTO asset VALUES ('wind', 'producer'), ('gas_well', 'producer'), ('ccgt', 'conversion'), ('battery', 'storage'), ('demand', 'consumer');
+        CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, solution DOUBLE);
+        INSERT INTO var_flow VALUES ('wind', 'demand', 2030, 1, 1, 1, 3), ('gas_well', 'ccgt', 2030, 1, 1, 1, 4), ('ccgt', 'demand', 2030, 1, 1, 1, 2),
+            ('battery', 'demand', 2030, 1, 1, 1, 1);
+        CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+        INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 1);
+        CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+        INSERT INTO rep_periods_data VALUES (2030, 1, 1, 1);
+    ";
+
+    #[test]
+    fn get_supply_exclude_conversion_test() {
+        let db_path: String = setup_test_db("supply_exclude_conversion", SUPPLY_FIXTURE_SQL);
+        let assets = |exclude_conversion: Option<bool>| -> Vec<Option<String>> {
+            column_string(&response_to_batches(get_supply(
+                db_path.clone(), 2030, 1, HashMap::new(), vec![], false, None, exclude_conversion, None, None, None,
+            ).unwrap()), "asset")
+        };
+
+        assert_eq!(assets(None), vec![Some("battery".to_string()), Some("ccgt".to_string()), Some("wind".to_string())]);
+        assert_eq!(assets(Some(true)), vec![Some("battery".to_string()), Some("wind".to_string())]);
+    }
+
+    #[test]
+    fn get_supply_exclude_conversion_subtract_storage_test() {
+        let db_path: String = setup_test_db("supply_exclude_conversion_subtract_storage", SUPPLY_FIXTURE_SQL);
+        let batches = response_to_batches(get_supply(
+            db_path, 2030, 1, HashMap::new(), vec![], false, None, Some(true), Some(true), None, None,
+        ).unwrap());
+
+        assert_eq!(column_string(&batches, "asset"), vec![Some("wind".to_string())]);
+        assert_eq!(column_f64(&batches, "y_axis"), vec![Some(3.0)]);
+    }
+
+    #[test]
+    fn get_supply_subtract_storage_test() {
+        let db_path: String = setup_test_db("supply_subtract_storage", SUPPLY_FIXTURE_SQL);
+        let supply = |subtract_storage: Option<bool>| -> (Vec<Option<String>>, f64) {
+            let batches = response_to_batches(get_supply(
+                db_path.clone(), 2030, 1, HashMap::new(), vec![], false, None, None, subtract_storage, None, None,
+            ).unwrap());
+            (column_string(&batches, "asset"), column_f64(&batches, "y_axis").into_iter().flatten().sum())
+        };
+
+        assert_eq!(supply(None), (vec![Some("battery".to_string()), Some("ccgt".to_string()), Some("wind".to_string())], 6.0));
+        assert_eq!(supply(Some(true)), (vec![Some("ccgt".to_string()), Some("wind".to_string())], 5.0));
+    }
+
+    #[test]
+    fn get_supply_duration_curve_test() {
+        // the fixture year spans two hourly blocks, with a supply of 4 then 6
+        let db_path: String = setup_tulipa_db("supply_duration_curve", "");
+        let batches = response_to_batches(get_supply_duration_curve(db_path, 2030, 1, HashMap::new(), false, None, None).unwrap());
+        let supply: Vec<f64> = column_f64(&batches, "supply").into_iter().flatten().collect();
+        let duration: Vec<f64> = column_f64(&batches, "duration").into_iter().flatten().collect();
+
+        assert_eq!(supply, vec![6.0, 4.0]);
+        assert!(supply.windows(2).all(|w| w[0] >= w[1]));
+        // the curve ends at the year's two hours
+        assert_eq!(duration, vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn get_supply_tulipa_fixture_test() {
+        // all supply reaches 'demand' through 'hub_be'
+        let db_path: String = setup_tulipa_db("supply_tulipa_fixture", "");
+        let batches = response_to_batches(get_supply(db_path, 2030, 1, HashMap::new(), vec![], false, None, None, None, None, None).unwrap());
+
+        assert_eq!(column_string(&batches, "asset"), vec![Some("hub_be".to_string()), Some("hub_be".to_string())]);
+        assert_eq!(column_f64(&batches, "y_axis"), vec![Some(4.0), Some(6.0)]);
+    }
+}
 
 // --- QUERIES ---
+const EXCLUDE_SOURCE_TYPES_SQL: &str = "
+      AND f.from_asset NOT IN (SELECT asset FROM asset WHERE type IN ({source_types}))";
+
+const SUPPLY_RESOLUTION_SQL: &str = "
+WITH supply_table AS (
+{supply_sql}
+)
+SELECT * FROM (
+{resolution_query}
+) AS subquery";
 
 const SUPPLY_SQL_WITHOUT_FILTERS: &str = "
-  (
     SELECT
       f.from_asset AS asset,
       f.year,
@@ -83,11 +239,9 @@ const SUPPLY_SQL_WITHOUT_FILTERS: &str = "
       a.type
     FROM var_flow AS f
     JOIN asset AS a ON f.to_asset = a.asset
-    WHERE a.type = 'consumer'
-  ) AS supply_flows";
+    WHERE a.type = 'consumer'{source_type_filter}";
 
 const SUPPLY_SQL_WITH_FILTERS: &str = "
-  (
     SELECT DISTINCT
       f.from_asset AS asset,
       f.year,
@@ -99,12 +253,10 @@ const SUPPLY_SQL_WITH_FILTERS: &str = "
     FROM var_flow AS f
     JOIN asset AS a ON f.to_asset = a.asset
     JOIN asset_category AS ac ON f.from_asset = ac.asset
-    WHERE a.type = 'consumer'
-      {filter_conditions} 
-  ) AS supply_flows";
+    WHERE a.type = 'consumer'{source_type_filter}
+      {filter_conditions}";
 
 const SUPPLY_SQL_WITH_FILTERS_AND_BREAKDOWN: &str = "
-  (
     -- The sql above:
     WITH base_flows AS (
       SELECT DISTINCT
@@ -117,7 +269,7 @@ const SUPPLY_SQL_WITH_FILTERS_AND_BREAKDOWN: &str = "
       FROM var_flow AS f
       JOIN asset AS a ON f.to_asset = a.asset
       JOIN asset_category AS ac ON f.from_asset = ac.asset
-      WHERE a.type = 'consumer'
+      WHERE a.type = 'consumer'{source_type_filter}
         {filter_conditions}
     )
     SELECT
@@ -142,5 +294,26 @@ const SUPPLY_SQL_WITH_FILTERS_AND_BREAKDOWN: &str = "
       bf.year,
       bf.time_block_start,
       bf.time_block_end,
-      bf.rep_period{breakdown_group_by}
-  ) AS supply_flows";
+      bf.rep_period{breakdown_group_by}";
+
+const SUPPLY_DURATION_CURVE_SQL: &str = "
+WITH supply AS (
+{supply_sql}
+),
+/* Spreads the merged runs back over their resolution periods, summing all supplying assets. */
+periods AS (
+  SELECT
+    UNNEST(generate_series(CAST(global_start AS BIGINT), CAST(global_end AS BIGINT) - 1)) AS period,
+    y_axis
+  FROM supply
+),
+period_supply AS (
+  SELECT period, SUM(y_axis) AS supply
+  FROM periods
+  GROUP BY period
+)
+SELECT
+  CAST(SUM({resolution}) OVER (ORDER BY supply DESC, period ROWS UNBOUNDED PRECEDING) AS DOUBLE) AS duration,
+  supply
+FROM period_supply
+ORDER BY duration;";
\ No newline at end of file
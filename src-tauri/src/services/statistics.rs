@@ -0,0 +1,196 @@
+use duckdb::{ arrow::{array::RecordBatch, datatypes::Schema}, types::Value, Row };
+use tauri::ipc::Response;
+use crate::duckdb_conn::{run_query_rb, run_query_row, serialize_recordbatch};
+use crate::services::query_builder::validate_ident;
+
+// summary statistics of one column, non-numeric columns only get count, nulls and distinct count
+#[tauri::command]
+pub fn get_column_stats(db_path: String, table: String, column: String) -> Result<Response, String> {
+    let table: &str = validate_ident(&table)?;
+    let column: &str = validate_ident(&column)?;
+
+    let stats_sql: &str = if is_numeric_column(db_path.clone(), table, column)? {
+        NUMERIC_STATS_SQL
+    } else {
+        GENERIC_STATS_SQL
+    };
+
+    let sql: String = stats_sql
+        .replace("{table}", table)
+        .replace("{column}", column);
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, sql, vec![])?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// equal-width histogram of a numeric column between its min and max, empty bins are included
+#[tauri::command]
+pub fn get_column_histogram(db_path: String, table: String, column: String, bins: u32) -> Result<Response, String> {
+    let table: &str = validate_ident(&table)?;
+    let column: &str = validate_ident(&column)?;
+    if bins < 1 {
+        return Err("Number of bins must be at least 1".to_string());
+    }
+    if !is_numeric_column(db_path.clone(), table, column)? {
+        return Err(format!("Column '{}' of table '{}' is not numeric", column, table));
+    }
+
+    let sql: String = HISTOGRAM_SQL
+        .replace("{table}", table)
+        .replace("{column}", column);
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, sql, vec![Value::from(bins)])?;
+
+    return serialize_recordbatch(res.0, res.1);
+}
+
+// looks up the column type, erroring if the column does not exist
+fn is_numeric_column(db_path: String, table: &str, column: &str) -> Result<bool, String> {
+    let data_types: Vec<String> = run_query_row(
+        db_path,
+        COLUMN_TYPE_SQL.to_string(),
+        vec![Value::from(table.to_string()), Value::from(column.to_string())],
+        |row: &Row<'_>| Ok(row.get::<usize, String>(0)?),
+    )?;
+
+    let data_type: &String = data_types.first()
+        .ok_or_else(|| format!("Column '{}' not found in table '{}'", column, table))?;
+
+    Ok(NUMERIC_TYPES.contains(&data_type.as_str()) || data_type.starts_with("DECIMAL"))
+}
+
+// --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{setup_test_db, response_to_batches, column_f64};
+
+    const DEMO_FIXTURE_SQL: &str = "
+        CREATE TABLE demoTable(id INTEGER, name VARCHAR);
+        INSERT INTO demoTable VALUES (1, 'Belgium'), (2, 'Netherlands'), (3, 'France'), (4, 'Germany'), (NULL, 'Germany');
+    ";
+
+    #[test]
+    fn get_column_stats_numeric_test() {
+        let db_path: String = setup_test_db("column_stats_numeric", DEMO_FIXTURE_SQL);
+        let batches = response_to_batches(get_column_stats(db_path, "demoTable".to_string(), "id".to_string()).unwrap());
+
+        assert_eq!(column_f64(&batches, "count"), vec![Some(4.0)]);
+        assert_eq!(column_f64(&batches, "nulls"), vec![Some(1.0)]);
+        assert_eq!(column_f64(&batches, "min"), vec![Some(1.0)]);
+        assert_eq!(column_f64(&batches, "max"), vec![Some(4.0)]);
+        assert_eq!(column_f64(&batches, "avg"), vec![Some(2.5)]);
+        let stddev: f64 = column_f64(&batches, "stddev")[0].unwrap();
+        assert!((stddev - (5.0_f64 / 3.0).sqrt()).abs() < 1e-9, "unexpected stddev: {}", stddev);
+    }
+
+    #[test]
+    fn get_column_stats_non_numeric_test() {
+        let db_path: String = setup_test_db("column_stats_non_numeric", DEMO_FIXTURE_SQL);
+        let batches = response_to_batches(get_column_stats(db_path, "demoTable".to_string(), "name".to_string()).unwrap());
+
+        assert_eq!(column_f64(&batches, "count"), vec![Some(5.0)]);
+        assert_eq!(column_f64(&batches, "distinct_count"), vec![Some(4.0)]);
+        assert!(batches[0].column_by_name("avg").is_none());
+    }
+
+    const DISTRIBUTION_FIXTURE_SQL: &str = "
+        CREATE TABLE distribution(value DOUBLE, label VARCHAR);
+        INSERT INTO distribution SELECT i, 'v' || i FROM range(1, 11) AS t(i);
+    ";
+
+    #[test]
+    fn get_column_histogram_test() {
+        let db_path: String = setup_test_db("column_histogram", DISTRIBUTION_FIXTURE_SQL);
+        let batches = response_to_batches(get_column_histogram(db_path, "distribution".to_string(), "value".to_string(), 3).unwrap());
+
+        let counts: Vec<f64> = column_f64(&batches, "count").into_iter().flatten().collect();
+        assert_eq!(counts, vec![3.0, 3.0, 4.0]);
+        assert_eq!(counts.iter().sum::<f64>(), 10.0);
+        assert_eq!(column_f64(&batches, "bin_lower"), vec![Some(1.0), Some(4.0), Some(7.0)]);
+        assert_eq!(column_f64(&batches, "bin_upper"), vec![Some(4.0), Some(7.0), Some(10.0)]);
+    }
+
+    #[test]
+    fn get_column_histogram_invalid_bins_test() {
+        let db_path: String = setup_test_db("column_histogram_bins", DISTRIBUTION_FIXTURE_SQL);
+        let res = get_column_histogram(db_path, "distribution".to_string(), "value".to_string(), 0);
+        assert!(res.is_err() && res.err().unwrap().contains("at least 1"));
+    }
+
+    #[test]
+    fn get_column_histogram_non_numeric_test() {
+        let db_path: String = setup_test_db("column_histogram_non_numeric", DISTRIBUTION_FIXTURE_SQL);
+        let res = get_column_histogram(db_path, "distribution".to_string(), "label".to_string(), 3);
+        assert!(res.is_err() && res.err().unwrap().contains("not numeric"));
+    }
+
+    #[test]
+    fn get_column_stats_invalid_identifier_test() {
+        let db_path: String = setup_test_db("column_stats_invalid", DEMO_FIXTURE_SQL);
+        let res = get_column_stats(db_path, "demoTable; DROP TABLE demoTable".to_string(), "id".to_string());
+        assert!(res.is_err() && res.err().unwrap().contains("Invalid identifier"));
+    }
+}
+
+// --- QUERIES ---
+const NUMERIC_TYPES: [&str; 14] = [
+    "TINYINT", "SMALLINT", "INTEGER", "BIGINT", "HUGEINT",
+    "UTINYINT", "USMALLINT", "UINTEGER", "UBIGINT", "UHUGEINT",
+    "FLOAT", "DOUBLE", "REAL", "VARINT",
+];
+
+const COLUMN_TYPE_SQL: &str = "
+    SELECT data_type
+    FROM information_schema.columns
+    WHERE table_name = ? AND column_name = ?;
+";
+
+const NUMERIC_STATS_SQL: &str = "
+    SELECT
+        COUNT({column}) AS count,
+        COUNT(*) - COUNT({column}) AS nulls,
+        COUNT(DISTINCT {column}) AS distinct_count,
+        MIN({column}) AS min,
+        MAX({column}) AS max,
+        AVG({column}) AS avg,
+        STDDEV_SAMP({column}) AS stddev
+    FROM {table};
+";
+
+const GENERIC_STATS_SQL: &str = "
+    SELECT
+        COUNT({column}) AS count,
+        COUNT(*) - COUNT({column}) AS nulls,
+        COUNT(DISTINCT {column}) AS distinct_count
+    FROM {table};
+";
+
+const HISTOGRAM_SQL: &str = "
+    WITH bounds AS (
+        SELECT
+            CAST(MIN({column}) AS DOUBLE) AS lo,
+            CAST(MAX({column}) AS DOUBLE) AS hi
+        FROM {table}
+    ), params AS ( -- a constant column ends up entirely in the first bin
+        SELECT lo, CASE WHEN hi > lo THEN (hi - lo) / $1 ELSE 1 END AS width
+        FROM bounds
+        WHERE lo IS NOT NULL
+    ), buckets AS ( -- the maximum belongs to the last bin
+        SELECT CAST(LEAST(FLOOR(({column} - p.lo) / p.width), $1 - 1) AS BIGINT) AS bin
+        FROM {table}
+        CROSS JOIN params p
+        WHERE {column} IS NOT NULL
+    ), all_bins AS (
+        SELECT UNNEST(generate_series(0, CAST($1 AS BIGINT) - 1)) AS bin
+    )
+    SELECT
+        ab.bin,
+        p.lo + ab.bin * p.width AS bin_lower,
+        p.lo + (ab.bin + 1) * p.width AS bin_upper,
+        COUNT(b.bin) AS count
+    FROM all_bins ab
+    CROSS JOIN params p
+    LEFT JOIN buckets b ON b.bin = ab.bin
+    GROUP BY ab.bin, p.lo, p.width
+    ORDER BY ab.bin;
+";
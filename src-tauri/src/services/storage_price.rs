@@ -1,14 +1,14 @@
 use duckdb::arrow::{array::RecordBatch, datatypes::Schema};
 use duckdb::{ types::Value};
 use tauri::ipc::Response;
-use crate::duckdb_conn::{run_query_rb, serialize_recordbatch};
+use crate::duckdb_conn::{debug_sql_metadata, run_query_rb, serialize_recordbatch_with_metadata, RESOLUTION_METADATA_KEY};
 use crate::services::query_builder::{build_resolution_query, build_resolution_query_both, 
     build_resolution_query_with_filters, build_resolution_query_with_filters_and_breakdown,
     build_resolution_query_with_filters_and_breakdown_both,
     build_breakdown_columns, build_breakdown_joins, build_breakdown_case_conditions,
     build_resolution_query_with_filters_both, build_breakdown_selects, build_breakdown_group_by,
-    build_filter_conditions};
-use crate::services::metadata::{check_column_in_table, apply_carrier_filter};
+    build_filter_conditions, with_category_descendants, with_cross_asset_aggregation, with_resolution_order, with_rounding, with_seasonal, with_clamped_negatives, with_timestamps, with_zeroed_values, build_pivot_query, RESOLUTION_PIVOT_INDEX, BOTH_COMBINATIONS, ResolutionOptions};
+use crate::services::metadata::{check_column_in_table, check_series_cap, apply_carrier_filter, ensure_metadata_available, fetch_pivot_series, resolve_resolution};
 use std::collections::HashMap;
 
 
@@ -22,21 +22,35 @@ pub fn get_storage_price_resolution(
     carrier: String,
     filters: HashMap<i32, Vec<i32>>,
     grouper: Vec<i32>,
-    enable_metadata: bool
+    enable_metadata: bool,
+    options: Option<ResolutionOptions>,
 ) -> Result<Response, String> {
+    let options: ResolutionOptions = options.unwrap_or_default();
+    // how the short- and long-term prices of the same period are combined, only with both storage types
+    if let Some(combine) = options.combine.as_deref() {
+        if !BOTH_COMBINATIONS.contains(&combine) {
+            return Err(format!("Invalid combination '{}', expected one of: {}", combine, BOTH_COMBINATIONS.join(", ")));
+        }
+    }
+    if enable_metadata {
+        ensure_metadata_available(&db_path)?;
+    }
+    let resolution: u32 = resolve_resolution(&db_path, year, resolution)?;
 
     let has_breakdown = !grouper.is_empty();
     
     let short_term_sql = if !check_column_in_table(db_path.clone(), "cons_balance_storage_rep_period", "dual_balance_storage_rep_period")? {
                 EMPTY_SHORT_TERM_SQL.to_string()
             } else {
-                apply_carrier_filter(SHORT_TERM_SQL, &carrier)
+                with_zeroed_values(&apply_carrier_filter(SHORT_TERM_SQL, &carrier), &["dual_value"], options.zero_epsilon)?
             };
     let long_term_sql = if !check_column_in_table(db_path.clone(), "cons_balance_storage_over_clustered_year", "dual_balance_storage_over_clustered_year")? {
                 EMPTY_LONG_TERM_SQL.to_string()
             } else {
-                apply_carrier_filter(LONG_TERM_SQL, &carrier)
+                with_zeroed_values(&apply_carrier_filter(LONG_TERM_SQL, &carrier), &["dual_value"], options.zero_epsilon)?
             };
+    let short_term_sql: String = with_cross_asset_aggregation(&short_term_sql, options.asset_aggregation.as_deref(), ("time_block_start", "time_block_end"))?;
+    let long_term_sql: String = with_cross_asset_aggregation(&long_term_sql, options.asset_aggregation.as_deref(), ("period_block_start", "period_block_end"))?;
     let pre_table_sql = match storage_type.as_str() {
         "short-term" => format!(" WITH storage_table AS ({})", short_term_sql),
         "long-term" => format!(" WITH storage_table AS ({})", long_term_sql),
@@ -68,8 +82,10 @@ pub fn get_storage_price_resolution(
                     &grouper,
                     "asset".to_string(),
                     "asset".to_string(),
-                    false
-                ).trim_end_matches(';').trim_end().to_string(),
+                    false,
+                    options.merge_digits,
+                    "year"
+                )?.trim_end_matches(';').trim_end().to_string(),
                 "long-term" => build_resolution_query_with_filters_and_breakdown(
                     "storage_table",
                     "dual_value",
@@ -80,8 +96,10 @@ pub fn get_storage_price_resolution(
                     &grouper,
                     "asset".to_string(),
                     "asset".to_string(),
-                    true
-                ).trim_end_matches(';').trim_end().to_string(),
+                    true,
+                    options.merge_digits,
+                    "year"
+                )?.trim_end_matches(';').trim_end().to_string(),
                 "both" => build_resolution_query_with_filters_and_breakdown_both(
                     "storage_table",
                     "storage_table_1",
@@ -93,7 +111,10 @@ pub fn get_storage_price_resolution(
                     &grouper,
                     "asset".to_string(),
                     "asset".to_string(),
-                ).trim_end_matches(';').trim_end().to_string(),
+                    options.merge_digits,
+                    "year",
+                    options.combine.as_deref(),
+                )?.trim_end_matches(';').trim_end().to_string(),
                 _ => return Err("Invalid storage type".to_string()),
             };
         } else {
@@ -107,8 +128,10 @@ pub fn get_storage_price_resolution(
                     &resolution.to_string(),
                     &filters,
                     "asset".to_string(),
-                    false
-                ).trim_end_matches(';').trim_end().to_string(),
+                    false,
+                    options.merge_digits,
+                    "year"
+                )?.trim_end_matches(';').trim_end().to_string(),
                 "long-term" => build_resolution_query_with_filters(
                     "storage_table",
                     "dual_value",
@@ -117,8 +140,10 @@ pub fn get_storage_price_resolution(
                     &resolution.to_string(),
                     &filters,
                     "asset".to_string(),
-                    true
-                ).trim_end_matches(';').trim_end().to_string(),
+                    true,
+                    options.merge_digits,
+                    "year"
+                )?.trim_end_matches(';').trim_end().to_string(),
                 "both" => build_resolution_query_with_filters_both(
                     "storage_table",
                     "storage_table_1",
@@ -128,7 +153,10 @@ pub fn get_storage_price_resolution(
                     &resolution.to_string(),
                     &filters,
                     "asset".to_string(),
-                ).trim_end_matches(';').trim_end().to_string(),
+                    options.merge_digits,
+                    "year",
+                    options.combine.as_deref(),
+                )?.trim_end_matches(';').trim_end().to_string(),
                 _ => return Err("Invalid storage type".to_string()),
             }
         }
@@ -143,7 +171,9 @@ pub fn get_storage_price_resolution(
         "avg",
         &resolution.to_string(),
         false,
-    ).trim_end_matches(';').trim_end().to_string(),
+        options.merge_digits,
+        "year",
+    )?.trim_end_matches(';').trim_end().to_string(),
     "long-term" => build_resolution_query(
         "storage_table",
         "dual_value",
@@ -151,7 +181,9 @@ pub fn get_storage_price_resolution(
         "avg",
         &resolution.to_string(),
         true,
-    ).trim_end_matches(';').trim_end().to_string(),
+        options.merge_digits,
+        "year",
+    )?.trim_end_matches(';').trim_end().to_string(),
     "both" => build_resolution_query_both(
         "storage_table",
         "storage_table_1",
@@ -159,7 +191,10 @@ pub fn get_storage_price_resolution(
         &["asset"],
         "avg",
         &resolution.to_string(),
-    ).trim_end_matches(';').trim_end().to_string(),
+        options.merge_digits,
+        "year",
+        options.combine.as_deref(),
+    )?.trim_end_matches(';').trim_end().to_string(),
     _ => return Err("Invalid storage type".to_string()),};
     }
 
@@ -184,17 +219,89 @@ pub fn get_storage_price_resolution(
     } else {
      wrapped_sql = temp_wrapped_sql.replace("{filtered_assets}", &"SELECT * FROM asset")
     }
-    let res: (Vec<RecordBatch>, Schema) = match storage_type.as_str() {
-        "short-term" | "long-term" => run_query_rb(db_path, wrapped_sql, vec![Value::from(year)])?,
-        "both" => run_query_rb(db_path, wrapped_sql, vec![Value::from(year), Value::from(year)])?,
+    let shared_filters: HashMap<i32, Vec<i32>> = if enable_metadata { filters } else { HashMap::new() };
+    let wrapped_sql: String = with_category_descendants(&wrapped_sql, &shared_filters, &grouper);
+    let args: Vec<Value> = match storage_type.as_str() {
+        "short-term" | "long-term" => vec![Value::from(year)],
+        "both" => vec![Value::from(year), Value::from(year)],
         _ => return Err("Invalid storage type".to_string())   
     };
+    check_series_cap(&db_path, &wrapped_sql, "asset", args.clone(), options.max_series)?;
+    let wrapped_sql: String = with_seasonal(&wrapped_sql, "asset", options.seasonal, options.order_by.as_deref(), options.pivot, resolution)?;
+    let wrapped_sql: String = with_clamped_negatives(&wrapped_sql, &["y_axis"], options.clamp_negative)?;
+    let wrapped_sql: String = with_rounding(&wrapped_sql, &["y_axis"], options.round_digits)?;
+    let wrapped_sql: String = with_timestamps(&wrapped_sql, options.start_date.as_deref(), options.seasonal.is_some(), resolution)?;
+    let wrapped_sql: String = if options.seasonal.is_some() {
+        wrapped_sql
+    } else if options.pivot {
+        if options.order_by.is_some() {
+            return Err("Custom ordering is not supported on pivoted results".to_string());
+        }
+        let series: Vec<String> = fetch_pivot_series(&db_path, &wrapped_sql, "asset", args.clone())?;
+        build_pivot_query(&wrapped_sql, "asset", &["y_axis"], &RESOLUTION_PIVOT_INDEX, &series)?
+    } else {
+        with_resolution_order(&wrapped_sql, options.order_by.as_deref())?
+    };
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, wrapped_sql.clone(), args)?;
 
-    return serialize_recordbatch(res.0, res.1);
+    let mut metadata: HashMap<String, String> = debug_sql_metadata(&wrapped_sql, options.include_sql);
+    metadata.insert(RESOLUTION_METADATA_KEY.to_string(), resolution.to_string());
+    return serialize_recordbatch_with_metadata(res.0, res.1, metadata);
 
 }
 
 // --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{setup_test_db, setup_tulipa_db, response_to_batches, column_f64};
+
+    // 'battery' has a short-term price of 4 and a long-term price of 2 over the same two hour period
+    const BOTH_STORAGE_FIXTURE_SQL: &str = "
+        CREATE TABLE asset(asset VARCHAR, type VARCHAR);
+        INSERT INTO asset VALUES ('battery', 'storage');
+        CREATE TABLE cons_balance_storage_rep_period(asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, dual_balance_storage_rep_period DOUBLE);
+        INSERT INTO cons_balance_storage_rep_period VALUES ('battery', 2030, 1, 1, 2, 4);
+        CREATE TABLE cons_balance_storage_over_clustered_year(asset VARCHAR, year INTEGER, period_block_start INTEGER, period_block_end INTEGER, dual_balance_storage_over_clustered_year DOUBLE);
+        INSERT INTO cons_balance_storage_over_clustered_year VALUES ('battery', 2030, 1, 1, 2);
+        CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+        INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 1);
+        CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+        INSERT INTO rep_periods_data VALUES (2030, 1, 2, 1);
+    ";
+
+    fn both_prices(name: &str, combine: Option<&str>) -> Result<Vec<Option<f64>>, String> {
+        let db_path: String = setup_test_db(name, BOTH_STORAGE_FIXTURE_SQL);
+        let response: Response = get_storage_price_resolution(
+            db_path, 2030, 2, "both".to_string(), "all".to_string(), HashMap::new(), vec![], false,
+            Some(ResolutionOptions { combine: combine.map(str::to_string), ..Default::default() }),
+        )?;
+        Ok(column_f64(&response_to_batches(response), "y_axis"))
+    }
+
+    #[test]
+    fn get_storage_price_resolution_combine_test() {
+        assert_eq!(both_prices("storage_price_combine_sum", Some("sum")), Ok(vec![Some(6.0)]));
+        assert_eq!(both_prices("storage_price_combine_avg", Some("avg")), Ok(vec![Some(3.0)]));
+        assert_eq!(both_prices("storage_price_combine_max", Some("max")), Ok(vec![Some(4.0)]));
+    }
+
+    #[test]
+    fn get_storage_price_resolution_invalid_combine_test() {
+        let err: String = both_prices("storage_price_combine_invalid", Some("min")).unwrap_err();
+        assert!(err.contains("Invalid combination 'min'"));
+    }
+
+    #[test]
+    fn get_storage_price_resolution_tulipa_fixture_test() {
+        let db_path: String = setup_tulipa_db("storage_price_tulipa_fixture", "");
+        let batches = response_to_batches(get_storage_price_resolution(
+            db_path, 2030, 1, "short-term".to_string(), "all".to_string(), HashMap::new(), vec![], false, None,
+        ).unwrap());
+
+        assert_eq!(column_f64(&batches, "y_axis"), vec![Some(3.0), Some(5.0)]);
+    }
+}
 
 // --- QUERIES ---
 
@@ -218,7 +325,7 @@ const SHORT_TERM_SQL: &str = "
                 st.rep_period,
                 st.time_block_start,
                 st.time_block_end,
-                dual_value
+                dual_value{per_asset_group_by}
                 {breakdown_group_by},
                 CASE 
                 {breakdown_case_conditions}
@@ -243,7 +350,7 @@ const LONG_TERM_SQL: &str = "
                 st_cl.year,
                 st_cl.period_block_start,
                 st_cl.period_block_end,
-                dual_value
+                dual_value{per_asset_group_by}
                 {breakdown_group_by},
                 CASE 
                 {breakdown_case_conditions}
@@ -1,10 +1,12 @@
-use duckdb::arrow::{array::RecordBatch, datatypes::Schema};
+use duckdb::{ arrow::{array::RecordBatch, datatypes::Schema}, types::Value, Row };
 use tauri::ipc::Response;
-use crate::duckdb_conn::{run_query_rb, serialize_recordbatch};
-use crate::services::metadata::check_column_in_table;
+use crate::duckdb_conn::{run_query_rb, run_query_row, serialize_recordbatch};
+use crate::services::metadata::{check_column_in_table, check_series_cap, ensure_metadata_available, with_milestone_fallback};
 use crate::services::query_builder::{build_filter_conditions,
 build_breakdown_joins,
-build_breakdown_case_conditions};
+build_breakdown_case_conditions,
+with_category_descendants,
+with_rounding};
 use std::collections::HashMap;
 
 #[tauri::command]
@@ -12,39 +14,80 @@ pub fn get_fixed_asset_cost(
     db_path: String,
     filters: HashMap<i32, Vec<i32>>,
     grouper: Vec<i32>,
-    enable_metadata: bool
+    enable_metadata: bool,
+    round_digits: Option<u32>,
+    year: Option<u32>,
+    asset_whitelist: Option<Vec<String>>,
+    max_series: Option<u32>
 ) -> Result<Response, String> {
     println!("querying system costs (fixed asset)");
+    let (sql, args): (String, Vec<Value>) = build_fixed_asset_cost_query(&db_path, filters, &grouper, enable_metadata, round_digits, year, asset_whitelist.as_deref())?;
+    check_series_cap(&db_path, &sql, "asset", args.clone(), max_series)?;
 
-    let mut sql =  FIXED_ASSET_COST_SQL.to_string()
-                .replace("{discount_factor_assets_cte}", &DISCOUNT_FACTOR_ASSETS_CTE)
-                .replace("{breakdown_joins}", &build_breakdown_joins(&grouper))
-                .replace("{breakdown_case_conditions}",  &build_breakdown_case_conditions(&grouper, "a.asset".to_string()));
-    
-    if enable_metadata {
-        sql = sql.replace("{filtered_assets}", &format!("SELECT * FROM asset AS a WHERE 1 {}", build_filter_conditions(&filters, "a.asset".to_string())));   
-    } else {
-        sql = sql.replace("{filtered_assets}", &"SELECT * FROM asset");
-    };
-    
-    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, sql, [].to_vec())?;
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, sql, args)?;
     println!("done!");
 
     return serialize_recordbatch(res.0, res.1);
 }
 
+// the fixed asset cost query of `get_fixed_asset_cost` with its parameters, shared with the analysis bundle
+pub fn build_fixed_asset_cost_query(
+    db_path: &str,
+    filters: HashMap<i32, Vec<i32>>,
+    grouper: &[i32],
+    enable_metadata: bool,
+    round_digits: Option<u32>,
+    year: Option<u32>,
+    asset_whitelist: Option<&[String]>
+) -> Result<(String, Vec<Value>), String> {
+    if enable_metadata {
+        ensure_metadata_available(db_path)?;
+    }
+    check_discount_rates(db_path, INVALID_ASSET_DISCOUNT_RATE_SQL)?;
+
+    let (filtered_assets, mut args): (String, Vec<Value>) = build_filtered_assets(&filters, enable_metadata, asset_whitelist);
+    let sql =  FIXED_ASSET_COST_SQL.to_string()
+                .replace("{discount_factor_assets_cte}", &DISCOUNT_FACTOR_ASSETS_CTE)
+                .replace("{breakdown_joins}", &build_breakdown_joins(grouper))
+                .replace("{breakdown_case_conditions}",  &build_breakdown_case_conditions(grouper, "a.asset".to_string()))
+                .replace("{filtered_assets}", &filtered_assets);
+    let shared_filters: HashMap<i32, Vec<i32>> = if enable_metadata { filters } else { HashMap::new() };
+    let sql: String = with_category_descendants(&sql, &shared_filters, grouper);
+    let (sql, year_args): (String, Vec<Value>) = with_year_filter(&sql, year);
+    args.extend(year_args);
+    let sql: String = with_rounding(&sql, &["assets_fixed_cost"], round_digits)?;
+    let sql: String = with_milestone_fallback(db_path, &sql)?;
+
+    Ok((sql, args))
+}
+
 #[tauri::command]
-pub fn get_fixed_flow_cost(db_path: String) -> Result<Response, String> {
+pub fn get_fixed_flow_cost(db_path: String, round_digits: Option<u32>, year: Option<u32>) -> Result<Response, String> {
     println!("querying flow costs (fixed)");
-    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, format!("{DISCOUNT_FACTOR_FLOWS_CTE}{FIXED_FLOW_COST_SQL}"), [].to_vec())?;
+    let (sql, args): (String, Vec<Value>) = build_fixed_flow_cost_query(&db_path, round_digits, year)?;
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, sql, args)?;
     println!("done!");
 
     return serialize_recordbatch(res.0, res.1);
 }
 
+// the fixed flow cost query of `get_fixed_flow_cost` with its parameters, shared with the analysis bundle
+pub fn build_fixed_flow_cost_query(db_path: &str, round_digits: Option<u32>, year: Option<u32>) -> Result<(String, Vec<Value>), String> {
+    check_discount_rates(db_path, INVALID_FLOW_DISCOUNT_RATE_SQL)?;
+    let (sql, args): (String, Vec<Value>) = with_year_filter(&format!("{DISCOUNT_FACTOR_FLOWS_CTE}{FIXED_FLOW_COST_SQL}"), year);
+    let sql: String = with_rounding(&sql, &["flow_fixed_cost"], round_digits)?;
+    let sql: String = with_milestone_fallback(db_path, &sql)?;
+
+    Ok((sql, args))
+}
+
 #[tauri::command]
-pub fn get_variable_flow_cost(db_path: String) -> Result<Response, String> {
-    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, format!("{DISCOUNT_FACTOR_FLOWS_CTE}{VARIABLE_FLOW_COST_SQL}"), [].to_vec())?;
+pub fn get_variable_flow_cost(db_path: String, round_digits: Option<u32>, year: Option<u32>) -> Result<Response, String> {
+    check_discount_rates(&db_path, INVALID_FLOW_DISCOUNT_RATE_SQL)?;
+    let (sql, args): (String, Vec<Value>) = with_year_filter(&format!("{DISCOUNT_FACTOR_FLOWS_CTE}{VARIABLE_FLOW_COST_SQL}"), year);
+    let sql: String = with_rounding(&sql, &["flow_variable_cost"], round_digits)?;
+    let sql: String = with_milestone_fallback(&db_path, &sql)?;
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, sql, args)?;
     println!("done!");
     return serialize_recordbatch(res.0, res.1);
 }
@@ -54,32 +97,211 @@ pub fn get_unit_on_cost(
     db_path: String, 
     filters: HashMap<i32, Vec<i32>>,
     grouper: Vec<i32>,
-    enable_metadata: bool
+    enable_metadata: bool,
+    round_digits: Option<u32>,
+    year: Option<u32>,
+    asset_whitelist: Option<Vec<String>>,
+    max_series: Option<u32>
 ) -> Result<Response, String> {
     println!("querying system costs (unit on)");
-    let sql = if check_column_in_table(db_path.clone(), "var_units_on", "solution") ? {
-        let intermediary_sql = UNIT_ON_COST_SQL.to_string()
+    if enable_metadata {
+        ensure_metadata_available(&db_path)?;
+    }
+    let (sql, mut args) = if check_column_in_table(db_path.clone(), "var_units_on", "solution") ? {
+        check_discount_rates(&db_path, INVALID_ASSET_DISCOUNT_RATE_SQL)?;
+        let (filtered_assets, args): (String, Vec<Value>) = build_filtered_assets(&filters, enable_metadata, asset_whitelist.as_deref());
+        let filtered_sql = UNIT_ON_COST_SQL.to_string()
                 .replace("{discount_factor_assets_cte}", &DISCOUNT_FACTOR_ASSETS_CTE)
                 .replace("{breakdown_joins}", &build_breakdown_joins(&grouper))
-                .replace("{breakdown_case_conditions}",  &build_breakdown_case_conditions(&grouper, "a.asset".to_string()));
-        if enable_metadata {
-            intermediary_sql.replace("{filtered_assets}", &format!("SELECT * FROM asset AS a WHERE 1 {}", build_filter_conditions(&filters, "a.asset".to_string())))
-        } else {
-            intermediary_sql.replace("{filtered_assets}", &"SELECT * FROM asset")
-        }
+                .replace("{breakdown_case_conditions}",  &build_breakdown_case_conditions(&grouper, "a.asset".to_string()))
+                .replace("{filtered_assets}", &filtered_assets);
+        let shared_filters: HashMap<i32, Vec<i32>> = if enable_metadata { filters } else { HashMap::new() };
+        (with_category_descendants(&filtered_sql, &shared_filters, &grouper), args)
     } else {
         println!("var_units_on doesn't have solution, falling to 0");
-        UNIT_ON_COST_SQL_FALLBACK.to_string()
+        // the fallback has no category filters, only the whitelist applies
+        let (filtered_assets, args): (String, Vec<Value>) = build_filtered_assets(&HashMap::new(), false, asset_whitelist.as_deref());
+        (UNIT_ON_COST_SQL_FALLBACK.replace("{filtered_assets}", &filtered_assets), args)
     };
+    let (sql, year_args): (String, Vec<Value>) = with_year_filter(&sql, year);
+    args.extend(year_args);
+    let sql: String = with_rounding(&sql, &["unit_on_cost"], round_digits)?;
+    let sql: String = with_milestone_fallback(&db_path, &sql)?;
+    check_series_cap(&db_path, &sql, "asset", args.clone(), max_series)?;
 
-
-    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, sql, [].to_vec())?;
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, sql, args)?;
     println!("done!");
 
     return serialize_recordbatch(res.0, res.1);
 }
 
+// the assets a cost query covers, replacing {filtered_assets}: those matching the category filters (with metadata
+// enabled) and, with a whitelist, only the listed ones; the asset names are bound, so the returned parameters go
+// before any parameter following {filtered_assets} in the query
+fn build_filtered_assets(filters: &HashMap<i32, Vec<i32>>, enable_metadata: bool, asset_whitelist: Option<&[String]>) -> (String, Vec<Value>) {
+    let mut sql: String = "SELECT * FROM asset AS a WHERE 1".to_string();
+    if enable_metadata {
+        sql.push_str(&format!(" {}", build_filter_conditions(filters, "a.asset".to_string())));
+    }
+
+    match asset_whitelist {
+        // an empty selection selects nothing
+        Some([]) => {
+            sql.push_str(" AND FALSE");
+            (sql, vec![])
+        }
+        Some(assets) => {
+            let placeholders: Vec<&str> = assets.iter().map(|_| "?").collect();
+            sql.push_str(&format!(" AND a.asset IN ({})", placeholders.join(", ")));
+            (sql, assets.iter().map(|asset| Value::from(asset.clone())).collect())
+        }
+        None => (sql, vec![]),
+    }
+}
+
+// restricts a cost query to a single milestone year, bound as its only parameter; all years when `None`
+fn with_year_filter(sql: &str, year: Option<u32>) -> (String, Vec<Value>) {
+    match year {
+        Some(year) => (sql.replace("{year_filter}", "\n    AND yd.year = ?"), vec![Value::from(year)]),
+        None => (sql.replace("{year_filter}", ""), vec![]),
+    }
+}
+
+// a discount rate of -1 divides by zero in the discount factor, so the costs are rejected instead of summing NaN/Inf
+fn check_discount_rates(db_path: &str, invalid_rates_sql: &str) -> Result<(), String> {
+    let invalid: Vec<String> = run_query_row(
+        db_path.to_string(),
+        invalid_rates_sql.to_string(),
+        vec![],
+        |row: &Row<'_>| Ok(row.get::<usize, String>(0)?),
+    )?;
+
+    if invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Invalid discount rate (must be greater than -1) for: {}", invalid.join(", ")))
+    }
+}
+
 // --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{setup_test_db, setup_tulipa_db, response_to_batches, column_f64, column_string};
+
+    // 'solar' has a zero lifetime, 'wind' none at all: both only count in their commission year 2030,
+    // 'old' was commissioned in 2020 with a zero lifetime and is no longer active in 2030
+    const FIXED_ASSET_COST_FIXTURE_SQL: &str = "
+        CREATE TABLE year_data(year INTEGER, is_milestone BOOLEAN);
+        INSERT INTO year_data VALUES (2030, TRUE);
+        CREATE TABLE asset(asset VARCHAR, type VARCHAR, capacity DOUBLE, capacity_storage_energy DOUBLE, discount_rate DOUBLE, technical_lifetime INTEGER);
+        INSERT INTO asset VALUES ('solar', 'producer', 10, 0, 0.05, 0), ('wind', 'producer', 5, 0, 0.05, NULL), ('old', 'producer', 100, 0, 0.05, 0);
+        CREATE TABLE asset_commission(asset VARCHAR, commission_year INTEGER, fixed_cost DOUBLE, fixed_cost_storage_energy DOUBLE);
+        INSERT INTO asset_commission VALUES ('solar', 2030, 2, 0), ('wind', 2030, 4, 0), ('old', 2020, 1, 0);
+        CREATE TABLE asset_both(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, initial_units DOUBLE, initial_storage_units DOUBLE);
+        INSERT INTO asset_both VALUES ('solar', 2030, 2030, 1, 0), ('wind', 2030, 2030, 1, 0), ('old', 2030, 2020, 1, 0);
+    ";
+
+    #[test]
+    fn get_fixed_asset_cost_zero_lifetime_test() {
+        let db_path: String = setup_test_db("fixed_asset_cost_zero_lifetime", FIXED_ASSET_COST_FIXTURE_SQL);
+        let batches = response_to_batches(get_fixed_asset_cost(db_path, HashMap::new(), vec![], false, None, None, None, None).unwrap());
+
+        // without breakdown there is one row per active asset: 10 * 2 and 5 * 4, undiscounted in the commission year
+        let costs: Vec<f64> = column_f64(&batches, "assets_fixed_cost").into_iter().flatten().collect();
+        assert_eq!(costs.len(), 2);
+        assert!(costs.iter().all(|c| c.is_finite() && *c == 20.0), "unexpected costs: {:?}", costs);
+    }
+
+    #[test]
+    fn get_fixed_asset_cost_single_year_test() {
+        // 'wind' lives on until 2040
+        let db_path: String = setup_test_db("fixed_asset_cost_single_year", &format!("{}
+            INSERT INTO year_data VALUES (2040, TRUE);
+            INSERT INTO asset_both VALUES ('wind', 2040, 2030, 1, 0);
+            UPDATE asset SET technical_lifetime = 20 WHERE asset = 'wind';
+        ", FIXED_ASSET_COST_FIXTURE_SQL));
+        let years = |year: Option<u32>| -> Vec<Option<f64>> {
+            column_f64(&response_to_batches(get_fixed_asset_cost(db_path.clone(), HashMap::new(), vec![], false, None, year, None, None).unwrap()), "milestone_year")
+        };
+
+        assert_eq!(years(None), vec![Some(2030.0), Some(2030.0), Some(2040.0)]);
+        assert_eq!(years(Some(2040)), vec![Some(2040.0)]);
+        assert_eq!(years(Some(2050)), Vec::<Option<f64>>::new());
+    }
+
+    #[test]
+    fn get_fixed_asset_cost_asset_whitelist_test() {
+        let db_path: String = setup_test_db("fixed_asset_cost_asset_whitelist", &format!("{}
+            INSERT INTO asset VALUES ('gas', 'producer', 1, 0, 0.05, 0);
+            INSERT INTO asset_commission VALUES ('gas', 2030, 7, 0);
+            INSERT INTO asset_both VALUES ('gas', 2030, 2030, 1, 0);
+        ", FIXED_ASSET_COST_FIXTURE_SQL));
+        let costs = |asset_whitelist: Option<Vec<String>>| -> Vec<f64> {
+            let mut costs: Vec<f64> = column_f64(
+                &response_to_batches(get_fixed_asset_cost(db_path.clone(), HashMap::new(), vec![], false, None, Some(2030), asset_whitelist, None).unwrap()),
+                "assets_fixed_cost",
+            ).into_iter().flatten().collect();
+            costs.sort_by(f64::total_cmp);
+            costs
+        };
+
+        // 'solar' and 'wind' cost 20 each, 'gas' 7
+        assert_eq!(costs(None), vec![7.0, 20.0, 20.0]);
+        assert_eq!(costs(Some(vec!["gas".to_string(), "wind".to_string()])), vec![7.0, 20.0]);
+        assert_eq!(costs(Some(vec![])), Vec::<f64>::new());
+    }
+
+    #[test]
+    fn get_fixed_asset_cost_invalid_discount_rate_test() {
+        let db_path: String = setup_test_db(
+            "fixed_asset_cost_invalid_rate",
+            &format!("{}UPDATE asset SET discount_rate = -1 WHERE asset = 'wind';", FIXED_ASSET_COST_FIXTURE_SQL),
+        );
+        let res = get_fixed_asset_cost(db_path, HashMap::new(), vec![], false, None, None, None, None);
+        assert!(res.is_err() && res.err().unwrap().contains("Invalid discount rate (must be greater than -1) for: wind"));
+    }
+
+    #[test]
+    fn get_fixed_flow_cost_directionality_test() {
+        // both lines have one unit of 10 MW at a fixed cost of 2, the electricity line can also be imported over
+        let db_path: String = setup_test_db("fixed_flow_cost_directionality", "
+            CREATE TABLE year_data(year INTEGER, is_milestone BOOLEAN);
+            INSERT INTO year_data VALUES (2030, TRUE);
+            CREATE TABLE flow(from_asset VARCHAR, to_asset VARCHAR, carrier VARCHAR, is_transport BOOLEAN, capacity DOUBLE, discount_rate DOUBLE, technical_lifetime INTEGER);
+            INSERT INTO flow VALUES ('nl', 'be', 'electricity', TRUE, 10, 0, 10), ('nl', 'de', 'hydrogen', TRUE, 10, 0, 10);
+            CREATE TABLE flow_commission(from_asset VARCHAR, to_asset VARCHAR, commission_year INTEGER, fixed_cost DOUBLE);
+            INSERT INTO flow_commission VALUES ('nl', 'be', 2030, 2), ('nl', 'de', 2030, 2);
+            CREATE TABLE flow_both(from_asset VARCHAR, to_asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, initial_export_units DOUBLE, initial_import_units DOUBLE);
+            INSERT INTO flow_both VALUES ('nl', 'be', 2030, 2030, 1, 1), ('nl', 'de', 2030, 2030, 1, 0);
+        ");
+        let batches = response_to_batches(get_fixed_flow_cost(db_path, None, None).unwrap());
+
+        let mut costs: Vec<(Option<String>, Option<f64>)> = column_string(&batches, "carrier").into_iter()
+            .zip(column_f64(&batches, "flow_fixed_cost"))
+            .collect();
+        costs.sort_by(|a, b| a.0.cmp(&b.0));
+        // the same physical line costs the same, a blanket halving would charge the hydrogen line only 10
+        assert_eq!(costs, vec![(Some("electricity".to_string()), Some(20.0)), (Some("hydrogen".to_string()), Some(20.0))]);
+    }
+
+    #[test]
+    fn get_fixed_asset_cost_tulipa_fixture_test() {
+        let db_path: String = setup_tulipa_db("fixed_asset_cost_tulipa_fixture", "");
+        let batches = response_to_batches(get_fixed_asset_cost(db_path, HashMap::new(), vec![7, 8], true, None, None, None, None).unwrap());
+
+        let mut costs: Vec<(Option<String>, Option<f64>)> = column_string(&batches, "asset").into_iter()
+            .zip(column_f64(&batches, "assets_fixed_cost"))
+            .collect();
+        costs.sort_by(|a, b| a.0.cmp(&b.0));
+        // 'battery' has no technology and also pays for its storage energy: 2 * 1 + 8 * 0.5
+        assert_eq!(costs, vec![
+            (Some("Other".to_string()), Some(6.0)),
+            (Some("renewable".to_string()), Some(10.0)),
+            (Some("thermal".to_string()), Some(15.0)),
+        ]);
+    }
+}
 
 // --- QUERIES ---
 
@@ -100,7 +322,7 @@ ComissionedFlowsByMilestoneYear AS (
         m.next_milestone_year,
         f.from_asset,
   		f.to_asset,
-        f.discount_rate,
+        COALESCE(f.discount_rate, 0) AS discount_rate,
         fc.commission_year
     FROM
         MilestoneYearsWithNext AS m
@@ -109,8 +331,9 @@ ComissionedFlowsByMilestoneYear AS (
     JOIN
         flow_commission AS fc ON f.from_asset = fc.from_asset AND f.to_asset = fc.to_asset
     WHERE fc.commission_year <= m.milestone_year
+        AND COALESCE(f.discount_rate, 0) > -1
 ),
-DiscountFactorPerYearAndFlow AS (
+RawDiscountFactorPerYearAndFlow AS (
     SELECT
         fm.milestone_year,
         fm.from_asset,
@@ -134,6 +357,9 @@ DiscountFactorPerYearAndFlow AS (
         fm.milestone_year,
         fm.from_asset,
   		fm.to_asset,
+),
+DiscountFactorPerYearAndFlow AS (
+    SELECT * FROM RawDiscountFactorPerYearAndFlow WHERE isfinite(discount_factor)
 )
 ";
 
@@ -158,7 +384,7 @@ JOIN
 JOIN 
 	DiscountFactorPerYearAndFlow AS df ON df.from_asset = vf.from_asset AND df.to_asset = vf.to_asset AND df.milestone_year = yd.year
 WHERE
-    yd.is_milestone = TRUE
+    yd.is_milestone = TRUE{year_filter}
 GROUP BY
     yd.year,
     f.carrier;
@@ -170,8 +396,11 @@ SELECT
     yd.year AS milestone_year,
     f.carrier,
     SUM(
-        0.5 * df.discount_factor * fc.fixed_cost * f.capacity *
-        (fb.initial_export_units + fb.initial_import_units)
+        -- a bidirectional line has as many import as export units for the same physical capacity, so the
+        -- units are halved; a unidirectional line (no import units) is paid in full for its export units
+        CASE WHEN COALESCE(fb.initial_import_units, 0) > 0 THEN 0.5 ELSE 1.0 END
+        * df.discount_factor * fc.fixed_cost * f.capacity *
+        (fb.initial_export_units + COALESCE(fb.initial_import_units, 0))
     ) AS flow_fixed_cost
 FROM
     year_data AS yd
@@ -183,9 +412,9 @@ JOIN
     flow_commission AS fc ON f.from_asset = fc.from_asset AND f.to_asset = fc.to_asset
 JOIN DiscountFactorPerYearAndFlow AS df ON df.from_asset = f.from_asset AND df.to_asset = f.to_asset AND df.milestone_year = yd.year
 WHERE
-    yd.is_milestone = TRUE
+    yd.is_milestone = TRUE{year_filter}
     AND f.is_transport = TRUE
-    AND yd.year BETWEEN fb.commission_year AND (fb.commission_year + f.technical_lifetime)
+    AND yd.year BETWEEN fb.commission_year AND (fb.commission_year + GREATEST(COALESCE(f.technical_lifetime, 0), 0))
 GROUP BY
     yd.year,
     f.carrier;
@@ -212,7 +441,7 @@ ComissionedAssetsByMilestoneYear AS (
         m.milestone_year,
         m.next_milestone_year,
         a.asset,
-        a.discount_rate,
+        COALESCE(a.discount_rate, 0) AS discount_rate,
         ac.commission_year
     FROM
         MilestoneYearsWithNext AS m
@@ -221,8 +450,11 @@ ComissionedAssetsByMilestoneYear AS (
     JOIN
         asset_commission AS ac ON a.asset = ac.asset
     WHERE ac.commission_year <= m.milestone_year
+        -- a missing discount rate means no discounting, a rate of -1 or below has no meaningful discount factor
+        -- (division by zero), such assets are rejected by the commands beforehand
+        AND COALESCE(a.discount_rate, 0) > -1
 ),
-DiscountFactorPerYearAndAsset AS (
+RawDiscountFactorPerYearAndAsset AS (
     -- Computes https://tulipaenergy.github.io/TulipaEnergyModel.jl/dev/40-formulation/#Discounting-Factor-for-Operation-Costs
     -- resulting a discount factor per (milestone year, asset) tuple
     -- Thus, one milestone year's costs also include the next non-milestone years' costs, accounting for inflation
@@ -253,6 +485,10 @@ DiscountFactorPerYearAndAsset AS (
     GROUP BY
         am.milestone_year,
         am.asset
+),
+DiscountFactorPerYearAndAsset AS (
+    -- NaN/Inf factors (e.g. from overflowing powers) would turn every total they are summed into garbage
+    SELECT * FROM RawDiscountFactorPerYearAndAsset WHERE isfinite(discount_factor)
 )
 ";
 
@@ -290,7 +526,7 @@ JOIN
     DiscountFactorPerYearAndAsset AS df ON yd.year = df.milestone_year AND a.asset = df.asset
 {breakdown_joins}
 WHERE
-    yd.is_milestone = TRUE
+    yd.is_milestone = TRUE{year_filter}
     AND a.unit_commitment = TRUE
 GROUP BY
     yd.year,
@@ -300,15 +536,29 @@ GROUP BY
     END;
 ";
 
+const INVALID_ASSET_DISCOUNT_RATE_SQL: &str = "
+SELECT asset
+FROM asset
+WHERE discount_rate <= -1
+ORDER BY asset;
+";
+
+const INVALID_FLOW_DISCOUNT_RATE_SQL: &str = "
+SELECT from_asset || ' -> ' || to_asset
+FROM flow
+WHERE discount_rate <= -1
+ORDER BY from_asset, to_asset;
+";
+
 const UNIT_ON_COST_SQL_FALLBACK: &str = "
 SELECT 
     yd.year AS milestone_year,
     a.asset,
     0 AS unit_on_cost
 FROM
-    year_data AS yd, asset AS a
+    year_data AS yd, ({filtered_assets}) AS a
 WHERE
-    yd.is_milestone = TRUE
+    yd.is_milestone = TRUE{year_filter}
     AND a.unit_commitment = TRUE
 GROUP BY
     yd.year,
@@ -345,8 +595,9 @@ JOIN
     DiscountFactorPerYearAndAsset AS df ON yd.year = df.milestone_year AND a.asset = df.asset
 {breakdown_joins}
 WHERE
-    yd.is_milestone = TRUE
-    AND yd.year BETWEEN ab.commission_year AND (ab.commission_year + a.technical_lifetime)
+    yd.is_milestone = TRUE{year_filter}
+    -- a missing or zero technical lifetime means the asset only lives in its commission year
+    AND yd.year BETWEEN ab.commission_year AND (ab.commission_year + GREATEST(COALESCE(a.technical_lifetime, 0), 0))
 GROUP BY
     yd.year,
     CASE 
@@ -1,15 +1,29 @@
 use duckdb::{ arrow::{array::RecordBatch, datatypes::Schema}, types::Value };
 use tauri::ipc::Response;
-use crate::{duckdb_conn::{run_query_rb, serialize_recordbatch}, services::metadata::check_column_in_table};
-use crate::services::query_builder::build_resolution_query;
+use std::collections::HashMap;
+use crate::{duckdb_conn::{debug_sql_metadata, run_query_rb, serialize_recordbatch, serialize_recordbatch_with_metadata, RESOLUTION_METADATA_KEY}, services::metadata::{check_column_in_table, check_table_exists, fetch_pivot_series, resolve_resolution}};
+use crate::services::query_builder::{build_resolution_query, with_resolution_order, with_rounding, with_seasonal, with_clamped_negatives, with_timestamps, with_zeroed_values, build_pivot_query, RESOLUTION_PIVOT_INDEX, ResolutionOptions};
 
 #[tauri::command]
-pub fn get_transportation_price_resolution(db_path: String, year: u32, carrier: String, resolution: u32, column_type: String) -> Result<Response, String> {
+pub fn get_transportation_price_resolution(
+    db_path: String,
+    year: u32,
+    carrier: String,
+    resolution: u32,
+    column_type: String,
+    options: Option<ResolutionOptions>,
+) -> Result<Response, String> {
+    let options: ResolutionOptions = options.unwrap_or_default();
+    let resolution: u32 = resolve_resolution(&db_path, year, resolution)?;
 let dual = &format!("dual_{}_transport_flow_limit_simple_method", column_type);
     let pre_table_sql: String;
     let wrapped_sql: String;
 
-    if check_column_in_table(db_path.clone(), "cons_transport_flow_limit_simple_method", dual)? {
+    if !check_table_exists(db_path.clone(), "cons_transport_flow_limit_simple_method")? {
+        // no transport constraints at all: an empty result with the regular columns
+        wrapped_sql = EMPTY_TRANSPORT_SQL.to_string();
+    }
+    else if check_column_in_table(db_path.clone(), "cons_transport_flow_limit_simple_method", dual)? {
     let carrier_filter = if carrier == "all" {
     ""
 } else {
@@ -28,6 +42,7 @@ let dual = &format!("dual_{}_transport_flow_limit_simple_method", column_type);
         JOIN flow AS f ON f.from_asset = tr.from_asset AND f.to_asset = tr.to_asset
         {}
     ", column_type, carrier_filter);
+    let pre_table_sql: String = with_zeroed_values(&pre_table_sql, &["dual_value"], options.zero_epsilon)?;
     let sql = build_resolution_query(
         "transportation_table",
         "dual_value",
@@ -35,7 +50,9 @@ let dual = &format!("dual_{}_transport_flow_limit_simple_method", column_type);
         "avg",
         &resolution.to_string(),
         false,
-    ).trim_end_matches(';').trim_end().to_string();
+        options.merge_digits,
+        "year",
+    )?.trim_end_matches(';').trim_end().to_string();
     wrapped_sql = format!(
     "
     WITH transportation_table AS (
@@ -60,9 +77,26 @@ let dual = &format!("dual_{}_transport_flow_limit_simple_method", column_type);
     FROM cons_transport_flow_limit_simple_method
     ", carrier);
     }
-    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, wrapped_sql, vec![Value::from(year)])?;
+    let wrapped_sql: String = with_seasonal(&wrapped_sql, "carrier", options.seasonal, options.order_by.as_deref(), options.pivot, resolution)?;
+    let wrapped_sql: String = with_clamped_negatives(&wrapped_sql, &["y_axis"], options.clamp_negative)?;
+    let wrapped_sql: String = with_rounding(&wrapped_sql, &["y_axis"], options.round_digits)?;
+    let wrapped_sql: String = with_timestamps(&wrapped_sql, options.start_date.as_deref(), options.seasonal.is_some(), resolution)?;
+    let wrapped_sql: String = if options.seasonal.is_some() {
+        wrapped_sql
+    } else if options.pivot {
+        if options.order_by.is_some() {
+            return Err("Custom ordering is not supported on pivoted results".to_string());
+        }
+        let series: Vec<String> = fetch_pivot_series(&db_path, &wrapped_sql, "carrier", vec![Value::from(year)])?;
+        build_pivot_query(&wrapped_sql, "carrier", &["y_axis"], &RESOLUTION_PIVOT_INDEX, &series)?
+    } else {
+        with_resolution_order(&wrapped_sql, options.order_by.as_deref())?
+    };
+    let res: (Vec<RecordBatch>, Schema) = run_query_rb(db_path, wrapped_sql.clone(), vec![Value::from(year)])?;
 
-    return serialize_recordbatch(res.0, res.1);
+    let mut metadata: HashMap<String, String> = debug_sql_metadata(&wrapped_sql, options.include_sql);
+    metadata.insert(RESOLUTION_METADATA_KEY.to_string(), resolution.to_string());
+    return serialize_recordbatch_with_metadata(res.0, res.1, metadata);
 
 }
 
@@ -74,8 +108,172 @@ pub fn get_transportation_carriers(db_path: String) -> Result<Response, String>
 }
 
 // --- TESTING ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duckdb_conn::SQL_METADATA_KEY;
+    use crate::services::metadata::AUTO_RESOLUTION;
+    use crate::services::query_builder::SeasonMode;
+    use std::io::Cursor;
+    use arrow_ipc::reader::StreamReader;
+    use crate::test_utils::{setup_test_db, setup_tulipa_db, response_to_batches, num_rows, column_f64};
+
+    // one representative period of 4 hourly time blocks, each with a different dual value
+    const TRANSPORT_FIXTURE_SQL: &str = "
+        CREATE TABLE flow(from_asset VARCHAR, to_asset VARCHAR, carrier VARCHAR, is_transport BOOLEAN);
+        INSERT INTO flow VALUES ('nl', 'be', 'electricity', TRUE);
+        CREATE TABLE cons_transport_flow_limit_simple_method(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER,
+            time_block_start INTEGER, time_block_end INTEGER, dual_max_transport_flow_limit_simple_method DOUBLE);
+        INSERT INTO cons_transport_flow_limit_simple_method VALUES
+            ('nl', 'be', 2030, 1, 1, 1, 1), ('nl', 'be', 2030, 1, 2, 2, 3), ('nl', 'be', 2030, 1, 3, 3, 2), ('nl', 'be', 2030, 1, 4, 4, 5);
+        CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+        INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 1);
+        CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+        INSERT INTO rep_periods_data VALUES (2030, 1, 4, 1);
+    ";
+
+    #[test]
+    fn get_transportation_price_resolution_default_order_test() {
+        let db_path: String = setup_test_db("transport_price_default_order", TRANSPORT_FIXTURE_SQL);
+        let batches = response_to_batches(get_transportation_price_resolution(
+            db_path, 2030, "all".to_string(), 1, "max".to_string(), None,
+        ).unwrap());
+
+        assert_eq!(column_f64(&batches, "global_start"), vec![Some(0.0), Some(1.0), Some(2.0), Some(3.0)]);
+        assert!(batches[0].schema().metadata().get(SQL_METADATA_KEY).is_none(), "SQL must not be included by default");
+    }
+
+    #[test]
+    fn get_transportation_price_resolution_include_sql_test() {
+        let db_path: String = setup_test_db("transport_price_include_sql", TRANSPORT_FIXTURE_SQL);
+        let batches = response_to_batches(get_transportation_price_resolution(
+            db_path, 2030, "all".to_string(), 1, "max".to_string(),
+            Some(ResolutionOptions { order_by: Some("y_axis DESC".to_string()), include_sql: true, ..Default::default() }),
+        ).unwrap());
+
+        let schema = batches[0].schema();
+        let sql: &String = schema.metadata().get(SQL_METADATA_KEY).expect("missing generated SQL");
+        assert!(sql.contains("FROM cons_transport_flow_limit_simple_method AS tr"));
+        assert!(sql.trim_end().ends_with("ORDER BY y_axis DESC"));
+        assert_eq!(column_f64(&batches, "y_axis"), vec![Some(5.0), Some(3.0), Some(2.0), Some(1.0)]);
+    }
+
+    #[test]
+    fn get_transportation_price_resolution_auto_test() {
+        let db_path: String = setup_test_db("transport_price_auto_resolution", TRANSPORT_FIXTURE_SQL);
+        let batches = response_to_batches(get_transportation_price_resolution(
+            db_path, 2030, "all".to_string(), AUTO_RESOLUTION, "max".to_string(), None,
+        ).unwrap());
+
+        // the fixture only spans 4 hours, so the chosen resolution stays hourly
+        assert_eq!(batches[0].schema().metadata().get(RESOLUTION_METADATA_KEY), Some(&"1".to_string()));
+        assert_eq!(column_f64(&batches, "y_axis"), vec![Some(1.0), Some(3.0), Some(2.0), Some(5.0)]);
+    }
+
+    // a full non-leap year of hourly time blocks, the dual value is the month number of the hour
+    const FULL_YEAR_TRANSPORT_FIXTURE_SQL: &str = "
+        CREATE TABLE flow(from_asset VARCHAR, to_asset VARCHAR, carrier VARCHAR, is_transport BOOLEAN);
+        INSERT INTO flow VALUES ('nl', 'be', 'electricity', TRUE);
+        CREATE TABLE cons_transport_flow_limit_simple_method(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER,
+            time_block_start INTEGER, time_block_end INTEGER, dual_max_transport_flow_limit_simple_method DOUBLE);
+        INSERT INTO cons_transport_flow_limit_simple_method
+            SELECT 'nl', 'be', 2030, 1, h, h, month(TIMESTAMP '2001-01-01' + to_hours(h - 1))
+            FROM range(1, 8761) AS hours(h);
+        CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+        INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 1);
+        CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+        INSERT INTO rep_periods_data VALUES (2030, 1, 8760, 1);
+    ";
+
+    #[test]
+    fn get_transportation_price_resolution_monthly_test() {
+        let db_path: String = setup_test_db("transport_price_monthly", FULL_YEAR_TRANSPORT_FIXTURE_SQL);
+        let batches = response_to_batches(get_transportation_price_resolution(
+            db_path, 2030, "all".to_string(), 24, "max".to_string(),
+            Some(ResolutionOptions { seasonal: Some(SeasonMode::Month), ..Default::default() }),
+        ).unwrap());
+
+        let months: Vec<Option<f64>> = (1..=12).map(|m| Some(m as f64)).collect();
+        assert_eq!(column_f64(&batches, "month"), months);
+        assert_eq!(column_f64(&batches, "y_axis"), months);
+    }
+
+    #[test]
+    fn get_transportation_price_resolution_seasonal_with_order_test() {
+        let db_path: String = setup_test_db("transport_price_seasonal_order", FULL_YEAR_TRANSPORT_FIXTURE_SQL);
+        let res = get_transportation_price_resolution(
+            db_path, 2030, "all".to_string(), 24, "max".to_string(),
+            Some(ResolutionOptions { order_by: Some("y_axis DESC".to_string()), seasonal: Some(SeasonMode::Season), ..Default::default() }),
+        );
+        assert!(res.err().unwrap().contains("Seasonal aggregation"));
+    }
+
+    #[test]
+    fn get_transportation_price_resolution_clamp_negative_test() {
+        let fixture: String = TRANSPORT_FIXTURE_SQL.to_string()
+            + "UPDATE cons_transport_flow_limit_simple_method SET dual_max_transport_flow_limit_simple_method = -4 WHERE time_block_start = 2;";
+        let db_path: String = setup_test_db("transport_price_clamp_negative", &fixture);
+        let prices = |clamp_negative: bool| column_f64(&response_to_batches(get_transportation_price_resolution(
+            db_path.clone(), 2030, "all".to_string(), 1, "max".to_string(),
+            Some(ResolutionOptions { clamp_negative, ..Default::default() }),
+        ).unwrap()), "y_axis");
+
+        assert_eq!(prices(false), vec![Some(1.0), Some(-4.0), Some(2.0), Some(5.0)]);
+        assert_eq!(prices(true), vec![Some(1.0), Some(0.0), Some(2.0), Some(5.0)]);
+    }
+
+    #[test]
+    fn get_transportation_price_resolution_descending_order_test() {
+        let db_path: String = setup_test_db("transport_price_descending_order", TRANSPORT_FIXTURE_SQL);
+        let batches = response_to_batches(get_transportation_price_resolution(
+            db_path, 2030, "all".to_string(), 1, "max".to_string(),
+            Some(ResolutionOptions { order_by: Some("y_axis DESC".to_string()), ..Default::default() }),
+        ).unwrap());
+
+        assert_eq!(column_f64(&batches, "y_axis"), vec![Some(5.0), Some(3.0), Some(2.0), Some(1.0)]);
+    }
+
+    #[test]
+    fn get_transportation_price_resolution_no_transport_table_test() {
+        let db_path: String = setup_test_db("transport_price_no_transport_table", "
+            CREATE TABLE flow(from_asset VARCHAR, to_asset VARCHAR, carrier VARCHAR, is_transport BOOLEAN);
+            CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+            CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+        ");
+        let response: Response = get_transportation_price_resolution(
+            db_path, 2030, "all".to_string(), 1, "max".to_string(), None,
+        ).unwrap();
+
+        let serial_data: Vec<u8> = Response::body(response).unwrap().deserialize().unwrap();
+        let reader = StreamReader::try_new(Cursor::new(serial_data), None).unwrap();
+        let schema = reader.schema();
+        let columns: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(columns, vec!["carrier", "milestone_year", "global_start", "global_end", "y_axis"]);
+        assert_eq!(num_rows(&reader.filter_map(Result::ok).collect::<Vec<_>>()), 0);
+    }
+
+    #[test]
+    fn get_transportation_price_resolution_tulipa_fixture_test() {
+        let db_path: String = setup_tulipa_db("transport_price_tulipa_fixture", "");
+        let batches = response_to_batches(get_transportation_price_resolution(
+            db_path, 2030, "electricity".to_string(), 2, "max".to_string(), None,
+        ).unwrap());
+
+        // the duals 0 and 7 averaged over the single 2 hour period
+        assert_eq!(column_f64(&batches, "y_axis"), vec![Some(3.5)]);
+    }
+}
 
 // --- QUERIES ---
+const EMPTY_TRANSPORT_SQL: &str = "
+    SELECT
+        CAST(NULL AS VARCHAR) AS carrier,
+        CAST(? AS INTEGER) AS milestone_year,
+        CAST(NULL AS DOUBLE) AS global_start,
+        CAST(NULL AS DOUBLE) AS global_end,
+        CAST(NULL AS DOUBLE) AS y_axis
+    WHERE FALSE
+    ";
 const CARRIER_SQL: &str = "
     SELECT DISTINCT carrier
     FROM flow
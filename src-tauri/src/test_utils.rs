@@ -0,0 +1,182 @@
+// helpers shared by the service tests, which run against on-disk fixture databases
+use std::{io::Cursor, path::PathBuf};
+use arrow_ipc::reader::StreamReader;
+use duckdb::{ arrow::{array::{Array, Float64Array, RecordBatch, StringArray}, compute::cast, datatypes::DataType}, Connection };
+use tauri::ipc::{ IpcResponse, Response };
+
+// creates a fresh database file in the temp dir filled by `setup_sql`, returns its path
+// NOTE: names must be unique per test, the global connection pool keeps the file open
+pub fn setup_test_db(name: &str, setup_sql: &str) -> String {
+    let path: PathBuf = std::env::temp_dir().join(format!("tulipa_test_{}.duckdb", name));
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(path.with_extension("duckdb.wal"));
+
+    // connection is dropped at the end of the scope, releasing the file lock for the pool
+    let conn: Connection = Connection::open(&path).expect("could not create test database");
+    conn.execute_batch(setup_sql).expect("could not setup test database");
+
+    path.to_string_lossy().to_string()
+}
+
+// `setup_test_db` with the shared Tulipa-shaped fixture `TULIPA_FIXTURE_SQL`, `extra_sql` then adjusts it per test
+pub fn setup_tulipa_db(name: &str, extra_sql: &str) -> String {
+    setup_test_db(name, &format!("{}{}", TULIPA_FIXTURE_SQL, extra_sql))
+}
+
+// deserializes an arrow IPC response back into record batches
+pub fn response_to_batches(response: Response) -> Vec<RecordBatch> {
+    let serial_data: Vec<u8> = Response::body(response).unwrap().deserialize().unwrap();
+
+    StreamReader::try_new(Cursor::new(serial_data), None)
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect()
+}
+
+pub fn num_rows(batches: &[RecordBatch]) -> usize {
+    batches.iter().fold(0, |acc, rb| acc + rb.num_rows())
+}
+
+// reads a numeric column of every batch as f64, whatever its arrow type
+pub fn column_f64(batches: &[RecordBatch], name: &str) -> Vec<Option<f64>> {
+    batches.iter()
+        .flat_map(|rb| {
+            let col = rb.column_by_name(name).unwrap_or_else(|| panic!("missing column '{}'", name));
+            let casted = cast(col, &DataType::Float64).expect("cast to f64 failed");
+            casted.as_any()
+                .downcast_ref::<Float64Array>()
+                .expect("downcast failed")
+                .iter()
+                .collect::<Vec<_>>()
+        }).collect()
+}
+
+
+// reads a column of every batch as strings, whatever its arrow type
+pub fn column_string(batches: &[RecordBatch], name: &str) -> Vec<Option<String>> {
+    batches.iter()
+        .flat_map(|rb| {
+            let col = rb.column_by_name(name).unwrap_or_else(|| panic!("missing column '{}'", name));
+            let casted = cast(col, &DataType::Utf8).expect("cast to string failed");
+            casted.as_any()
+                .downcast_ref::<StringArray>()
+                .expect("downcast failed")
+                .iter()
+                .map(|v| v.map(str::to_string))
+                .collect::<Vec<_>>()
+        }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query_i64(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get::<usize, i64>(0)).expect("fixture query failed")
+    }
+
+    #[test]
+    fn setup_tulipa_db_test() {
+        let path: String = setup_tulipa_db("tulipa_fixture", "INSERT INTO asset VALUES ('nuclear', 'producer', 1, 0, 0, 40);");
+        let conn: Connection = Connection::open(&path).unwrap();
+
+        for table in TULIPA_FIXTURE_TABLES {
+            let exists: i64 = query_i64(&conn, &format!("SELECT COUNT(*) FROM information_schema.tables WHERE table_name = '{}'", table));
+            assert_eq!(exists, 1, "missing fixture table '{}'", table);
+        }
+        assert_eq!(query_i64(&conn, "SELECT COUNT(*) FROM asset"), 9);
+
+        // hubs only pass energy on, in every time block
+        let unbalanced: i64 = query_i64(&conn, "
+            SELECT COUNT(*) FROM (
+                SELECT a.asset, vf.time_block_start
+                FROM asset AS a
+                JOIN var_flow AS vf ON a.asset IN (vf.from_asset, vf.to_asset)
+                WHERE a.type = 'hub'
+                GROUP BY a.asset, vf.time_block_start
+                HAVING SUM(CASE WHEN vf.to_asset = a.asset THEN vf.solution ELSE -vf.solution END) <> 0
+            )
+        ");
+        assert_eq!(unbalanced, 0);
+    }
+}
+
+// the tables of `TULIPA_FIXTURE_SQL`
+const TULIPA_FIXTURE_TABLES: [&str; 18] = [
+    "year_data", "rep_periods_mapping", "rep_periods_data", "category", "asset", "asset_category", "flow", "var_flow",
+    "asset_both", "asset_commission", "var_assets_investment", "var_assets_decommission", "flow_both", "flow_commission",
+    "cons_capacity_outgoing_simple_method", "cons_balance_storage_rep_period", "cons_balance_storage_over_clustered_year",
+    "cons_transport_flow_limit_simple_method",
+];
+
+// one milestone year (2030) of a single representative period of two hourly time blocks, weight 1.
+// Electricity: 'solar' and 'ccgt' feed 'hub_nl', which transports to 'hub_be', where 'battery' charges in the first
+// hour, discharges in the second and 'demand' consumes 4 then 6. Gas: 'gas_well' feeds 'ccgt' (efficiency 0.5).
+// 'wind' is not built and has no results. Categories: the countries NL and BE under 'location', with the nodes as
+// leaves, and 'renewable' and 'thermal' under 'technology'; 'battery' and the hubs have no technology
+pub const TULIPA_FIXTURE_SQL: &str = "
+    CREATE TABLE year_data(year INTEGER, length INTEGER, is_milestone BOOLEAN);
+    INSERT INTO year_data VALUES (2030, 8760, TRUE);
+    CREATE TABLE rep_periods_mapping(year INTEGER, period INTEGER, rep_period INTEGER, weight DOUBLE);
+    INSERT INTO rep_periods_mapping VALUES (2030, 1, 1, 1);
+    CREATE TABLE rep_periods_data(year INTEGER, rep_period INTEGER, num_timesteps INTEGER, resolution DOUBLE);
+    INSERT INTO rep_periods_data VALUES (2030, 1, 2, 1);
+
+    CREATE TABLE category(id INTEGER, name VARCHAR, parent_id INTEGER, level INTEGER);
+    INSERT INTO category VALUES (1, 'location', NULL, 2), (2, 'NL', 1, 1), (3, 'BE', 1, 1), (4, 'NL_nodes', 2, 0), (5, 'BE_nodes', 3, 0),
+        (6, 'technology', NULL, 1), (7, 'renewable', 6, 0), (8, 'thermal', 6, 0);
+    CREATE TABLE asset(asset VARCHAR, type VARCHAR, capacity DOUBLE, capacity_storage_energy DOUBLE, discount_rate DOUBLE, technical_lifetime INTEGER);
+    INSERT INTO asset VALUES
+        ('solar', 'producer', 10, 0, 0, 30), ('wind', 'producer', 5, 0, 0, 30), ('gas_well', 'producer', 10, 0, 0, 30),
+        ('ccgt', 'conversion', 5, 0, 0, 30), ('battery', 'storage', 2, 8, 0, 15),
+        ('hub_nl', 'hub', 0, 0, 0, NULL), ('hub_be', 'hub', 0, 0, 0, NULL), ('demand', 'consumer', 0, 0, 0, NULL);
+    CREATE TABLE asset_category(asset VARCHAR, root_id INTEGER, leaf_id INTEGER);
+    INSERT INTO asset_category VALUES
+        ('solar', 1, 4), ('wind', 1, 4), ('gas_well', 1, 4), ('ccgt', 1, 4), ('hub_nl', 1, 4),
+        ('battery', 1, 5), ('hub_be', 1, 5), ('demand', 1, 5),
+        ('solar', 6, 7), ('wind', 6, 7), ('gas_well', 6, 8), ('ccgt', 6, 8);
+
+    CREATE TABLE flow(from_asset VARCHAR, to_asset VARCHAR, carrier VARCHAR, is_transport BOOLEAN, capacity DOUBLE, discount_rate DOUBLE, technical_lifetime INTEGER);
+    INSERT INTO flow VALUES
+        ('solar', 'hub_nl', 'electricity', FALSE, 0, 0, NULL), ('gas_well', 'ccgt', 'gas', FALSE, 0, 0, NULL),
+        ('ccgt', 'hub_nl', 'electricity', FALSE, 0, 0, NULL), ('hub_nl', 'hub_be', 'electricity', TRUE, 10, 0, 30),
+        ('hub_be', 'battery', 'electricity', FALSE, 0, 0, NULL), ('battery', 'hub_be', 'electricity', FALSE, 0, 0, NULL),
+        ('hub_be', 'demand', 'electricity', FALSE, 0, 0, NULL);
+    CREATE TABLE var_flow(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER, time_block_start INTEGER, time_block_end INTEGER, solution DOUBLE);
+    INSERT INTO var_flow VALUES
+        ('solar', 'hub_nl', 2030, 1, 1, 1, 4), ('solar', 'hub_nl', 2030, 1, 2, 2, 2),
+        ('gas_well', 'ccgt', 2030, 1, 1, 1, 2), ('gas_well', 'ccgt', 2030, 1, 2, 2, 4),
+        ('ccgt', 'hub_nl', 2030, 1, 1, 1, 1), ('ccgt', 'hub_nl', 2030, 1, 2, 2, 2),
+        ('hub_nl', 'hub_be', 2030, 1, 1, 1, 5), ('hub_nl', 'hub_be', 2030, 1, 2, 2, 4),
+        ('hub_be', 'battery', 2030, 1, 1, 1, 1), ('hub_be', 'battery', 2030, 1, 2, 2, 0),
+        ('battery', 'hub_be', 2030, 1, 1, 1, 0), ('battery', 'hub_be', 2030, 1, 2, 2, 2),
+        ('hub_be', 'demand', 2030, 1, 1, 1, 4), ('hub_be', 'demand', 2030, 1, 2, 2, 6);
+
+    CREATE TABLE asset_both(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, initial_units DOUBLE, initial_storage_units DOUBLE);
+    INSERT INTO asset_both VALUES
+        ('solar', 2030, 2030, 1, 0), ('gas_well', 2030, 2030, 1, 0), ('ccgt', 2030, 2030, 1, 0), ('battery', 2030, 2030, 1, 1);
+    CREATE TABLE asset_commission(asset VARCHAR, commission_year INTEGER, fixed_cost DOUBLE, fixed_cost_storage_energy DOUBLE, investment_cost DOUBLE);
+    INSERT INTO asset_commission VALUES
+        ('solar', 2030, 1, 0, 100), ('gas_well', 2030, 0.5, 0, 50), ('ccgt', 2030, 2, 0, 80), ('battery', 2030, 1, 0.5, 60);
+    CREATE TABLE var_assets_investment(asset VARCHAR, milestone_year INTEGER, solution DOUBLE);
+    INSERT INTO var_assets_investment VALUES ('solar', 2030, 1);
+    CREATE TABLE var_assets_decommission(asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, solution DOUBLE);
+    CREATE TABLE flow_both(from_asset VARCHAR, to_asset VARCHAR, milestone_year INTEGER, commission_year INTEGER, initial_export_units DOUBLE, initial_import_units DOUBLE);
+    INSERT INTO flow_both VALUES ('hub_nl', 'hub_be', 2030, 2030, 1, 1);
+    CREATE TABLE flow_commission(from_asset VARCHAR, to_asset VARCHAR, commission_year INTEGER, fixed_cost DOUBLE);
+    INSERT INTO flow_commission VALUES ('hub_nl', 'hub_be', 2030, 2);
+
+    CREATE TABLE cons_capacity_outgoing_simple_method(asset VARCHAR, year INTEGER, rep_period INTEGER,
+        time_block_start INTEGER, time_block_end INTEGER, dual_max_output_flows_limit_simple_method DOUBLE);
+    INSERT INTO cons_capacity_outgoing_simple_method VALUES
+        ('solar', 2030, 1, 1, 1, 10), ('solar', 2030, 1, 2, 2, 10), ('gas_well', 2030, 1, 1, 1, 20), ('gas_well', 2030, 1, 2, 2, 20),
+        ('ccgt', 2030, 1, 1, 1, 50), ('ccgt', 2030, 1, 2, 2, 65), ('battery', 2030, 1, 1, 1, 30), ('battery', 2030, 1, 2, 2, 30);
+    CREATE TABLE cons_balance_storage_rep_period(asset VARCHAR, year INTEGER, rep_period INTEGER,
+        time_block_start INTEGER, time_block_end INTEGER, dual_balance_storage_rep_period DOUBLE);
+    INSERT INTO cons_balance_storage_rep_period VALUES ('battery', 2030, 1, 1, 1, 3), ('battery', 2030, 1, 2, 2, 5);
+    CREATE TABLE cons_balance_storage_over_clustered_year(asset VARCHAR, year INTEGER, period_block_start INTEGER,
+        period_block_end INTEGER, dual_balance_storage_over_clustered_year DOUBLE);
+    CREATE TABLE cons_transport_flow_limit_simple_method(from_asset VARCHAR, to_asset VARCHAR, year INTEGER, rep_period INTEGER,
+        time_block_start INTEGER, time_block_end INTEGER, dual_max_transport_flow_limit_simple_method DOUBLE, dual_min_transport_flow_limit_simple_method DOUBLE);
+    INSERT INTO cons_transport_flow_limit_simple_method VALUES ('hub_nl', 'hub_be', 2030, 1, 1, 1, 0, 0), ('hub_nl', 'hub_be', 2030, 1, 2, 2, 7, 0);
+";